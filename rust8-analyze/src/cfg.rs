@@ -0,0 +1,409 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use rust8_core::emu::Emulator;
+use rust8_core::error::Error;
+
+/// where execution starts
+pub const ENTRY: u16 = 0x200;
+
+/// longest Bnnn jump table followed
+const MAX_JUMP_TABLE: u16 = 128;
+
+/// values of I tracked at once before giving up on knowing it
+const MAX_INDEX_VALUES: usize = 16;
+
+/// How control gets from one instruction to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edge {
+    /// straight on to the next instruction
+    Next,
+    /// 1nnn
+    Jump,
+    /// a skip instruction skipping
+    Skip,
+    /// Bnnn, to its base address or an entry of a jump table there
+    Indirect,
+    /// from a call to the instruction after it, once the subroutine returns
+    Return,
+}
+
+/// A reachable instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: u16,
+    /// where control can go next within the same subroutine
+    pub successors: Vec<(u16, Edge)>,
+    /// the subroutine a 2nnn calls
+    pub call: Option<u16>,
+}
+
+/// A straight line of instructions only ever entered at the top
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: u16,
+    /// address just past the last instruction
+    pub end: u16,
+    pub successors: Vec<(u16, Edge)>,
+}
+
+impl Block {
+    /// address of the last instruction
+    pub fn last(&self) -> u16 {
+        self.end - 2
+    }
+}
+
+/// The blocks reachable from a call target, or from the entry point, without following calls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: u16,
+    /// starts of the subroutine's blocks in address order
+    pub blocks: Vec<u16>,
+    /// subroutines it calls
+    pub callees: Vec<u16>,
+    /// whether any path ends in a RET
+    pub returns: bool,
+}
+
+/// Memory drawn by a DRW while I held its address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sprite {
+    pub address: u16,
+    /// tallest draw of it, in rows and so bytes
+    pub height: u8,
+    /// addresses of the DRW instructions
+    pub drawn_by: Vec<u16>,
+}
+
+impl Sprite {
+    pub fn bytes(&self) -> Range<u16> {
+        self.address..(self.address + u16::from(self.height)).min(4096)
+    }
+}
+
+/// An Fx55 or Fx33 that writes over reachable code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// address of the writing instruction
+    pub at: u16,
+    pub target: Range<u16>,
+    /// instructions it overwrites
+    pub overwrites: Vec<u16>,
+}
+
+/// What I might hold when an instruction runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Index {
+    Known(BTreeSet<u16>),
+    Unknown,
+}
+
+impl Index {
+    fn known(address: u16) -> Self {
+        Index::Known(Some(address).into_iter().collect())
+    }
+
+    /// widen self to cover other as well, returning whether self changed
+    fn join(&mut self, other: &Index) -> bool {
+        let joined = match (&*self, other) {
+            (Index::Known(a), Index::Known(b)) => {
+                let union: BTreeSet<u16> = a.union(b).copied().collect();
+                if union.len() > MAX_INDEX_VALUES { Index::Unknown } else { Index::Known(union) }
+            }
+            _ => Index::Unknown,
+        };
+        let changed = joined != *self;
+        *self = joined;
+        changed
+    }
+}
+
+/// Everything found out about a rom without running it
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    memory: Box<[u8; 4096]>,
+    rom: Range<u16>,
+    instructions: BTreeMap<u16, Instruction>,
+    blocks: BTreeMap<u16, Block>,
+    subroutines: BTreeMap<u16, Subroutine>,
+    sprites: BTreeMap<u16, Sprite>,
+    code_writes: Vec<CodeWrite>,
+    unreached: Vec<Range<u16>>,
+}
+
+impl Analysis {
+    /// Analyze rom as it would be loaded at 0x200
+    pub fn new(rom: &[u8]) -> Result<Self, Error> {
+        let mut emu = Emulator::new();
+        emu.load_rom(rom)?;
+        let memory = Box::new(emu.memory);
+        let rom = ENTRY..ENTRY + rom.len() as u16;
+
+        let (instructions, entries) = explore(&memory);
+        let blocks = blocks(&instructions, &entries);
+        let subroutines = entries.iter()
+            .filter(|entry| blocks.contains_key(entry))
+            .map(|&entry| (entry, subroutine(&blocks, &instructions, entry)))
+            .collect();
+        let mut analysis = Analysis {
+            memory,
+            rom,
+            instructions,
+            blocks,
+            subroutines,
+            sprites: BTreeMap::new(),
+            code_writes: Vec::new(),
+            unreached: Vec::new(),
+        };
+        analysis.find_memory_accesses(&entries);
+        analysis.unreached = analysis.find_unreached();
+        Ok(analysis)
+    }
+
+    pub fn memory(&self) -> &[u8; 4096] {
+        &self.memory
+    }
+
+    /// where the rom sits in memory
+    pub fn rom(&self) -> Range<u16> {
+        self.rom.clone()
+    }
+
+    /// every reachable instruction by address
+    pub fn instructions(&self) -> &BTreeMap<u16, Instruction> {
+        &self.instructions
+    }
+
+    /// basic blocks by start address
+    pub fn blocks(&self) -> &BTreeMap<u16, Block> {
+        &self.blocks
+    }
+
+    /// subroutines by entry address, the entry point included
+    pub fn subroutines(&self) -> &BTreeMap<u16, Subroutine> {
+        &self.subroutines
+    }
+
+    /// sprite data by address
+    pub fn sprites(&self) -> &BTreeMap<u16, Sprite> {
+        &self.sprites
+    }
+
+    /// writes that can change code, the rom is self modifying if there are any
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        &self.code_writes
+    }
+
+    /// ranges of the rom that are neither reachable code nor known sprite data
+    pub fn unreached(&self) -> &[Range<u16>] {
+        &self.unreached
+    }
+
+    /// Track what I can hold through the program to find the sprites DRW reads and the code Fx55 and Fx33 write
+    fn find_memory_accesses(&mut self, entries: &BTreeSet<u16>) {
+        let mut index: BTreeMap<u16, Index> = entries.iter().map(|&entry| (entry, Index::Unknown)).collect();
+        let _ = index.insert(ENTRY, Index::known(0));
+        let mut work: Vec<u16> = entries.iter().copied().collect();
+        while let Some(address) = work.pop() {
+            let instruction = match self.instructions.get(&address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            let after = match instruction.opcode >> 12 {
+                0xA => Index::known(instruction.opcode & 0xFFF),
+                // the callee can leave anything in I
+                0x2 => Index::Unknown,
+                0xF if matches!(instruction.opcode & 0xFF, 0x1E | 0x29 | 0x55 | 0x65) => Index::Unknown,
+                _ => index[&address].clone(),
+            };
+            for &(successor, _) in &instruction.successors {
+                let changed = match index.get_mut(&successor) {
+                    Some(existing) => existing.join(&after),
+                    None => index.insert(successor, after.clone()).is_none(),
+                };
+                if changed {
+                    work.push(successor);
+                }
+            }
+        }
+
+        for (&address, instruction) in &self.instructions {
+            let values = match index.get(&address) {
+                Some(Index::Known(values)) => values,
+                _ => continue,
+            };
+            let opcode = instruction.opcode;
+            let height = (opcode & 0xF) as u8;
+            if opcode >> 12 == 0xD && height > 0 {
+                for &value in values {
+                    let sprite = self.sprites.entry(value).or_insert(Sprite { address: value, height, drawn_by: Vec::new() });
+                    sprite.height = sprite.height.max(height);
+                    sprite.drawn_by.push(address);
+                }
+            }
+            let written = match (opcode >> 12, opcode & 0xFF) {
+                (0xF, 0x55) => (opcode >> 8 & 0xF) + 1,
+                (0xF, 0x33) => 3,
+                _ => continue,
+            };
+            for &value in values {
+                let target = value..(value + written).min(4096);
+                let overwrites: Vec<u16> = self.instructions.keys()
+                    .copied()
+                    .filter(|&other| other < target.end && other + 2 > target.start)
+                    .collect();
+                if !overwrites.is_empty() {
+                    self.code_writes.push(CodeWrite { at: address, target, overwrites });
+                }
+            }
+        }
+    }
+
+    fn find_unreached(&self) -> Vec<Range<u16>> {
+        let mut covered = BTreeSet::new();
+        for &address in self.instructions.keys() {
+            covered.extend(address..address + 2);
+        }
+        for sprite in self.sprites.values() {
+            covered.extend(sprite.bytes());
+        }
+        let mut unreached: Vec<Range<u16>> = Vec::new();
+        for address in self.rom.clone().filter(|address| !covered.contains(address)) {
+            match unreached.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => unreached.push(address..address + 1),
+            }
+        }
+        unreached
+    }
+}
+
+pub(crate) fn fetch(memory: &[u8; 4096], address: u16) -> u16 {
+    u16::from(memory[address as usize]) << 8 | u16::from(memory[address as usize + 1])
+}
+
+/// whether an instruction decodes to anything
+fn valid(opcode: u16) -> bool {
+    match opcode >> 12 {
+        0x0 => opcode == 0x00E0 || opcode == 0x00EE,
+        0x5 | 0x9 => opcode & 0xF == 0,
+        0x8 => matches!(opcode & 0xF, 0x0..=0x7 | 0xE),
+        0xE => matches!(opcode & 0xFF, 0x9E | 0xA1),
+        0xF => matches!(opcode & 0xFF, 0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65),
+        _ => true,
+    }
+}
+
+/// Where an instruction can send control, an invalid one is taken to be data and ends the path
+fn flow(memory: &[u8; 4096], address: u16, opcode: u16) -> Instruction {
+    let next = (address + 2) & 0xFFF;
+    let nnn = opcode & 0xFFF;
+    let (successors, call) = match opcode >> 12 {
+        _ if !valid(opcode) || opcode == 0x00EE => (Vec::new(), None),
+        0x1 => (vec![(nnn, Edge::Jump)], None),
+        0x2 => (vec![(next, Edge::Return)], Some(nnn)),
+        0x3 | 0x4 | 0x5 | 0x9 | 0xE => (vec![(next, Edge::Next), ((address + 4) & 0xFFF, Edge::Skip)], None),
+        0xB => (jump_table(memory, nnn), None),
+        _ => (vec![(next, Edge::Next)], None),
+    };
+    Instruction { opcode, successors, call }
+}
+
+/// Bnnn lands somewhere from nnn on, which is often a table of jumps to follow
+fn jump_table(memory: &[u8; 4096], base: u16) -> Vec<(u16, Edge)> {
+    let mut targets = vec![(base, Edge::Indirect)];
+    let entries = (1..MAX_JUMP_TABLE).map(|entry| base + entry * 2).take_while(|&address| address < 0xFFF);
+    if base < 0xFFF && fetch(memory, base) >> 12 == 0x1 {
+        targets.extend(entries.take_while(|&address| fetch(memory, address) >> 12 == 0x1).map(|address| (address, Edge::Indirect)));
+    }
+    targets
+}
+
+/// every instruction reachable from the entry point, and the subroutine entries found on the way
+fn explore(memory: &[u8; 4096]) -> (BTreeMap<u16, Instruction>, BTreeSet<u16>) {
+    let mut instructions = BTreeMap::new();
+    let mut entries: BTreeSet<u16> = Some(ENTRY).into_iter().collect();
+    let mut work = vec![ENTRY];
+    while let Some(address) = work.pop() {
+        // the last byte of memory doesn't hold a whole instruction
+        if address >= 0xFFF || instructions.contains_key(&address) {
+            continue;
+        }
+        let instruction = flow(memory, address, fetch(memory, address));
+        work.extend(instruction.successors.iter().map(|&(successor, _)| successor));
+        if let Some(callee) = instruction.call {
+            if entries.insert(callee) {
+                work.push(callee);
+            }
+        }
+        let _ = instructions.insert(address, instruction);
+    }
+    (instructions, entries)
+}
+
+fn falls_through(instruction: &Instruction) -> bool {
+    instruction.successors.len() == 1 && instruction.successors[0].1 == Edge::Next
+}
+
+fn blocks(instructions: &BTreeMap<u16, Instruction>, entries: &BTreeSet<u16>) -> BTreeMap<u16, Block> {
+    let mut leaders = entries.clone();
+    let mut predecessors: BTreeMap<u16, usize> = BTreeMap::new();
+    for instruction in instructions.values() {
+        for &(successor, _) in &instruction.successors {
+            *predecessors.entry(successor).or_insert(0) += 1;
+            if !falls_through(instruction) {
+                let _ = leaders.insert(successor);
+            }
+        }
+    }
+    leaders.extend(predecessors.iter().filter(|&(_, &count)| count > 1).map(|(&address, _)| address));
+
+    let mut blocks = BTreeMap::new();
+    for &start in leaders.iter().filter(|leader| instructions.contains_key(leader)) {
+        let mut address = start;
+        loop {
+            let instruction = &instructions[&address];
+            let next = address + 2;
+            if !falls_through(instruction) || leaders.contains(&next) || !instructions.contains_key(&next) {
+                let _ = blocks.insert(start, Block { start, end: next, successors: instruction.successors.clone() });
+                break;
+            }
+            address = next;
+        }
+    }
+    blocks
+}
+
+fn subroutine(blocks: &BTreeMap<u16, Block>, instructions: &BTreeMap<u16, Instruction>, entry: u16) -> Subroutine {
+    let mut seen = BTreeSet::new();
+    let mut work = vec![entry];
+    let mut callees = BTreeSet::new();
+    let mut returns = false;
+    while let Some(start) = work.pop() {
+        let block = match blocks.get(&start) {
+            Some(block) if seen.insert(start) => block,
+            _ => continue,
+        };
+        let last = &instructions[&block.last()];
+        callees.extend(last.call);
+        returns |= last.opcode == 0x00EE;
+        work.extend(block.successors.iter().map(|&(successor, _)| successor));
+    }
+    Subroutine { entry, blocks: seen.into_iter().collect(), callees: callees.into_iter().collect(), returns }
+}
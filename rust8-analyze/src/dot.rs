@@ -0,0 +1,76 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rust8_core::cpu::mnemonic;
+
+use crate::cfg::{fetch, Analysis, Edge, ENTRY};
+
+/// The control flow graph as Graphviz DOT, one cluster per subroutine
+pub struct Dot<'a>(pub &'a Analysis);
+
+/// name of the subroutine starting at entry
+pub fn subroutine_name(entry: u16) -> String {
+    if entry == ENTRY { "main".to_string() } else { format!("sub_{:03X}", entry) }
+}
+
+impl fmt::Display for Dot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let analysis = self.0;
+        writeln!(f, "digraph rom {{")?;
+        writeln!(f, "    node [shape=box fontname=\"monospace\"];")?;
+
+        // a block shared by several subroutines is drawn in the first
+        let mut owners = BTreeMap::new();
+        for subroutine in analysis.subroutines().values() {
+            for &block in &subroutine.blocks {
+                let _ = owners.entry(block).or_insert(subroutine.entry);
+            }
+        }
+        for subroutine in analysis.subroutines().values() {
+            writeln!(f, "    subgraph cluster_{:03X} {{", subroutine.entry)?;
+            writeln!(f, "        label=\"{}\";", subroutine_name(subroutine.entry))?;
+            for block in subroutine.blocks.iter().filter(|block| owners[block] == subroutine.entry) {
+                let block = &analysis.blocks()[block];
+                write!(f, "        b{:03X} [label=\"", block.start)?;
+                for address in (block.start..block.end).step_by(2) {
+                    let opcode = fetch(analysis.memory(), address);
+                    write!(f, "{:03X}  {:04X}  {}\\l", address, opcode, mnemonic(opcode))?;
+                }
+                writeln!(f, "\"];")?;
+            }
+            writeln!(f, "    }}")?;
+        }
+
+        for block in analysis.blocks().values() {
+            for &(successor, edge) in &block.successors {
+                let style = match edge {
+                    Edge::Next => "",
+                    Edge::Jump => " [label=\"jump\"]",
+                    Edge::Skip => " [label=\"skip\"]",
+                    Edge::Indirect => " [label=\"indirect\" style=dotted]",
+                    Edge::Return => " [label=\"return\"]",
+                };
+                writeln!(f, "    b{:03X} -> b{:03X}{};", block.start, successor, style)?;
+            }
+            if let Some(callee) = analysis.instructions()[&block.last()].call {
+                writeln!(f, "    b{:03X} -> b{:03X} [label=\"call\" style=dashed];", block.start, callee)?;
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::io;
+
+use rust8_core::error::Error;
+
+#[derive(Debug)]
+pub enum AnalyzeError {
+    Io(io::Error),
+    Emulator(Error),
+    Png(png::EncodingError),
+    Json(serde_json::Error),
+    /// a heatmap scale of 0 or over MAX_SCALE, or a sprite sheet too large for a png
+    InvalidScale(u32),
+}
+
+impl fmt::Display for AnalyzeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyzeError::Io(e) => write!(f, "i/o error: {}", e),
+            AnalyzeError::Emulator(e) => write!(f, "{}", e),
+            AnalyzeError::Png(e) => write!(f, "png encoding error: {}", e),
+            AnalyzeError::Json(e) => write!(f, "json error: {}", e),
+            AnalyzeError::InvalidScale(scale) => write!(f, "invalid scale {}", scale),
+        }
+    }
+}
+
+impl std::error::Error for AnalyzeError {}
+
+impl From<io::Error> for AnalyzeError {
+    fn from(e: io::Error) -> Self {
+        AnalyzeError::Io(e)
+    }
+}
+
+impl From<Error> for AnalyzeError {
+    fn from(e: Error) -> Self {
+        AnalyzeError::Emulator(e)
+    }
+}
+
+impl From<png::EncodingError> for AnalyzeError {
+    fn from(e: png::EncodingError) -> Self {
+        AnalyzeError::Png(e)
+    }
+}
+
+impl From<serde_json::Error> for AnalyzeError {
+    fn from(e: serde_json::Error) -> Self {
+        AnalyzeError::Json(e)
+    }
+}
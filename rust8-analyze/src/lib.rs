@@ -0,0 +1,57 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Static analysis of chip-8 roms.
+//!
+//! `Analysis::new` follows every path from 0x200 through jumps, skips, calls and returns, and
+//! Bnnn jump tables, to build a control flow graph split into subroutines. Tracking what I can hold
+//! on the way finds the sprite data DRW reads and Fx55 or Fx33 writes over code. What's left of
+//! the rom is reported as unreached. The graph exports as Graphviz DOT with `Dot` and the rom
+//! disassembles with `Listing`.
+//!
+//! Static analysis can't see through computed jumps or I, so `record_accesses` runs the rom with the
+//! core's `AccessMap` to find what really executed and what was read or written as data. The map
+//! exports as json or a heatmap png, and labels the data regions of a `Listing`. `record_profile` does the
+//! same with the core's `Profile`, counting instructions by the subroutines that ran them for a
+//! report or a flamegraph.
+//!
+//! `extract_sprites` pulls out every sprite the analysis or a run found DRW drawing, to draw as ascii
+//! art with `SpriteArt` or as a png sheet along with the font.
+
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod cfg;
+mod dot;
+mod error;
+mod listing;
+mod profile;
+mod runtime;
+mod sprites;
+
+pub use crate::cfg::{Analysis, Block, CodeWrite, Edge, Instruction, Sprite, Subroutine, ENTRY};
+pub use crate::dot::{subroutine_name, Dot};
+pub use crate::error::AnalyzeError;
+pub use crate::listing::Listing;
+pub use crate::profile::{record_profile, write_folded};
+pub use crate::runtime::{record_accesses, write_access_json, write_heatmap};
+pub use crate::sprites::{extract_sprites, font_glyphs, write_sprite_sheet, Found, Graphic, SpriteArt};
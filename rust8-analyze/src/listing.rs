@@ -0,0 +1,97 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use rust8_core::access::{Access, AccessMap};
+use rust8_core::cpu::mnemonic;
+
+use crate::cfg::{fetch, Analysis};
+use crate::dot::subroutine_name;
+
+/// A disassembly of the rom, labelling subroutines, jump targets and sprites.
+/// Reachable code is disassembled, sprite data is drawn and anything else is listed byte by byte.
+pub struct Listing<'a> {
+    analysis: &'a Analysis,
+    access: Option<&'a AccessMap>,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(analysis: &'a Analysis) -> Self {
+        Listing { analysis, access: None }
+    }
+
+    /// Label what static analysis couldn't reach by how a run of the rom used it:
+    /// code that only ran through paths the analysis can't follow, sprites drawn through
+    /// a computed I and data moved by Fx33, Fx55 and Fx65
+    pub fn with_access(self, access: &'a AccessMap) -> Self {
+        Listing { access: Some(access), ..self }
+    }
+
+    fn count(&self, access: Access, address: u16) -> u32 {
+        self.access.map_or(0, |map| map.count(access, address))
+    }
+}
+
+pub(crate) fn pixels(byte: u8) -> String {
+    (0..8).map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }).collect()
+}
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let analysis = self.analysis;
+        let rom = analysis.rom();
+        let mut address = rom.start;
+        while address < rom.end {
+            if analysis.subroutines().contains_key(&address) {
+                writeln!(f, "\n{}:", subroutine_name(address))?;
+            } else if analysis.blocks().contains_key(&address) {
+                writeln!(f, "loc_{:03X}:", address)?;
+            }
+            if let Some(sprite) = analysis.sprites().get(&address) {
+                writeln!(f, "sprite_{:03X}:", address)?;
+                for row in sprite.bytes().filter(|&row| row < rom.end) {
+                    let byte = analysis.memory()[row as usize];
+                    writeln!(f, "    {:03X}  {:02X}        ; {}", row, byte, pixels(byte))?;
+                }
+                address = sprite.bytes().end;
+            } else if analysis.instructions().contains_key(&address) {
+                let opcode = fetch(analysis.memory(), address);
+                write!(f, "    {:03X}  {:04X}  {}", address, opcode, mnemonic(opcode))?;
+                for write in analysis.code_writes().iter().filter(|write| write.at == address) {
+                    write!(f, "  ; writes code at {:03X}", write.target.start)?;
+                }
+                writeln!(f)?;
+                address += 2;
+            } else if address + 1 < rom.end && self.count(Access::Execute, address) > 0 && self.count(Access::Execute, address + 1) > 0 {
+                let opcode = fetch(analysis.memory(), address);
+                writeln!(f, "    {:03X}  {:04X}  {}  ; executed at runtime", address, opcode, mnemonic(opcode))?;
+                address += 2;
+            } else {
+                let byte = analysis.memory()[address as usize];
+                let (sprite, read, written) = (self.count(Access::Sprite, address), self.count(Access::Read, address), self.count(Access::Write, address));
+                if sprite > 0 {
+                    writeln!(f, "    {:03X}  {:02X}        ; {} drawn at runtime", address, byte, pixels(byte))?;
+                } else if read > 0 || written > 0 {
+                    writeln!(f, "    {:03X}  {:02X}        ; data, read {} written {}", address, byte, read, written)?;
+                } else {
+                    writeln!(f, "    {:03X}  {:02X}        ; unreached", address, byte)?;
+                }
+                address += 1;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process;
+
+use rust8_analyze::{
+    extract_sprites, font_glyphs, record_accesses, record_profile, subroutine_name, write_access_json, write_folded,
+    write_heatmap, write_sprite_sheet, Analysis, Dot, Listing, SpriteArt,
+};
+use rust8_capture::{save_png, GifRecorder, Palette};
+use rust8_core::emu::Emulator;
+use rust8_core::profile::Profile;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+/// frames run for an access map or a profile when --run isn't given
+const DEFAULT_FRAMES: u32 = 600;
+
+enum Output {
+    Summary,
+    Dot,
+    Listing,
+    Profile,
+    Sprites,
+}
+
+struct Args {
+    output: Output,
+    rom: PathBuf,
+    /// frames to run the rom for to record an access map
+    frames: Option<u32>,
+    /// whether to profile those frames as well
+    profile: bool,
+    quirks: Quirks,
+    map: Option<PathBuf>,
+    heatmap: Option<PathBuf>,
+    scale: u32,
+    folded: Option<PathBuf>,
+    sheet: Option<PathBuf>,
+    /// the display after the run, as a png
+    screenshot: Option<PathBuf>,
+    /// every frame of the run, as a gif
+    gif: Option<PathBuf>,
+}
+
+fn parse_args() -> Args {
+    let mut output = Output::Summary;
+    let mut rom = None;
+    let mut frames = None;
+    let mut quirks = Quirks::default();
+    let mut map = None;
+    let mut heatmap = None;
+    let mut scale = 8;
+    let mut folded = None;
+    let mut sheet = None;
+    let mut screenshot = None;
+    let mut gif = None;
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--dot" => output = Output::Dot,
+            "--disasm" => output = Output::Listing,
+            "--profile" => output = Output::Profile,
+            "--sprites" => output = Output::Sprites,
+            "--run" => frames = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--quirks" => quirks = Quirks::from_name(&value()).unwrap_or_else(|| usage()),
+            "--map" => map = Some(PathBuf::from(value())),
+            "--heatmap" => heatmap = Some(PathBuf::from(value())),
+            "--scale" => scale = value().parse().unwrap_or_else(|_| usage()),
+            "--folded" => folded = Some(PathBuf::from(value())),
+            "--sheet" => sheet = Some(PathBuf::from(value())),
+            "--screenshot" => screenshot = Some(PathBuf::from(value())),
+            "--gif" => gif = Some(PathBuf::from(value())),
+            _ if arg.starts_with('-') || rom.is_some() => usage(),
+            _ => rom = Some(PathBuf::from(arg)),
+        }
+    }
+    let profile = matches!(output, Output::Profile) || folded.is_some();
+    if frames.is_none() && (map.is_some() || heatmap.is_some() || profile || screenshot.is_some() || gif.is_some()) {
+        frames = Some(DEFAULT_FRAMES);
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+    Args { output, rom, frames, profile, quirks, map, heatmap, scale, folded, sheet, screenshot, gif }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: rust8-analyze [--dot | --disasm | --profile | --sprites] [--run FRAMES] [--quirks QUIRKS] [--map FILE] [--heatmap FILE] [--scale N] [--folded FILE] [--sheet FILE] [--screenshot FILE] [--gif FILE] ROM");
+    eprintln!("  prints a summary of the rom, its control flow graph as Graphviz DOT, a disassembly, a profile or its sprites");
+    eprintln!("  --run records how the rom uses memory over that many frames, labelling data in the disassembly,");
+    eprintln!("  --map and --heatmap save that record as json and as a png");
+    eprintln!("  --profile counts the instructions each subroutine ran over those frames, --folded saves them as folded stacks for a flamegraph");
+    eprintln!("  --sprites draws the font and every sprite found as ascii art, with --run adding those drawn at runtime, --sheet saves them as a png");
+    eprintln!("  --screenshot saves the display at the end of the run as a png, --gif records every frame of it at 60fps");
+    process::exit(2);
+}
+
+fn fail(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn summary(analysis: &Analysis) {
+    println!("{} instructions in {} blocks", analysis.instructions().len(), analysis.blocks().len());
+    println!("\nsubroutines:");
+    for subroutine in analysis.subroutines().values() {
+        let callees: Vec<String> = subroutine.callees.iter().map(|&callee| subroutine_name(callee)).collect();
+        println!(
+            "  {:<8} {:03X}  {} blocks{}{}",
+            subroutine_name(subroutine.entry),
+            subroutine.entry,
+            subroutine.blocks.len(),
+            if subroutine.returns { "" } else { ", never returns" },
+            if callees.is_empty() { String::new() } else { format!(", calls {}", callees.join(" ")) },
+        );
+    }
+    println!("\nsprites:");
+    for sprite in analysis.sprites().values() {
+        let drawn_by: Vec<String> = sprite.drawn_by.iter().map(|address| format!("{:03X}", address)).collect();
+        println!("  {:03X}  {} rows, drawn at {}", sprite.address, sprite.height, drawn_by.join(" "));
+    }
+    if !analysis.code_writes().is_empty() {
+        println!("\nself modifying writes:");
+        for write in analysis.code_writes() {
+            println!("  {:03X} writes {:03X}..{:03X}", write.at, write.target.start, write.target.end);
+        }
+    }
+    if !analysis.unreached().is_empty() {
+        println!("\nunreached:");
+        for range in analysis.unreached() {
+            println!("  {:03X}..{:03X}", range.start, range.end);
+        }
+    }
+}
+
+fn report(profile: &Profile) {
+    let total = profile.total().max(1) as f64;
+    let mut subroutines = profile.subroutines();
+    subroutines.sort_by_key(|subroutine| std::cmp::Reverse(subroutine.inclusive));
+    println!("{} instructions\n", profile.total());
+    println!("  {:<8} {:>5}  {:>8}  {:>10} {:>6}  {:>10} {:>6}", "name", "entry", "calls", "inclusive", "", "exclusive", "");
+    for subroutine in subroutines {
+        println!(
+            "  {:<8}   {:03X}  {:>8}  {:>10} {:>5.1}%  {:>10} {:>5.1}%",
+            subroutine_name(subroutine.entry),
+            subroutine.entry,
+            subroutine.calls,
+            subroutine.inclusive,
+            100.0 * subroutine.inclusive as f64 / total,
+            subroutine.exclusive,
+            100.0 * subroutine.exclusive as f64 / total,
+        );
+    }
+    let draws = profile.draws_per_frame();
+    if let (Some(min), Some(max)) = (draws.iter().min(), draws.iter().max()) {
+        let sum: u64 = draws.iter().map(|&count| u64::from(count)).sum();
+        println!(
+            "\nDRW per frame: min {}, mean {:.2}, max {} over {} frames",
+            min,
+            sum as f64 / draws.len() as f64,
+            max,
+            draws.len(),
+        );
+    }
+}
+
+/// run the frames, recording each one to the gif and saving the last as a screenshot
+fn capture(emu: &mut Emulator, frames: u32, args: &Args) {
+    let palette = Palette::default();
+    let mut gif = args.gif.as_ref().map(|path| GifRecorder::create(path, args.scale, &palette).unwrap_or_else(|e| fail(e)));
+    for _ in 0..frames {
+        emu.run_frame();
+        if let Some(gif) = gif.as_mut() {
+            gif.push_frame(&emu.display.vram).unwrap_or_else(|e| fail(e));
+        }
+    }
+    if let Some(gif) = gif {
+        gif.finish().unwrap_or_else(|e| fail(e));
+    }
+    if let Some(path) = &args.screenshot {
+        save_png(path, &emu.display.vram, args.scale, &palette).unwrap_or_else(|e| fail(e));
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let rom = fs::read(&args.rom).unwrap_or_else(|e| fail(e));
+    let analysis = Analysis::new(&rom).unwrap_or_else(|e| fail(e));
+    let mut access = None;
+    let mut profile = None;
+    if let Some(frames) = args.frames {
+        // the profile and the capture get runs of their own with the same random numbers, so all see the same frames
+        let rng = Rng::default();
+        let emulator = || {
+            let mut emu = Emulator::new();
+            emu.quirks = args.quirks;
+            emu.rng = rng;
+            emu.load_rom(&rom).unwrap_or_else(|e| fail(e));
+            emu
+        };
+        access = Some(record_accesses(&mut emulator(), frames));
+        if args.profile {
+            profile = Some(record_profile(&mut emulator(), frames));
+        }
+        if args.screenshot.is_some() || args.gif.is_some() {
+            capture(&mut emulator(), frames, &args);
+        }
+    }
+    if let (Some(path), Some(access)) = (&args.map, &access) {
+        let file = BufWriter::new(File::create(path).unwrap_or_else(|e| fail(e)));
+        write_access_json(file, access).unwrap_or_else(|e| fail(e));
+    }
+    if let (Some(path), Some(access)) = (&args.heatmap, &access) {
+        let file = BufWriter::new(File::create(path).unwrap_or_else(|e| fail(e)));
+        write_heatmap(file, access, args.scale).unwrap_or_else(|e| fail(e));
+    }
+    if let (Some(path), Some(profile)) = (&args.folded, &profile) {
+        let file = BufWriter::new(File::create(path).unwrap_or_else(|e| fail(e)));
+        write_folded(file, profile).unwrap_or_else(|e| fail(e));
+    }
+    let sprites = if matches!(args.output, Output::Sprites) || args.sheet.is_some() {
+        let mut sprites = font_glyphs();
+        sprites.extend(extract_sprites(&analysis, access.as_deref()));
+        sprites
+    } else {
+        Vec::new()
+    };
+    if let Some(path) = &args.sheet {
+        let file = BufWriter::new(File::create(path).unwrap_or_else(|e| fail(e)));
+        write_sprite_sheet(file, &sprites, args.scale).unwrap_or_else(|e| fail(e));
+    }
+    match args.output {
+        Output::Summary => summary(&analysis),
+        Output::Dot => print!("{}", Dot(&analysis)),
+        Output::Listing => match &access {
+            Some(access) => print!("{}", Listing::new(&analysis).with_access(access)),
+            None => print!("{}", Listing::new(&analysis)),
+        },
+        Output::Profile => report(profile.as_deref().unwrap_or_else(|| usage())),
+        Output::Sprites => print!("{}", SpriteArt(&sprites)),
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::Write;
+
+use rust8_core::emu::Emulator;
+use rust8_core::profile::Profile;
+
+use crate::dot::subroutine_name;
+use crate::AnalyzeError;
+
+/// Run emu for frames with a profile, returning the profile
+pub fn record_profile(emu: &mut Emulator, frames: u32) -> Box<Profile> {
+    emu.profile = Some(Box::default());
+    for _ in 0..frames {
+        emu.run_frame();
+    }
+    emu.profile.take().unwrap_or_default()
+}
+
+/// Write the profile's stacks in the folded format flamegraph.pl and inferno read, one
+/// `main;sub_2A4;sub_300 COUNT` line per stack with the instructions it ran itself
+pub fn write_folded<W: Write>(mut writer: W, profile: &Profile) -> Result<(), AnalyzeError> {
+    for (stack, count) in profile.stacks() {
+        let names: Vec<String> = stack.into_iter().map(subroutine_name).collect();
+        writeln!(writer, "{} {}", names.join(";"), count)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::Write;
+
+use png::{BitDepth, ColorType};
+use rust8_core::access::{Access, AccessMap};
+use rust8_capture::MAX_SCALE;
+use rust8_core::emu::Emulator;
+use serde::Serialize;
+
+use crate::AnalyzeError;
+
+/// Run emu for frames with an access map, returning the map
+pub fn record_accesses(emu: &mut Emulator, frames: u32) -> Box<AccessMap> {
+    emu.access = Some(Box::default());
+    for _ in 0..frames {
+        emu.run_frame();
+    }
+    emu.access.take().unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct Entry {
+    address: u16,
+    executed: u32,
+    sprite: u32,
+    read: u32,
+    written: u32,
+}
+
+#[derive(Serialize)]
+struct Map {
+    /// only the addresses something touched
+    addresses: Vec<Entry>,
+}
+
+/// Write the map as json, an object holding an `addresses` array with the address and the
+/// executed, sprite, read and written counts of every address that was touched
+pub fn write_access_json<W: Write>(writer: W, map: &AccessMap) -> Result<(), AnalyzeError> {
+    let addresses = (0..4096u16)
+        .filter(|&address| map.touched(address))
+        .map(|address| Entry {
+            address,
+            executed: map.count(Access::Execute, address),
+            sprite: map.count(Access::Sprite, address),
+            read: map.count(Access::Read, address),
+            written: map.count(Access::Write, address),
+        })
+        .collect();
+    serde_json::to_writer_pretty(writer, &Map { addresses })?;
+    Ok(())
+}
+
+/// Encode the map as a 64x64 rgb png with one pixel per address, row by row from 0x000.
+/// Green is execution, blue is reads as sprites or by Fx65 and red is writes, each brighter on a log
+/// scale the more often it happened relative to the busiest address. Untouched memory is black.
+/// Each address is scale pixels square, up to MAX_SCALE.
+pub fn write_heatmap<W: Write>(writer: W, map: &AccessMap, scale: u32) -> Result<(), AnalyzeError> {
+    if scale == 0 || scale > MAX_SCALE {
+        return Err(AnalyzeError::InvalidScale(scale));
+    }
+    let reads: Vec<u32> = (0..4096).map(|address| map.counts(Access::Sprite)[address].saturating_add(map.counts(Access::Read)[address])).collect();
+    let channels = [&map.counts(Access::Write)[..], &map.counts(Access::Execute)[..], &reads[..]];
+    let maxima: Vec<u32> = channels.iter().map(|counts| counts.iter().copied().max().unwrap_or(0)).collect();
+
+    let size = 64 * scale as usize;
+    let mut pixels = Vec::with_capacity(size * size * 3);
+    for y in 0..size {
+        for x in 0..size {
+            let address = (y / scale as usize) * 64 + x / scale as usize;
+            pixels.extend(channels.iter().zip(maxima.iter()).map(|(counts, &max)| intensity(counts[address], max)));
+        }
+    }
+    let mut encoder = png::Encoder::new(writer, size as u32, size as u32);
+    encoder.set_color(ColorType::RGB);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
+
+/// brightness of a count, anything touched at all is visible
+fn intensity(count: u32, max: u32) -> u8 {
+    if count == 0 {
+        return 0;
+    }
+    let scale = (1.0 + f64::from(count)).ln() / (1.0 + f64::from(max)).ln();
+    (64.0 + 191.0 * scale).round() as u8
+}
@@ -0,0 +1,161 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+
+use png::{BitDepth, ColorType};
+use rust8_core::access::AccessMap;
+use rust8_core::constants::{FONTSET, FONTSET_START};
+
+use crate::cfg::Analysis;
+use crate::listing::pixels;
+use crate::AnalyzeError;
+
+/// sprites per row of a sheet
+const COLUMNS: usize = 16;
+/// a sheet cell fits the widest and tallest chip-8 sprite with a pixel of border
+const CELL_WIDTH: usize = 9;
+const CELL_HEIGHT: usize = 16;
+/// grey of the border between cells
+const BORDER: u8 = 0x40;
+
+/// How a sprite was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Found {
+    /// I was known to hold its address at a DRW without running the rom
+    Static,
+    /// a run of the rom drew it
+    Runtime,
+    Both,
+    /// one of the interpreter's hexadecimal digits
+    Font,
+}
+
+impl Found {
+    pub fn name(self) -> &'static str {
+        match self {
+            Found::Static => "static",
+            Found::Runtime => "runtime",
+            Found::Both => "static and runtime",
+            Found::Font => "font",
+        }
+    }
+}
+
+/// The rows of a sprite as they were in memory when the rom was loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graphic {
+    pub address: u16,
+    pub rows: Vec<u8>,
+    pub found: Found,
+}
+
+impl Graphic {
+    pub fn name(&self) -> String {
+        match self.found {
+            Found::Font => format!("font_{:X}", (self.address - FONTSET_START) / 5),
+            _ => format!("sprite_{:03X}", self.address),
+        }
+    }
+}
+
+/// Every sprite the analysis found, and those access drew if there was a run, by address. A sprite
+/// drawn with different heights is extracted once with the tallest.
+pub fn extract_sprites(analysis: &Analysis, access: Option<&AccessMap>) -> Vec<Graphic> {
+    let mut sprites: BTreeMap<u16, (u8, Found)> = BTreeMap::new();
+    for sprite in analysis.sprites().values() {
+        let _ = sprites.insert(sprite.address, (sprite.height, Found::Static));
+    }
+    for &(address, height) in access.map(AccessMap::sprites).into_iter().flatten() {
+        let entry = sprites.entry(address).or_insert((height, Found::Runtime));
+        entry.0 = entry.0.max(height);
+        if entry.1 == Found::Static {
+            entry.1 = Found::Both;
+        }
+    }
+    sprites.into_iter()
+        .filter(|&(_, (height, _))| height > 0)
+        .map(|(address, (height, found))| Graphic { address, rows: rows(analysis.memory(), address, height), found })
+        .collect()
+}
+
+/// The sixteen digits Fx29 points I at
+pub fn font_glyphs() -> Vec<Graphic> {
+    FONTSET.chunks(5).enumerate().map(|(digit, rows)| Graphic {
+        address: FONTSET_START + 5 * digit as u16,
+        rows: rows.to_vec(),
+        found: Found::Font,
+    }).collect()
+}
+
+/// rows of a sprite, wrapping around the end of memory like DRW does
+fn rows(memory: &[u8; 4096], address: u16, height: u8) -> Vec<u8> {
+    (0..usize::from(height)).map(|row| memory[(usize::from(address) + row) & 0xFFF]).collect()
+}
+
+/// Sprites drawn as ascii art, a `sprite_21E` header for each and then its rows
+pub struct SpriteArt<'a>(pub &'a [Graphic]);
+
+impl fmt::Display for SpriteArt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for graphic in self.0 {
+            writeln!(f, "{}  {} rows, {}", graphic.name(), graphic.rows.len(), graphic.found.name())?;
+            for (row, &byte) in graphic.rows.iter().enumerate() {
+                writeln!(f, "    {:03X}  {:02X}  {}", (usize::from(graphic.address) + row) & 0xFFF, byte, pixels(byte))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encode the sprites as a greyscale png sheet, sixteen to a row in address order with white pixels
+/// on black, each in a cell big enough for a 15 row sprite and bordered in grey. Every pixel is
+/// scale pixels square.
+pub fn write_sprite_sheet<W: Write>(writer: W, graphics: &[Graphic], scale: u32) -> Result<(), AnalyzeError> {
+    let columns = graphics.len().clamp(1, COLUMNS);
+    let lines = graphics.len().div_ceil(COLUMNS).max(1);
+    let (width, height) = (1 + columns * CELL_WIDTH, 1 + lines * CELL_HEIGHT);
+    let size = width.max(height).checked_mul(scale as usize);
+    if scale == 0 || size.is_none_or(|size| size > usize::from(u16::MAX)) {
+        return Err(AnalyzeError::InvalidScale(scale));
+    }
+    let mut sheet = vec![BORDER; width * height];
+    for (index, graphic) in graphics.iter().enumerate() {
+        let (left, top) = (1 + (index % COLUMNS) * CELL_WIDTH, 1 + (index / COLUMNS) * CELL_HEIGHT);
+        for y in 0..CELL_HEIGHT - 1 {
+            let byte = graphic.rows.get(y).copied();
+            for x in 0..CELL_WIDTH - 1 {
+                sheet[(top + y) * width + left + x] = match byte {
+                    Some(byte) if byte & (0x80 >> x) != 0 => 0xFF,
+                    Some(_) => 0,
+                    None => BORDER,
+                };
+            }
+        }
+    }
+
+    let scale = scale as usize;
+    let mut pixels = Vec::with_capacity(width * height * scale * scale);
+    for y in 0..height * scale {
+        pixels.extend((0..width * scale).map(|x| sheet[(y / scale) * width + x / scale]));
+    }
+    let mut encoder = png::Encoder::new(writer, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(ColorType::Grayscale);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
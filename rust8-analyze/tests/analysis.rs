@@ -0,0 +1,78 @@
+use rust8_analyze::{Analysis, CodeWrite, Dot, Edge, Listing};
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+/// a subroutine that overwrites code, a Bnnn jump table, a sprite and bytes nothing reaches
+const ROM: [u8; 27] = [
+    0x22, 0x08, // 200 CALL 0x208
+    0xB2, 0x0E, // 202 JP V0, 0x20E
+    0xFF, 0xFF, // 204
+    0x00, 0x00, // 206
+    0xA2, 0x12, // 208 LD I, 0x212
+    0xF0, 0x55, // 20A LD [I], V0
+    0x00, 0xEE, // 20C RET
+    0x12, 0x12, // 20E JP 0x212
+    0x12, 0x14, // 210 JP 0x214
+    0x12, 0x12, // 212 JP 0x212
+    0xA2, 0x1A, // 214 LD I, 0x21A
+    0xD0, 0x11, // 216 DRW V0, V1, 1
+    0x12, 0x18, // 218 JP 0x218
+    0x81, // 21A
+];
+
+#[test]
+fn maze() {
+    let analysis = Analysis::new(MAZE).unwrap();
+    assert_eq!(analysis.subroutines().keys().copied().collect::<Vec<_>>(), [0x200]);
+    assert!(!analysis.subroutines()[&0x200].returns);
+    assert_eq!(analysis.blocks().len(), 8);
+    // I is 0x222 or 0x21E depending on the random skip before the draw
+    assert_eq!(analysis.sprites().keys().copied().collect::<Vec<_>>(), [0x21E, 0x222]);
+    assert_eq!(analysis.sprites()[&0x21E].drawn_by, [0x20C]);
+    assert!(analysis.code_writes().is_empty());
+    assert!(analysis.unreached().is_empty());
+}
+
+#[test]
+fn subroutines_and_jump_tables() {
+    let analysis = Analysis::new(&ROM).unwrap();
+    let subroutines = analysis.subroutines();
+    assert_eq!(subroutines.keys().copied().collect::<Vec<_>>(), [0x200, 0x208]);
+    assert_eq!(subroutines[&0x200].callees, [0x208]);
+    assert!(subroutines[&0x208].returns);
+    assert_eq!(subroutines[&0x208].blocks, [0x208]);
+
+    let table: Vec<u16> = analysis.instructions()[&0x202].successors.iter()
+        .map(|&(target, edge)| {
+            assert_eq!(edge, Edge::Indirect);
+            target
+        })
+        .collect();
+    assert_eq!(table, [0x20E, 0x210, 0x212]);
+    assert!(analysis.instructions().contains_key(&0x218));
+}
+
+#[test]
+fn data_and_self_modifying_code() {
+    let analysis = Analysis::new(&ROM).unwrap();
+    assert_eq!(analysis.sprites().keys().copied().collect::<Vec<_>>(), [0x21A]);
+    assert_eq!(analysis.code_writes(), [CodeWrite { at: 0x20A, target: 0x212..0x213, overwrites: vec![0x212] }]);
+    assert_eq!(analysis.unreached().len(), 1);
+    assert_eq!(analysis.unreached()[0], 0x204..0x208);
+}
+
+#[test]
+fn exports() {
+    let analysis = Analysis::new(&ROM).unwrap();
+    let dot = Dot(&analysis).to_string();
+    assert!(dot.starts_with("digraph rom {"));
+    assert!(dot.contains("subgraph cluster_208 {"));
+    assert!(dot.contains("b200 -> b208 [label=\"call\" style=dashed];"));
+    assert!(dot.contains("b202 -> b210 [label=\"indirect\" style=dotted];"));
+
+    let listing = Listing::new(&analysis).to_string();
+    assert!(listing.contains("\nsub_208:\n"));
+    assert!(listing.contains("20A  F055  LD [I], 0x0  ; writes code at 212"));
+    assert!(listing.contains("204  FF        ; unreached"));
+    assert!(listing.contains("21A  81        ; #......#"));
+}
@@ -0,0 +1,79 @@
+use rust8_analyze::{record_accesses, record_profile, write_access_json, write_folded, write_heatmap, Analysis, Listing};
+use rust8_capture::MAX_SCALE;
+use rust8_core::access::Access;
+use rust8_core::emu::Emulator;
+
+/// draws a sprite through an I that's only known at runtime, then reads a byte of data
+const ROM: [u8; 21] = [
+    0xA2, 0x0E, // 200 LD I, 0x20E
+    0x60, 0x02, // 202 LD V0, 2
+    0xF0, 0x1E, // 204 ADD I, V0
+    0xD1, 0x11, // 206 DRW V1, V1, 1
+    0xA2, 0x14, // 208 LD I, 0x214
+    0xF0, 0x65, // 20A LD V0, [I]
+    0x12, 0x0C, // 20C JP 0x20C
+    0x00, 0x00, // 20E
+    0xF0, 0x00, // 210
+    0x00, 0x00, // 212
+    0x42, // 214
+];
+
+fn accesses() -> Box<rust8_core::access::AccessMap> {
+    let mut emu = Emulator::new();
+    emu.load_rom(&ROM).unwrap();
+    record_accesses(&mut emu, 2)
+}
+
+#[test]
+fn runs_record_every_kind_of_access() {
+    let map = accesses();
+    assert_eq!(map.count(Access::Execute, 0x200), 1);
+    assert_eq!(map.count(Access::Execute, 0x201), 1);
+    assert!(map.count(Access::Execute, 0x20C) > 1);
+    assert_eq!(map.count(Access::Sprite, 0x210), 1);
+    assert_eq!(map.count(Access::Read, 0x214), 1);
+    assert!(!map.touched(0x20E));
+}
+
+#[test]
+fn listing_labels_data_found_at_runtime() {
+    let analysis = Analysis::new(&ROM).unwrap();
+    let map = accesses();
+    let listing = Listing::new(&analysis).with_access(&map).to_string();
+    assert!(listing.contains("20E  00        ; unreached"));
+    assert!(listing.contains("210  F0        ; ####.... drawn at runtime"));
+    assert!(listing.contains("214  42        ; data, read 1 written 0"));
+    // without a run the same bytes are just unreached
+    assert!(Listing::new(&analysis).to_string().contains("210  F0        ; unreached"));
+}
+
+#[test]
+fn exports() {
+    let map = accesses();
+    let mut json = Vec::new();
+    write_access_json(&mut json, &map).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    let sprite = json["addresses"].as_array().unwrap().iter().find(|entry| entry["address"] == 0x210).unwrap();
+    assert_eq!(sprite["sprite"], 1);
+    assert_eq!(sprite["executed"], 0);
+
+    let mut png = Vec::new();
+    write_heatmap(&mut png, &map, 4).unwrap();
+    let (info, _) = png::Decoder::new(&png[..]).read_info().unwrap();
+    assert_eq!((info.width, info.height), (256, 256));
+    assert!(write_heatmap(Vec::new(), &map, 0).is_err());
+    assert!(write_heatmap(Vec::new(), &map, MAX_SCALE + 1).is_err());
+    assert!(write_heatmap(Vec::new(), &map, u32::MAX).is_err());
+}
+
+#[test]
+fn folded_stacks() {
+    // main calls 204, which idles
+    let rom = [0x22, 0x04, 0x00, 0x00, 0x12, 0x04];
+    let mut emu = Emulator::new();
+    emu.load_rom(&rom).unwrap();
+    let profile = record_profile(&mut emu, 1);
+    let mut folded = Vec::new();
+    write_folded(&mut folded, &profile).unwrap();
+    assert_eq!(String::from_utf8(folded).unwrap(), "main 1\nmain;sub_204 14\n");
+}
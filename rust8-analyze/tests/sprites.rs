@@ -0,0 +1,61 @@
+use rust8_analyze::{extract_sprites, font_glyphs, record_accesses, write_sprite_sheet, Analysis, Found, Graphic, SpriteArt};
+use rust8_core::emu::Emulator;
+
+/// draws one sprite through a known I, one through a computed I and the digit 7
+const ROM: [u8; 26] = [
+    0xA2, 0x14, // 200 LD I, 0x214
+    0xD0, 0x02, // 202 DRW V0, V0, 2
+    0xA2, 0x14, // 204 LD I, 0x214
+    0x60, 0x02, // 206 LD V0, 2
+    0xF0, 0x1E, // 208 ADD I, V0
+    0xD1, 0x13, // 20A DRW V1, V1, 3
+    0x60, 0x07, // 20C LD V0, 7
+    0xF0, 0x29, // 20E LD F, V0
+    0xD1, 0x15, // 210 DRW V1, V1, 5
+    0x12, 0x12, // 212 JP 0x212
+    0xFF, 0x81, // 214
+    0x18, 0x3C, 0x18, // 216
+    0x00,
+];
+
+#[test]
+fn static_and_runtime_sprites() {
+    let analysis = Analysis::new(&ROM).unwrap();
+    let statics = extract_sprites(&analysis, None);
+    assert_eq!(statics, vec![Graphic { address: 0x214, rows: vec![0xFF, 0x81], found: Found::Static }]);
+
+    let mut emu = Emulator::new();
+    emu.load_rom(&ROM).unwrap();
+    let map = record_accesses(&mut emu, 1);
+    let sprites = extract_sprites(&analysis, Some(&map));
+    let found: Vec<(String, usize, Found)> = sprites.iter().map(|sprite| (sprite.name(), sprite.rows.len(), sprite.found)).collect();
+    assert_eq!(found, vec![
+        ("sprite_073".to_string(), 5, Found::Runtime),
+        ("sprite_214".to_string(), 2, Found::Both),
+        ("sprite_216".to_string(), 3, Found::Runtime),
+    ]);
+    assert_eq!(sprites[0].rows, font_glyphs()[7].rows);
+}
+
+#[test]
+fn ascii_art() {
+    let font = font_glyphs();
+    assert_eq!(font.len(), 16);
+    let art = SpriteArt(&font[..1]).to_string();
+    assert_eq!(art, "font_0  5 rows, font\n    050  F0  ####....\n    051  90  #..#....\n    052  90  #..#....\n    053  90  #..#....\n    054  F0  ####....\n");
+}
+
+#[test]
+fn sheet() {
+    let mut png = Vec::new();
+    write_sprite_sheet(&mut png, &font_glyphs(), 2).unwrap();
+    let decoder = png::Decoder::new(&png[..]);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height), (2 * (1 + 16 * 9), 2 * (1 + 16)));
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+    // the top left of the 0 is lit, just right of the grey border
+    assert_eq!(&pixels[..4], &[0x40, 0x40, 0x40, 0x40]);
+    assert_eq!(pixels[(2 * info.width as usize) + 2], 0xFF);
+    assert!(write_sprite_sheet(Vec::new(), &font_glyphs(), 0).is_err());
+}
@@ -0,0 +1,36 @@
+//! Frames per second across a batch, run with `cargo bench -p rust8-batch`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust8_batch::Batch;
+use rust8_core::backend::BackendKind;
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_frame");
+    for &count in &[1, 64, 1024, 4096] {
+        group.throughput(Throughput::Elements(count as u64));
+        for &(name, threads) in &[("1 thread", 1), ("all cores", 0)] {
+            let mut batch = Batch::new(count, MAZE, 0).unwrap().with_threads(threads).unwrap();
+            for (i, input) in batch.keypad_mut().iter_mut().enumerate() {
+                *input = 1 << (i % 16);
+            }
+            let _ = group.bench_with_input(BenchmarkId::new(name, count), &count, |b, _| b.iter(|| batch.run_frame()));
+        }
+    }
+    group.finish();
+}
+
+fn backends(c: &mut Criterion) {
+    let count = 1024;
+    let mut group = c.benchmark_group("backend");
+    group.throughput(Throughput::Elements(count as u64));
+    for &kind in BackendKind::ALL.iter() {
+        let mut batch = Batch::new(count, MAZE, 0).unwrap().with_backend(kind);
+        let _ = group.bench_function(kind.name(), |b| b.iter(|| batch.run_frame()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput, backends);
+criterion_main!(benches);
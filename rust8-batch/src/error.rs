@@ -0,0 +1,51 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use rayon::ThreadPoolBuildError;
+
+#[derive(Debug)]
+pub enum BatchError {
+    /// the rom couldn't be loaded into the instances
+    Rom(rust8_core::error::Error),
+    /// a save state couldn't be restored into an instance
+    State(rust8_core::error::Error),
+    ThreadPool(ThreadPoolBuildError),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Rom(e) => write!(f, "could not load rom: {}", e),
+            BatchError::State(e) => write!(f, "could not load state: {}", e),
+            BatchError::ThreadPool(e) => write!(f, "could not start thread pool: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+impl From<rust8_core::error::Error> for BatchError {
+    fn from(e: rust8_core::error::Error) -> Self {
+        BatchError::Rom(e)
+    }
+}
+
+impl From<ThreadPoolBuildError> for BatchError {
+    fn from(e: ThreadPoolBuildError) -> Self {
+        BatchError::ThreadPool(e)
+    }
+}
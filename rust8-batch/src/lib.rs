@@ -0,0 +1,469 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//! Run many emulators at once, a frame at a time, spread over a thread pool.
+//!
+//! A batch keeps its instances as a struct of arrays: one array per field with an entry per
+//! instance, so registers, memory, pc, I, timers, framebuffers and keypads of the whole batch each sit
+//! in one place. A host can fill all the keypads, run a frame and hand the framebuffers to a model
+//! without going through the instances one at a time.
+//!
+//! Each thread steps its share of the instances by index through a scratch `Emulator`, copying an
+//! instance's fields in, running its frame with the instance's backend and copying them back out.
+
+#![forbid(unsafe_code)]
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod error;
+
+pub use crate::error::BatchError;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+/// bytes of each instance's framebuffer
+pub const FRAME_SIZE: usize = DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize;
+
+/// instances a thread runs one after another without splitting them any further
+const CHUNK: usize = 64;
+
+// backends move between the pool's threads
+const _: () = {
+    const fn send<T: Send>() {}
+    send::<Backend>()
+};
+
+pub struct Batch {
+    machines: Machines,
+    pool: ThreadPool,
+}
+
+/// every instance's state, an array per field with an entry per instance
+struct Machines {
+    registers: Vec<[u8; 16]>,
+    memory: Vec<[u8; 4096]>,
+    index: Vec<u16>,
+    pc: Vec<u16>,
+    sp: Vec<u8>,
+    stack: Vec<[u16; 16]>,
+    delay: Vec<u8>,
+    sound: Vec<u8>,
+    /// last instruction each instance executed
+    instruction: Vec<u16>,
+    /// every instance's display back to back, one byte per pixel, 1 where it's lit
+    vram: Vec<u8>,
+    /// keypad bitmask each instance holds during the next frame, bit n for key n
+    keypad: Vec<u16>,
+    /// keys each instance held during the last frame, to tell new presses apart
+    held: Vec<u16>,
+    /// press each instance hasn't handed to Fx0A yet
+    pending: Vec<Option<u8>>,
+    rng: Vec<Rng>,
+    quirks: Vec<Quirks>,
+    tickrate: Vec<u32>,
+    /// how each instance executes instructions
+    backends: Vec<Backend>,
+}
+
+impl Batch {
+    /// Create count instances running rom, instance i seeds its random numbers with seed + i.
+    /// The pool gets a thread per core.
+    pub fn new(count: usize, rom: &[u8], seed: u64) -> Result<Self, BatchError> {
+        let mut emulators = Vec::with_capacity(count);
+        for i in 0..count {
+            let mut emu = Emulator::new();
+            emu.rng = Rng::from_seed(seed.wrapping_add(i as u64));
+            emu.load_rom(rom)?;
+            emulators.push(emu);
+        }
+        Batch::from_emulators(emulators)
+    }
+
+    /// Batch up emulators that are already set up, with their own quirks or tickrates.
+    /// Host callbacks and access or profile recording are left behind.
+    pub fn from_emulators(mut emulators: Vec<Emulator>) -> Result<Self, BatchError> {
+        let count = emulators.len();
+        let mut machines = Machines {
+            registers: vec![[0; 16]; count],
+            memory: vec![[0; 4096]; count],
+            index: vec![0; count],
+            pc: vec![0; count],
+            sp: vec![0; count],
+            stack: vec![[0; 16]; count],
+            delay: vec![0; count],
+            sound: vec![0; count],
+            instruction: vec![0; count],
+            vram: vec![0; count * FRAME_SIZE],
+            keypad: vec![0; count],
+            held: vec![0; count],
+            pending: vec![None; count],
+            rng: vec![Rng::from_seed(1); count],
+            quirks: vec![Quirks::default(); count],
+            tickrate: vec![0; count],
+            backends: emulators.iter().map(|_| Backend::default()).collect(),
+        };
+        let mut lanes = machines.lanes();
+        for (i, emu) in emulators.iter_mut().enumerate() {
+            emu.display.dirty = true;
+            lanes.store(i, emu);
+        }
+        machines.keypad.copy_from_slice(&machines.held);
+        Ok(Batch { machines, pool: ThreadPoolBuilder::new().build()? })
+    }
+
+    /// Use a pool of this many threads instead, 0 picks one per core
+    pub fn with_threads(mut self, threads: usize) -> Result<Self, BatchError> {
+        self.pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+        Ok(self)
+    }
+
+    /// Execute instructions with another backend, the interpreter by default
+    pub fn with_backend(mut self, kind: BackendKind) -> Self {
+        self.machines.backends = self.machines.backends.iter().map(|_| Backend::new(kind)).collect();
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.pc.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.pc.is_empty()
+    }
+
+    /// Run one frame on every instance with its keypad held
+    pub fn run_frame(&mut self) {
+        let lanes = self.machines.lanes();
+        self.pool.install(|| run_frames(lanes));
+    }
+
+    /// Keypad bitmasks for the next frame, one per instance
+    pub fn keypad_mut(&mut self) -> &mut [u16] {
+        &mut self.machines.keypad
+    }
+
+    pub fn keypad(&self) -> &[u16] {
+        &self.machines.keypad
+    }
+
+    /// Every framebuffer back to back, FRAME_SIZE bytes each
+    pub fn frames(&self) -> &[u8] {
+        &self.machines.vram
+    }
+
+    /// The framebuffer of a single instance
+    pub fn frame(&self, instance: usize) -> &[u8] {
+        &self.machines.vram[instance * FRAME_SIZE..(instance + 1) * FRAME_SIZE]
+    }
+
+    pub fn registers(&self) -> &[[u8; 16]] {
+        &self.machines.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut [[u8; 16]] {
+        &mut self.machines.registers
+    }
+
+    pub fn memory(&self, instance: usize) -> &[u8; 4096] {
+        &self.machines.memory[instance]
+    }
+
+    /// An instance's memory to write to, its backend forgets what it compiled from the old contents
+    pub fn memory_mut(&mut self, instance: usize) -> &mut [u8; 4096] {
+        self.machines.backends[instance].invalidate();
+        &mut self.machines.memory[instance]
+    }
+
+    pub fn pc(&self) -> &[u16] {
+        &self.machines.pc
+    }
+
+    pub fn pc_mut(&mut self) -> &mut [u16] {
+        &mut self.machines.pc
+    }
+
+    /// The I register of each instance
+    pub fn index(&self) -> &[u16] {
+        &self.machines.index
+    }
+
+    pub fn index_mut(&mut self) -> &mut [u16] {
+        &mut self.machines.index
+    }
+
+    pub fn delay(&self) -> &[u8] {
+        &self.machines.delay
+    }
+
+    pub fn delay_mut(&mut self) -> &mut [u8] {
+        &mut self.machines.delay
+    }
+
+    /// Each instance's sound timer, a tone plays while it's above zero
+    pub fn sound(&self) -> &[u8] {
+        &self.machines.sound
+    }
+
+    pub fn sound_mut(&mut self) -> &mut [u8] {
+        &mut self.machines.sound
+    }
+
+    /// A standalone copy of an instance, for save states or anything else the arrays leave out
+    pub fn emulator(&self, instance: usize) -> Emulator {
+        let mut emu = Emulator::new();
+        self.machines.instance(instance).load(&mut emu);
+        emu
+    }
+
+    /// Restore a save state into an instance, which keeps its quirks and tickrate
+    pub fn load_state(&mut self, instance: usize, state: &[u8]) -> Result<(), BatchError> {
+        let mut emu = self.emulator(instance);
+        emu.load_state(state).map_err(BatchError::State)?;
+        self.machines.backends[instance].invalidate();
+        self.machines.lanes().store(instance, &mut emu);
+        self.machines.keypad[instance] = self.machines.held[instance];
+        Ok(())
+    }
+}
+
+/// instance i of anything with every array as a field, Machines or Lanes
+macro_rules! instance {
+    ($arrays:expr, $i:expr) => {{
+        let (arrays, i) = ($arrays, $i);
+        Instance {
+            registers: &arrays.registers[i],
+            memory: &arrays.memory[i],
+            index: arrays.index[i],
+            pc: arrays.pc[i],
+            sp: arrays.sp[i],
+            stack: &arrays.stack[i],
+            delay: arrays.delay[i],
+            sound: arrays.sound[i],
+            instruction: arrays.instruction[i],
+            vram: &arrays.vram[i * FRAME_SIZE..(i + 1) * FRAME_SIZE],
+            held: arrays.held[i],
+            pending: arrays.pending[i],
+            rng: arrays.rng[i],
+            quirks: arrays.quirks[i],
+            tickrate: arrays.tickrate[i],
+        }
+    }};
+}
+
+impl Machines {
+    fn instance(&self, i: usize) -> Instance<'_> {
+        instance!(self, i)
+    }
+
+    fn lanes(&mut self) -> Lanes<'_> {
+        Lanes {
+            registers: &mut self.registers,
+            memory: &mut self.memory,
+            index: &mut self.index,
+            pc: &mut self.pc,
+            sp: &mut self.sp,
+            stack: &mut self.stack,
+            delay: &mut self.delay,
+            sound: &mut self.sound,
+            instruction: &mut self.instruction,
+            vram: &mut self.vram,
+            keypad: &mut self.keypad,
+            held: &mut self.held,
+            pending: &mut self.pending,
+            rng: &mut self.rng,
+            quirks: &mut self.quirks,
+            tickrate: &mut self.tickrate,
+            backends: &mut self.backends,
+        }
+    }
+}
+
+/// run a frame on every instance in lanes, splitting them between threads
+fn run_frames(mut lanes: Lanes<'_>) {
+    let count = lanes.len();
+    if count > CHUNK {
+        let (left, right) = lanes.split_at(count / 2);
+        let _ = rayon::join(|| run_frames(left), || run_frames(right));
+        return;
+    }
+    let mut emu = Emulator::new();
+    for i in 0..count {
+        lanes.instance(i).load(&mut emu);
+        emu.keyboard.set_keypad(lanes.keypad[i]);
+        lanes.backends[i].run_frame(&mut emu);
+        lanes.backends[i].sync(&mut emu);
+        lanes.store(i, &mut emu);
+    }
+}
+
+/// one instance's entry in each array
+struct Instance<'a> {
+    registers: &'a [u8; 16],
+    memory: &'a [u8; 4096],
+    index: u16,
+    pc: u16,
+    sp: u8,
+    stack: &'a [u16; 16],
+    delay: u8,
+    sound: u8,
+    instruction: u16,
+    vram: &'a [u8],
+    held: u16,
+    pending: Option<u8>,
+    rng: Rng,
+    quirks: Quirks,
+    tickrate: u32,
+}
+
+impl Instance<'_> {
+    /// copy the instance into emu, replacing whatever was there
+    fn load(&self, emu: &mut Emulator) {
+        emu.registers = *self.registers;
+        emu.memory = *self.memory;
+        emu.index = self.index;
+        emu.pc = self.pc;
+        emu.sp = self.sp;
+        emu.stack = *self.stack;
+        emu.delay = self.delay;
+        emu.sound = self.sound;
+        emu.instruction = self.instruction;
+        for (pixel, &byte) in emu.display.vram.iter_mut().zip(self.vram) {
+            *pixel = byte != 0;
+        }
+        emu.display.dirty = false;
+        let _ = emu.keyboard.take_keypress();
+        if let Some(key) = self.pending {
+            emu.keyboard.press_key(key);
+        }
+        for (key, down) in emu.keyboard.keypad.iter_mut().enumerate() {
+            *down = self.held & (1 << key) != 0;
+        }
+        emu.rng = self.rng;
+        emu.quirks = self.quirks;
+        emu.tickrate = self.tickrate;
+    }
+}
+
+/// the same instances in every array, borrowed mutably so they can be split between threads
+struct Lanes<'a> {
+    registers: &'a mut [[u8; 16]],
+    memory: &'a mut [[u8; 4096]],
+    index: &'a mut [u16],
+    pc: &'a mut [u16],
+    sp: &'a mut [u8],
+    stack: &'a mut [[u16; 16]],
+    delay: &'a mut [u8],
+    sound: &'a mut [u8],
+    instruction: &'a mut [u16],
+    vram: &'a mut [u8],
+    keypad: &'a mut [u16],
+    held: &'a mut [u16],
+    pending: &'a mut [Option<u8>],
+    rng: &'a mut [Rng],
+    quirks: &'a mut [Quirks],
+    tickrate: &'a mut [u32],
+    backends: &'a mut [Backend],
+}
+
+impl<'a> Lanes<'a> {
+    fn len(&self) -> usize {
+        self.pc.len()
+    }
+
+    /// the first mid instances and the rest
+    fn split_at(self, mid: usize) -> (Lanes<'a>, Lanes<'a>) {
+        let (registers, registers_right) = self.registers.split_at_mut(mid);
+        let (memory, memory_right) = self.memory.split_at_mut(mid);
+        let (index, index_right) = self.index.split_at_mut(mid);
+        let (pc, pc_right) = self.pc.split_at_mut(mid);
+        let (sp, sp_right) = self.sp.split_at_mut(mid);
+        let (stack, stack_right) = self.stack.split_at_mut(mid);
+        let (delay, delay_right) = self.delay.split_at_mut(mid);
+        let (sound, sound_right) = self.sound.split_at_mut(mid);
+        let (instruction, instruction_right) = self.instruction.split_at_mut(mid);
+        let (vram, vram_right) = self.vram.split_at_mut(mid * FRAME_SIZE);
+        let (keypad, keypad_right) = self.keypad.split_at_mut(mid);
+        let (held, held_right) = self.held.split_at_mut(mid);
+        let (pending, pending_right) = self.pending.split_at_mut(mid);
+        let (rng, rng_right) = self.rng.split_at_mut(mid);
+        let (quirks, quirks_right) = self.quirks.split_at_mut(mid);
+        let (tickrate, tickrate_right) = self.tickrate.split_at_mut(mid);
+        let (backends, backends_right) = self.backends.split_at_mut(mid);
+        (
+            Lanes {
+                registers, memory, index, pc, sp, stack, delay, sound, instruction,
+                vram, keypad, held, pending, rng, quirks, tickrate, backends,
+            },
+            Lanes {
+                registers: registers_right,
+                memory: memory_right,
+                index: index_right,
+                pc: pc_right,
+                sp: sp_right,
+                stack: stack_right,
+                delay: delay_right,
+                sound: sound_right,
+                instruction: instruction_right,
+                vram: vram_right,
+                keypad: keypad_right,
+                held: held_right,
+                pending: pending_right,
+                rng: rng_right,
+                quirks: quirks_right,
+                tickrate: tickrate_right,
+                backends: backends_right,
+            },
+        )
+    }
+
+    fn instance(&self, i: usize) -> Instance<'_> {
+        instance!(self, i)
+    }
+
+    /// copy emu back into instance i, taking its pending keypress
+    fn store(&mut self, i: usize, emu: &mut Emulator) {
+        self.registers[i] = emu.registers;
+        self.memory[i] = emu.memory;
+        self.index[i] = emu.index;
+        self.pc[i] = emu.pc;
+        self.sp[i] = emu.sp;
+        self.stack[i] = emu.stack;
+        self.delay[i] = emu.delay;
+        self.sound[i] = emu.sound;
+        self.instruction[i] = emu.instruction;
+        if emu.display.dirty {
+            let frame = &mut self.vram[i * FRAME_SIZE..(i + 1) * FRAME_SIZE];
+            for (byte, &pixel) in frame.iter_mut().zip(emu.display.vram.iter()) {
+                *byte = pixel as u8;
+            }
+        }
+        self.held[i] = emu.keyboard.keypad.iter().rev().fold(0, |bits, &down| bits << 1 | down as u16);
+        self.pending[i] = emu.keyboard.take_keypress();
+        self.rng[i] = emu.rng;
+        self.quirks[i] = emu.quirks;
+        self.tickrate[i] = emu.tickrate;
+    }
+}
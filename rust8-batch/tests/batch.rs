@@ -0,0 +1,192 @@
+//! Batches against standalone emulators, with per instance keypads and any number of threads
+
+use rust8_batch::{Batch, FRAME_SIZE};
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::constants::FONTSET_START;
+use rust8_core::emu::Emulator;
+use rust8_core::rng::Rng;
+use rust8_core::state::STATE_SIZE;
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+/// rewrites the ADD V2 at 0x208 into ADD V2, 5 with its sixth instruction
+const SELF_MODIFYING: [u8; 20] = [
+    0x60, 0x72, // LD V0, 0x72
+    0x61, 0x05, // LD V1, 0x05
+    0xA2, 0x08, // LD I, 0x208
+    0x73, 0x01, // ADD V3, 1
+    0x72, 0x01, // ADD V2, 1
+    0xF1, 0x55, // LD [I], V1
+    0x33, 0x02, // SE V3, 2
+    0x12, 0x04, // JP 0x204
+    0x12, 0x10, // JP 0x210
+    0x00, 0x00,
+];
+
+/// waits for a key, then draws its digit and sets both timers to it
+const KEY_DIGIT: [u8; 12] = [
+    0xF0, 0x0A, // LD V0, K
+    0xF0, 0x29, // LD F, V0
+    0xD1, 0x15, // DRW V1, V1, 5
+    0xF0, 0x15, // LD DT, V0
+    0xF0, 0x18, // LD ST, V0
+    0x12, 0x0A, // JP 0x20A
+];
+
+/// a random byte stored as bcd and a random sprite drawn, over and over
+const NOISE: [u8; 14] = [
+    0xC0, 0xFF, // RND V0, 0xFF
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x33, // LD B, V0
+    0xC1, 0x3F, // RND V1, 0x3F
+    0xC2, 0x1F, // RND V2, 0x1F
+    0xD1, 0x23, // DRW V1, V2, 3
+    0x12, 0x00, // JP 0x200
+];
+
+fn emulator(rom: &[u8], seed: u64, tickrate: u32) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.load_rom(rom).unwrap();
+    emu.rng = Rng::from_seed(seed);
+    emu.tickrate = tickrate;
+    emu
+}
+
+fn assert_instance_matches(batch: &Batch, instance: usize, emu: &Emulator) {
+    assert_eq!(batch.registers()[instance], emu.registers, "registers of {}", instance);
+    assert_eq!(batch.pc()[instance], emu.pc, "pc of {}", instance);
+    assert_eq!(batch.index()[instance], emu.index, "I of {}", instance);
+    assert_eq!(batch.delay()[instance], emu.delay, "delay of {}", instance);
+    assert_eq!(batch.sound()[instance], emu.sound, "sound of {}", instance);
+    assert!(batch.memory(instance)[..] == emu.memory[..], "memory of {}", instance);
+    let vram: Vec<u8> = emu.display.vram.iter().map(|&pixel| pixel as u8).collect();
+    assert!(batch.frame(instance) == &vram[..], "framebuffer of {}", instance);
+}
+
+/// pixels lit by the font's sprite for a hex digit
+fn lit_pixels(digit: u8) -> usize {
+    let emu = Emulator::new();
+    let start = usize::from(FONTSET_START) + usize::from(digit) * 5;
+    let sprite = &emu.memory[start..start + 5];
+    sprite.iter().map(|row| row.count_ones() as usize).sum()
+}
+
+#[test]
+fn keypads_are_per_instance() {
+    let mut batch = Batch::new(40, &KEY_DIGIT, 0).unwrap();
+    for (i, keys) in batch.keypad_mut().iter_mut().take(32).enumerate() {
+        *keys = 1 << (i % 16);
+    }
+    batch.run_frame();
+
+    for i in 0..32 {
+        let key = (i % 16) as u8;
+        assert_eq!(batch.registers()[i][0], key, "V0 of {}", i);
+        assert_eq!(batch.pc()[i], 0x20A);
+        // counted down once at the end of the frame that set them
+        assert_eq!((batch.delay()[i], batch.sound()[i]), (key.saturating_sub(1), key.saturating_sub(1)));
+        assert_eq!(batch.frame(i).iter().filter(|&&pixel| pixel == 1).count(), lit_pixels(key), "digit of {}", i);
+    }
+    // the rest held nothing and are still waiting
+    for i in 32..40 {
+        assert_eq!(batch.pc()[i], 0x200, "pc of {}", i);
+        assert!(batch.frame(i).iter().all(|&pixel| pixel == 0));
+    }
+
+    // a key that's still held isn't a new press
+    let mut batch = Batch::new(1, &[0xF0, 0x0A, 0xF1, 0x0A, 0x12, 0x04], 0).unwrap();
+    batch.keypad_mut()[0] = 1 << 3;
+    batch.run_frame();
+    batch.run_frame();
+    assert_eq!(batch.pc(), [0x202]);
+    batch.keypad_mut()[0] = 1 << 3 | 1 << 7;
+    batch.run_frame();
+    assert_eq!(batch.pc(), [0x204]);
+    assert_eq!(batch.registers()[0][..2], [3, 7]);
+}
+
+#[test]
+fn same_result_on_any_number_of_threads() {
+    let run = |threads| {
+        let emulators = (0..300).map(|i| emulator(if i % 2 == 0 { &NOISE } else { MAZE }, i, 5 + i as u32 % 13)).collect();
+        let mut batch = Batch::from_emulators(emulators).unwrap().with_threads(threads).unwrap();
+        for frame in 0..20 {
+            for (i, keys) in batch.keypad_mut().iter_mut().enumerate() {
+                *keys = 1 << ((i + frame) % 16);
+            }
+            batch.run_frame();
+        }
+        batch
+    };
+    let one = run(1);
+    assert!(one.frames().contains(&1));
+    for &threads in &[2, 7] {
+        let many = run(threads);
+        assert!(many.frames() == one.frames(), "frames on {} threads", threads);
+        assert_eq!(many.registers(), one.registers());
+        assert_eq!(many.pc(), one.pc());
+        assert_eq!(many.index(), one.index());
+        assert!((0..one.len()).all(|i| many.memory(i)[..] == one.memory(i)[..]), "memory on {} threads", threads);
+    }
+}
+
+#[test]
+fn backends_match_standalone_emulators() {
+    let roms: [&[u8]; 4] = [&SELF_MODIFYING, &KEY_DIGIT, &NOISE, MAZE];
+    // tickrate 6 ends SELF_MODIFYING's first frame on its write, which the next frame has to see
+    let setup = |i: usize| emulator(roms[i % roms.len()], i as u64, 6 + i as u32 % 5);
+    let count = 200;
+    for &kind in BackendKind::ALL.iter() {
+        let mut batch = Batch::from_emulators((0..count).map(setup).collect()).unwrap().with_backend(kind);
+        let mut singles: Vec<(Emulator, Backend)> = (0..count).map(|i| (setup(i), Backend::new(kind))).collect();
+
+        for frame in 0..30 {
+            for (i, (emu, backend)) in singles.iter_mut().enumerate() {
+                let keys = if (i + frame) % 3 == 0 { 1 << (i % 16) } else { 0 };
+                batch.keypad_mut()[i] = keys;
+                emu.keyboard.set_keypad(keys);
+                backend.run_frame(emu);
+            }
+            batch.run_frame();
+            for (i, (emu, _)) in singles.iter().enumerate() {
+                assert_instance_matches(&batch, i, emu);
+            }
+        }
+        assert_eq!(batch.registers()[0][2], 6, "{} ran the rewritten add", kind);
+    }
+}
+
+#[test]
+fn writes_and_save_states_reach_the_backend() {
+    for &kind in BackendKind::ALL.iter() {
+        let mut batch = Batch::new(2, &[0x60, 0x01, 0x12, 0x00], 0).unwrap().with_backend(kind);
+        batch.run_frame();
+        assert_eq!(batch.registers()[0][0], 1);
+
+        // LD V0, 2 in place of LD V0, 1 for the first instance only
+        batch.memory_mut(0)[0x201] = 2;
+        batch.run_frame();
+        assert_eq!((batch.registers()[0][0], batch.registers()[1][0]), (2, 1), "{}", kind);
+
+        let mut state = vec![0; STATE_SIZE];
+        emulator(&[0x60, 0x03, 0x12, 0x00], 1, 15).save_state(&mut state).unwrap();
+        batch.load_state(1, &state).unwrap();
+        batch.run_frame();
+        assert_eq!(batch.registers()[1][0], 3, "{}", kind);
+        assert!(batch.load_state(1, &state[..10]).is_err());
+
+        assert_instance_matches(&batch, 1, &batch.emulator(1));
+    }
+}
+
+#[test]
+fn frames_are_laid_out_back_to_back() {
+    let mut batch = Batch::new(3, &KEY_DIGIT, 0).unwrap();
+    batch.keypad_mut()[1] = 1 << 8;
+    batch.run_frame();
+    assert_eq!(batch.frames().len(), 3 * FRAME_SIZE);
+    assert!(batch.frames()[..FRAME_SIZE].iter().all(|&pixel| pixel == 0));
+    assert!(batch.frames()[FRAME_SIZE..2 * FRAME_SIZE] == *batch.frame(1));
+    assert!(batch.frame(1).contains(&1));
+    assert!(batch.frames()[2 * FRAME_SIZE..].iter().all(|&pixel| pixel == 0));
+}
@@ -0,0 +1,160 @@
+#ifndef RUST8_H
+#define RUST8_H
+
+/* generated by cbindgen from rust8-capi, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * width of the display in pixels
+ */
+#define RUST8_DISPLAY_WIDTH 64
+
+/**
+ * height of the display in pixels
+ */
+#define RUST8_DISPLAY_HEIGHT 32
+
+/**
+ * bytes written by rust8_emulator_get_framebuffer, one per pixel
+ */
+#define RUST8_FRAMEBUFFER_SIZE (RUST8_DISPLAY_WIDTH * RUST8_DISPLAY_HEIGHT)
+
+/**
+ * Result of every fallible call
+ */
+typedef enum rust8_error {
+  RUST8_ERROR_OK = 0,
+  /**
+   * a required pointer argument was null
+   */
+  RUST8_ERROR_NULL_POINTER = 1,
+  /**
+   * the rom doesn't fit in memory above 0x200
+   */
+  RUST8_ERROR_ROM_TOO_LARGE = 2,
+  /**
+   * an output buffer or save state is smaller than required
+   */
+  RUST8_ERROR_BUFFER_TOO_SMALL = 3,
+  /**
+   * a save state is corrupt or from an incompatible version
+   */
+  RUST8_ERROR_INVALID_STATE = 4,
+  /**
+   * a key outside 0-F
+   */
+  RUST8_ERROR_INVALID_KEY = 5,
+  /**
+   * rust8 panicked, the emulator may be left half updated
+   */
+  RUST8_ERROR_PANICKED = 6,
+} rust8_error;
+
+/**
+ * An emulator, only ever handled through a pointer from rust8_emulator_create
+ */
+typedef struct rust8_emulator rust8_emulator;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create an emulator with its random number generator seeded from seed,
+ * free it with rust8_emulator_destroy, null if that failed
+ */
+struct rust8_emulator *rust8_emulator_create(uint64_t seed);
+
+/**
+ * Free an emulator, passing null does nothing
+ *
+ * # Safety
+ * emulator must be null or come from rust8_emulator_create and not have been destroyed already
+ */
+void rust8_emulator_destroy(struct rust8_emulator *emulator);
+
+/**
+ * Load a rom at 0x200, the rest of the emulator is left as it is
+ *
+ * # Safety
+ * emulator must be a live emulator and rom must point to len readable bytes
+ */
+enum rust8_error rust8_emulator_load_rom(struct rust8_emulator *emulator,
+                                         const uint8_t *rom,
+                                         size_t len);
+
+/**
+ * Execute cycles instructions without touching the timers or polling the host
+ *
+ * # Safety
+ * emulator must be a live emulator
+ */
+enum rust8_error rust8_emulator_run_cycles(struct rust8_emulator *emulator, uint32_t cycles);
+
+/**
+ * Run a 60hz frame, the emulator's tickrate worth of instructions and one timer tick
+ *
+ * # Safety
+ * emulator must be a live emulator
+ */
+enum rust8_error rust8_emulator_run_frame(struct rust8_emulator *emulator);
+
+/**
+ * Press or release a keypad key, 0x0 to 0xF
+ *
+ * # Safety
+ * emulator must be a live emulator
+ */
+enum rust8_error rust8_emulator_set_key(struct rust8_emulator *emulator, uint8_t key, bool down);
+
+/**
+ * Copy the display into out, one byte per pixel row by row, 1 for lit and 0 for unlit.
+ * out must hold at least RUST8_FRAMEBUFFER_SIZE bytes.
+ *
+ * # Safety
+ * emulator must be a live emulator and out must point to len writable bytes
+ */
+enum rust8_error rust8_emulator_get_framebuffer(const struct rust8_emulator *emulator,
+                                                uint8_t *out,
+                                                size_t len);
+
+/**
+ * Size in bytes of a save state
+ */
+size_t rust8_state_size(void);
+
+/**
+ * Write a save state into out, which must hold at least rust8_state_size() bytes
+ *
+ * # Safety
+ * emulator must be a live emulator and out must point to len writable bytes
+ */
+enum rust8_error rust8_emulator_save_state(const struct rust8_emulator *emulator,
+                                           uint8_t *out,
+                                           size_t len);
+
+/**
+ * Restore a save state written by rust8_emulator_save_state, the emulator is untouched on error
+ *
+ * # Safety
+ * emulator must be a live emulator and state must point to len readable bytes
+ */
+enum rust8_error rust8_emulator_load_state(struct rust8_emulator *emulator,
+                                           const uint8_t *state,
+                                           size_t len);
+
+/**
+ * A static, nul terminated description of an error
+ */
+const char *rust8_error_message(enum rust8_error error);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUST8_H */
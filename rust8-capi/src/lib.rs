@@ -0,0 +1,246 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A C API for rust8-core, the header is generated into `include/rust8.h` on every build.
+//!
+//! Link against `librust8_capi.so` or `librust8_capi.a` and drive the emulator through an opaque
+//! `rust8_emulator*`. Every function that can fail returns a `rust8_error`, `RUST8_ERROR_OK` on success.
+
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use rust8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rust8_core::emu::Emulator;
+use rust8_core::error::Error;
+use rust8_core::rng::Rng;
+use rust8_core::state::STATE_SIZE;
+
+/// width of the display in pixels
+pub const RUST8_DISPLAY_WIDTH: usize = 64;
+/// height of the display in pixels
+pub const RUST8_DISPLAY_HEIGHT: usize = 32;
+/// bytes written by rust8_emulator_get_framebuffer, one per pixel
+pub const RUST8_FRAMEBUFFER_SIZE: usize = RUST8_DISPLAY_WIDTH * RUST8_DISPLAY_HEIGHT;
+
+// cbindgen can't see the core's constants so they're spelled out above
+const _: () = assert!(RUST8_DISPLAY_WIDTH == DISPLAY_WIDTH as usize && RUST8_DISPLAY_HEIGHT == DISPLAY_HEIGHT as usize);
+
+/// An emulator, only ever handled through a pointer from rust8_emulator_create
+pub struct Rust8Emulator {
+    emu: Emulator,
+}
+
+/// Result of every fallible call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rust8Error {
+    Ok = 0,
+    /// a required pointer argument was null
+    NullPointer = 1,
+    /// the rom doesn't fit in memory above 0x200
+    RomTooLarge = 2,
+    /// an output buffer or save state is smaller than required
+    BufferTooSmall = 3,
+    /// a save state is corrupt or from an incompatible version
+    InvalidState = 4,
+    /// a key outside 0-F
+    InvalidKey = 5,
+    /// rust8 panicked, the emulator may be left half updated
+    Panicked = 6,
+}
+
+impl From<Error> for Rust8Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::RomTooLarge(_) => Rust8Error::RomTooLarge,
+            Error::StateBufferTooSmall(_) => Rust8Error::BufferTooSmall,
+            Error::InvalidState => Rust8Error::InvalidState,
+        }
+    }
+}
+
+impl From<Result<(), Error>> for Rust8Error {
+    fn from(result: Result<(), Error>) -> Self {
+        result.map_or_else(Rust8Error::from, |()| Rust8Error::Ok)
+    }
+}
+
+/// Run an entry point, turning a panic into Rust8Error::Panicked instead of unwinding into C
+fn guard(body: impl FnOnce() -> Rust8Error) -> Rust8Error {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(Rust8Error::Panicked)
+}
+
+/// Create an emulator with its random number generator seeded from seed,
+/// free it with rust8_emulator_destroy, null if that failed
+#[no_mangle]
+pub extern "C" fn rust8_emulator_create(seed: u64) -> *mut Rust8Emulator {
+    panic::catch_unwind(|| {
+        let mut emu = Emulator::new();
+        emu.rng = Rng::from_seed(seed);
+        Box::into_raw(Box::new(Rust8Emulator { emu }))
+    }).unwrap_or(ptr::null_mut())
+}
+
+/// Free an emulator, passing null does nothing
+///
+/// # Safety
+/// emulator must be null or come from rust8_emulator_create and not have been destroyed already
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_destroy(emulator: *mut Rust8Emulator) {
+    if !emulator.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(emulator))));
+    }
+}
+
+/// Load a rom at 0x200, the rest of the emulator is left as it is
+///
+/// # Safety
+/// emulator must be a live emulator and rom must point to len readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_load_rom(emulator: *mut Rust8Emulator, rom: *const u8, len: usize) -> Rust8Error {
+    guard(|| match (emulator.as_mut(), rom.is_null()) {
+        (Some(emulator), false) => emulator.emu.load_rom(slice::from_raw_parts(rom, len)).into(),
+        _ => Rust8Error::NullPointer,
+    })
+}
+
+/// Execute cycles instructions without touching the timers or polling the host
+///
+/// # Safety
+/// emulator must be a live emulator
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_run_cycles(emulator: *mut Rust8Emulator, cycles: u32) -> Rust8Error {
+    guard(|| match emulator.as_mut() {
+        Some(emulator) => {
+            for _ in 0..cycles {
+                emulator.emu.tick();
+            }
+            Rust8Error::Ok
+        }
+        None => Rust8Error::NullPointer,
+    })
+}
+
+/// Run a 60hz frame, the emulator's tickrate worth of instructions and one timer tick
+///
+/// # Safety
+/// emulator must be a live emulator
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_run_frame(emulator: *mut Rust8Emulator) -> Rust8Error {
+    guard(|| match emulator.as_mut() {
+        Some(emulator) => {
+            emulator.emu.run_frame();
+            Rust8Error::Ok
+        }
+        None => Rust8Error::NullPointer,
+    })
+}
+
+/// Press or release a keypad key, 0x0 to 0xF
+///
+/// # Safety
+/// emulator must be a live emulator
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_set_key(emulator: *mut Rust8Emulator, key: u8, down: bool) -> Rust8Error {
+    guard(|| match emulator.as_mut() {
+        Some(_) if key >= 16 => Rust8Error::InvalidKey,
+        Some(emulator) if down => {
+            emulator.emu.keyboard.press_key(key);
+            Rust8Error::Ok
+        }
+        Some(emulator) => {
+            emulator.emu.keyboard.release_key(key);
+            Rust8Error::Ok
+        }
+        None => Rust8Error::NullPointer,
+    })
+}
+
+/// Copy the display into out, one byte per pixel row by row, 1 for lit and 0 for unlit.
+/// out must hold at least RUST8_FRAMEBUFFER_SIZE bytes.
+///
+/// # Safety
+/// emulator must be a live emulator and out must point to len writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_get_framebuffer(emulator: *const Rust8Emulator, out: *mut u8, len: usize) -> Rust8Error {
+    guard(|| match (emulator.as_ref(), out.is_null()) {
+        (Some(_), false) if len < RUST8_FRAMEBUFFER_SIZE => Rust8Error::BufferTooSmall,
+        (Some(emulator), false) => {
+            let out = slice::from_raw_parts_mut(out, RUST8_FRAMEBUFFER_SIZE);
+            for (byte, &pixel) in out.iter_mut().zip(emulator.emu.display.vram.iter()) {
+                *byte = pixel as u8;
+            }
+            Rust8Error::Ok
+        }
+        _ => Rust8Error::NullPointer,
+    })
+}
+
+/// Size in bytes of a save state
+#[no_mangle]
+pub extern "C" fn rust8_state_size() -> usize {
+    STATE_SIZE
+}
+
+/// Write a save state into out, which must hold at least rust8_state_size() bytes
+///
+/// # Safety
+/// emulator must be a live emulator and out must point to len writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_save_state(emulator: *const Rust8Emulator, out: *mut u8, len: usize) -> Rust8Error {
+    guard(|| match (emulator.as_ref(), out.is_null()) {
+        (Some(emulator), false) => emulator.emu.save_state(slice::from_raw_parts_mut(out, len)).into(),
+        _ => Rust8Error::NullPointer,
+    })
+}
+
+/// Restore a save state written by rust8_emulator_save_state, the emulator is untouched on error
+///
+/// # Safety
+/// emulator must be a live emulator and state must point to len readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn rust8_emulator_load_state(emulator: *mut Rust8Emulator, state: *const u8, len: usize) -> Rust8Error {
+    guard(|| match (emulator.as_mut(), state.is_null()) {
+        (Some(emulator), false) => emulator.emu.load_state(slice::from_raw_parts(state, len)).into(),
+        _ => Rust8Error::NullPointer,
+    })
+}
+
+/// A static, nul terminated description of an error
+#[no_mangle]
+pub extern "C" fn rust8_error_message(error: Rust8Error) -> *const c_char {
+    let message: &[u8] = match error {
+        Rust8Error::Ok => b"no error\0",
+        Rust8Error::NullPointer => b"a required pointer was null\0",
+        Rust8Error::RomTooLarge => b"rom is too large to fit in memory\0",
+        Rust8Error::BufferTooSmall => b"buffer is too small\0",
+        Rust8Error::InvalidState => b"save state is invalid or from a different version\0",
+        Rust8Error::InvalidKey => b"key is not a keypad key, expected 0-F\0",
+        Rust8Error::Panicked => b"rust8 panicked, the emulator may be in an inconsistent state\0",
+    };
+    message.as_ptr().cast()
+}
@@ -0,0 +1,100 @@
+/* exercises the C API the way an embedding tool would, exits non-zero on the first failure */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "rust8.h"
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,    \
+                    __LINE__, #cond);                                 \
+            exit(1);                                                  \
+        }                                                             \
+    } while (0)
+
+#define CHECK_OK(call) CHECK((call) == RUST8_ERROR_OK)
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    CHECK(file != NULL);
+    CHECK(fseek(file, 0, SEEK_END) == 0);
+    *len = (size_t)ftell(file);
+    rewind(file);
+    uint8_t *data = malloc(*len);
+    CHECK(data != NULL);
+    CHECK(fread(data, 1, *len, file) == *len);
+    fclose(file);
+    return data;
+}
+
+static size_t lit_pixels(const uint8_t *framebuffer) {
+    size_t lit = 0;
+    for (size_t i = 0; i < RUST8_FRAMEBUFFER_SIZE; i++) {
+        CHECK(framebuffer[i] <= 1);
+        lit += framebuffer[i];
+    }
+    return lit;
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+    size_t rom_len;
+    uint8_t *rom = read_file(argv[1], &rom_len);
+    uint8_t framebuffer[RUST8_FRAMEBUFFER_SIZE];
+    uint8_t saved_framebuffer[RUST8_FRAMEBUFFER_SIZE];
+
+    rust8_emulator *emu = rust8_emulator_create(42);
+    CHECK(emu != NULL);
+    CHECK_OK(rust8_emulator_load_rom(emu, rom, rom_len));
+
+    /* errors come back as codes rather than crashes */
+    static uint8_t huge[4096];
+    CHECK(rust8_emulator_load_rom(emu, huge, sizeof huge) == RUST8_ERROR_ROM_TOO_LARGE);
+    CHECK(rust8_emulator_load_rom(NULL, rom, rom_len) == RUST8_ERROR_NULL_POINTER);
+    CHECK(rust8_emulator_set_key(emu, 16, true) == RUST8_ERROR_INVALID_KEY);
+    CHECK(rust8_emulator_get_framebuffer(emu, framebuffer, 10) == RUST8_ERROR_BUFFER_TOO_SMALL);
+    CHECK(strlen(rust8_error_message(RUST8_ERROR_INVALID_STATE)) > 0);
+    CHECK(strlen(rust8_error_message(RUST8_ERROR_PANICKED)) > 0);
+
+    CHECK_OK(rust8_emulator_set_key(emu, 0x5, true));
+    CHECK_OK(rust8_emulator_set_key(emu, 0x5, false));
+    CHECK_OK(rust8_emulator_run_cycles(emu, 100));
+    for (int i = 0; i < 60; i++) {
+        CHECK_OK(rust8_emulator_run_frame(emu));
+    }
+    CHECK_OK(rust8_emulator_get_framebuffer(emu, framebuffer, sizeof framebuffer));
+    CHECK(lit_pixels(framebuffer) > 0);
+
+    /* a state restored into a fresh emulator picks up exactly where the first left off */
+    size_t state_len = rust8_state_size();
+    uint8_t *state = malloc(state_len);
+    CHECK(state != NULL);
+    CHECK(rust8_emulator_save_state(emu, state, state_len - 1) == RUST8_ERROR_BUFFER_TOO_SMALL);
+    CHECK_OK(rust8_emulator_save_state(emu, state, state_len));
+    for (int i = 0; i < 30; i++) {
+        CHECK_OK(rust8_emulator_run_frame(emu));
+    }
+    CHECK_OK(rust8_emulator_get_framebuffer(emu, saved_framebuffer, sizeof saved_framebuffer));
+
+    rust8_emulator *restored = rust8_emulator_create(0);
+    CHECK_OK(rust8_emulator_load_state(restored, state, state_len));
+    for (int i = 0; i < 30; i++) {
+        CHECK_OK(rust8_emulator_run_frame(restored));
+    }
+    CHECK_OK(rust8_emulator_get_framebuffer(restored, framebuffer, sizeof framebuffer));
+    CHECK(memcmp(framebuffer, saved_framebuffer, sizeof framebuffer) == 0);
+
+    memset(state, 0, state_len);
+    CHECK(rust8_emulator_load_state(restored, state, state_len) == RUST8_ERROR_INVALID_STATE);
+
+    rust8_emulator_destroy(restored);
+    rust8_emulator_destroy(emu);
+    rust8_emulator_destroy(NULL);
+    free(state);
+    free(rom);
+    puts("ok");
+    return 0;
+}
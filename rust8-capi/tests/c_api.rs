@@ -0,0 +1,43 @@
+//! Compiles tests/c/capi_test.c against the generated header and the built library, then runs it
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// cargo builds the cdylib into the deps directory next to the test binary,
+/// `cargo build` also copies it up into the profile directory
+fn library_dir() -> PathBuf {
+    let name = format!("{}rust8_capi{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX);
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    [deps.clone(), deps.parent().unwrap().to_path_buf()]
+        .iter()
+        .find(|dir| dir.join(&name).exists())
+        .cloned()
+        .unwrap_or_else(|| panic!("{} hasn't been built", name))
+}
+
+#[test]
+fn c_program_runs() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library = library_dir();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi_test");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg("-I").arg(manifest.join("include"))
+        .arg(manifest.join("tests/c/capi_test.c"))
+        .arg("-L").arg(&library)
+        .arg(format!("-Wl,-rpath,{}", library.display()))
+        .arg("-lrust8_capi")
+        .arg("-o").arg(&program)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {}: {}", compiler, e));
+    assert!(status.success(), "failed to compile capi_test.c");
+
+    let output = Command::new(&program).arg(manifest.join("../roms/maze.ch8")).output().unwrap();
+    assert!(output.status.success(), "capi_test failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ok");
+}
@@ -0,0 +1,40 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Screenshot and recording support for rust8.
+//!
+//! Everything in here works on a raw `Display.vram` buffer so it can be driven from a window
+//! or from an emulator running without one.
+
+#![forbid(unsafe_code)]
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod error;
+mod palette;
+mod recorder;
+mod screenshot;
+
+pub use crate::error::CaptureError;
+pub use crate::palette::Palette;
+pub use crate::recorder::GifRecorder;
+pub use crate::screenshot::{save_png, write_png, MAX_SCALE};
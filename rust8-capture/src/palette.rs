@@ -0,0 +1,49 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Colors used when turning vram into an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// rgb color of an unset pixel
+    pub off: [u8; 3],
+    /// rgb color of a set pixel
+    pub on: [u8; 3],
+}
+
+impl Palette {
+    pub const fn new(off: [u8; 3], on: [u8; 3]) -> Self {
+        Palette { off, on }
+    }
+
+    /// return the rgb color for a pixel
+    pub fn color(&self, pixel: bool) -> [u8; 3] {
+        if pixel { self.on } else { self.off }
+    }
+
+    /// return the palette packed as 0x00RRGGBB, the format minifb expects
+    pub fn to_u32(&self) -> (u32, u32) {
+        (pack(self.off), pack(self.on))
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new([0x00, 0x00, 0x00], [0xff, 0xff, 0xff])
+    }
+}
+
+fn pack(rgb: [u8; 3]) -> u32 {
+    (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32
+}
@@ -0,0 +1,108 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use gif::{Encoder, Frame, Repeat, SetParameter};
+
+use crate::screenshot::{indexed_pixels, scaled_size};
+use crate::{CaptureError, Palette};
+
+/// Frame rate the recorder assumes frames are pushed at
+pub const FRAMES_PER_SECOND: u64 = 60;
+
+/// Records a sequence of vram frames into a looping animated gif.
+///
+/// Frames are expected once per 60hz frame. Gif delays are in hundredths of a second, which
+/// can't represent 1/60s exactly, so identical consecutive frames are merged and each written
+/// frame gets the delay that keeps it aligned with the 60hz clock.
+pub struct GifRecorder<W: Write> {
+    encoder: Encoder<W>,
+    scale: u32,
+    pending: Option<[bool; 64 * 32]>,
+    /// frame number the pending frame started on
+    pending_start: u64,
+    frames: u64,
+}
+
+impl GifRecorder<BufWriter<File>> {
+    /// Start recording to the file at path
+    pub fn create<P: AsRef<Path>>(path: P, scale: u32, palette: &Palette) -> Result<Self, CaptureError> {
+        GifRecorder::new(BufWriter::new(File::create(path)?), scale, palette)
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, scale: u32, palette: &Palette) -> Result<Self, CaptureError> {
+        let (width, height) = scaled_size(scale)?;
+        let mut global_palette = palette.off.to_vec();
+        global_palette.extend_from_slice(&palette.on);
+        let mut encoder = Encoder::new(writer, width as u16, height as u16, &global_palette)?;
+        encoder.set(Repeat::Infinite)?;
+        Ok(GifRecorder {
+            encoder,
+            scale,
+            pending: None,
+            pending_start: 0,
+            frames: 0,
+        })
+    }
+
+    /// Add one 60hz frame to the recording
+    pub fn push_frame(&mut self, vram: &[bool; 64 * 32]) -> Result<(), CaptureError> {
+        if self.pending.as_ref() != Some(vram) {
+            self.flush()?;
+            self.pending = Some(*vram);
+            self.pending_start = self.frames;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// number of 60hz frames recorded so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Write out the last frame and the gif trailer
+    pub fn finish(mut self) -> Result<(), CaptureError> {
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<(), CaptureError> {
+        if let Some(vram) = self.pending.take() {
+            let (width, height) = scaled_size(self.scale)?;
+            let mut frame = Frame::from_indexed_pixels(width as u16, height as u16, &indexed_pixels(&vram, self.scale), None);
+            let delay = centiseconds(self.frames) - centiseconds(self.pending_start);
+            frame.delay = delay.min(u16::MAX as u64) as u16;
+            self.encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for GifRecorder<W> {
+    fn drop(&mut self) {
+        // finish reports errors, dropping without it just makes a best effort
+        let _ = self.flush();
+    }
+}
+
+/// timestamp of a 60hz frame in hundredths of a second
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
@@ -0,0 +1,70 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use png::{BitDepth, ColorType};
+use rust8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+use crate::{CaptureError, Palette};
+
+/// Encode vram as an rgb png, each chip-8 pixel becomes a `scale` x `scale` block
+pub fn write_png<W: Write>(writer: W, vram: &[bool; 64 * 32], scale: u32, palette: &Palette) -> Result<(), CaptureError> {
+    let (width, height) = scaled_size(scale)?;
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(ColorType::RGB);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb_pixels(vram, scale, palette))?;
+    Ok(())
+}
+
+/// Write a png screenshot of vram to the file at path
+pub fn save_png<P: AsRef<Path>>(path: P, vram: &[bool; 64 * 32], scale: u32, palette: &Palette) -> Result<(), CaptureError> {
+    let file = BufWriter::new(File::create(path)?);
+    write_png(file, vram, scale, palette)
+}
+
+/// return the dimensions of the display at the given scale
+/// Largest scale accepted, 4096x2048 images, beyond which a frame is just memory spent on big pixels
+pub const MAX_SCALE: u32 = 64;
+
+pub(crate) fn scaled_size(scale: u32) -> Result<(u32, u32), CaptureError> {
+    if scale == 0 || scale > MAX_SCALE {
+        return Err(CaptureError::InvalidScale(scale));
+    }
+    Ok((DISPLAY_WIDTH as u32 * scale, DISPLAY_HEIGHT as u32 * scale))
+}
+
+pub(crate) fn indexed_pixels(vram: &[bool; 64 * 32], scale: u32) -> Vec<u8> {
+    let (width, height) = (DISPLAY_WIDTH as usize * scale as usize, DISPLAY_HEIGHT as usize * scale as usize);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let index = (y / scale as usize) * DISPLAY_WIDTH as usize + (x / scale as usize);
+            pixels.push(vram[index] as u8);
+        }
+    }
+    pixels
+}
+
+fn rgb_pixels(vram: &[bool; 64 * 32], scale: u32, palette: &Palette) -> Vec<u8> {
+    indexed_pixels(vram, scale)
+        .into_iter()
+        .flat_map(|pixel| palette.color(pixel != 0).to_vec())
+        .collect()
+}
@@ -0,0 +1,83 @@
+//! Screenshots and recordings decoded again to check what was written
+
+use rust8_capture::{write_png, CaptureError, GifRecorder, Palette, MAX_SCALE};
+
+const OFF: [u8; 3] = [0x10, 0x20, 0x30];
+const ON: [u8; 3] = [0xF0, 0xE0, 0xD0];
+
+/// the top left and bottom right pixels set
+fn corners() -> [bool; 64 * 32] {
+    let mut vram = [false; 64 * 32];
+    vram[0] = true;
+    vram[64 * 32 - 1] = true;
+    vram
+}
+
+#[test]
+fn palette_colors() {
+    let palette = Palette::new(OFF, ON);
+    assert_eq!(palette.color(false), OFF);
+    assert_eq!(palette.color(true), ON);
+    assert_eq!(palette.to_u32(), (0x10_20_30, 0xF0_E0_D0));
+    assert_eq!(Palette::default().to_u32(), (0x00_00_00, 0xFF_FF_FF));
+}
+
+#[test]
+fn png_is_the_scaled_display() {
+    let mut file = Vec::new();
+    write_png(&mut file, &corners(), 2, &Palette::new(OFF, ON)).unwrap();
+
+    let (info, mut reader) = png::Decoder::new(&file[..]).read_info().unwrap();
+    assert_eq!((info.width, info.height), (128, 64));
+    assert_eq!(info.color_type, png::ColorType::RGB);
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+    let pixel = |x: usize, y: usize| &pixels[(y * 128 + x) * 3..][..3];
+    for &(x, y) in [(0, 0), (1, 1), (126, 62), (127, 63)].iter() {
+        assert_eq!(pixel(x, y), ON, "{} {}", x, y);
+    }
+    for &(x, y) in [(2, 0), (0, 2), (64, 32), (125, 63)].iter() {
+        assert_eq!(pixel(x, y), OFF, "{} {}", x, y);
+    }
+}
+
+#[test]
+fn invalid_scales_are_rejected() {
+    // 1023 still fits a gif's u16 width but would need gigabytes of pixels
+    for &scale in [0, MAX_SCALE + 1, 1023, u32::MAX].iter() {
+        match write_png(Vec::new(), &corners(), scale, &Palette::default()) {
+            Err(CaptureError::InvalidScale(invalid)) => assert_eq!(invalid, scale),
+            other => panic!("scale {} gave {:?}", scale, other),
+        }
+        assert!(GifRecorder::new(Vec::new(), scale, &Palette::default()).is_err());
+    }
+    assert!(GifRecorder::new(Vec::new(), MAX_SCALE, &Palette::default()).is_ok());
+}
+
+#[test]
+fn gif_merges_repeated_frames() {
+    let mut file = Vec::new();
+    let mut gif = GifRecorder::new(&mut file, 1, &Palette::new(OFF, ON)).unwrap();
+    for _ in 0..3 {
+        gif.push_frame(&[false; 64 * 32]).unwrap();
+    }
+    for _ in 0..2 {
+        gif.push_frame(&corners()).unwrap();
+    }
+    assert_eq!(gif.frames(), 5);
+    gif.finish().unwrap();
+
+    let mut reader = gif::Decoder::new(&file[..]).read_info().unwrap();
+    assert_eq!((reader.width(), reader.height()), (64, 32));
+    assert_eq!(&reader.global_palette().unwrap()[..6], &[OFF, ON].concat()[..]);
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.read_next_frame().unwrap() {
+        frames.push((frame.delay, frame.buffer.to_vec()));
+    }
+    // delays in hundredths of a second, 3 frames is 5 and 5 frames is 8 rounded
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].0, 5);
+    assert!(frames[0].1.iter().all(|&pixel| pixel == 0));
+    assert_eq!(frames[1].0, 3);
+    assert_eq!((frames[1].1[0], frames[1].1[1], frames[1].1[64 * 32 - 1]), (1, 0, 1));
+}
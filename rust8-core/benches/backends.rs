@@ -0,0 +1,47 @@
+//! Every backend on the bundled roms, run with `cargo bench -p rust8-core`
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::emu::Emulator;
+use rust8_core::rng::Rng;
+
+/// a tight loop of register arithmetic, where decoding is most of the work
+const ALU_LOOP: [u8; 10] = [
+    0x70, 0x01, // ADD V0, 1
+    0x81, 0x04, // ADD V1, V0
+    0x82, 0x13, // XOR V2, V1
+    0x83, 0x20, // LD V3, V2
+    0x12, 0x00, // JP 0x200
+];
+
+const ROMS: [(&str, &[u8]); 2] = [("maze", include_bytes!("../../roms/maze.ch8")), ("alu loop", &ALU_LOOP)];
+const FRAMES: u32 = 60;
+const TICKRATE: u32 = 1000;
+
+fn emulator(rom: &[u8]) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::from_seed(1);
+    emu.tickrate = TICKRATE;
+    emu.load_rom(rom).unwrap();
+    emu
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.throughput(Throughput::Elements(u64::from(FRAMES * TICKRATE)));
+    for &(name, rom) in ROMS.iter() {
+        for &kind in BackendKind::ALL.iter() {
+            let _ = group.bench_with_input(BenchmarkId::new(kind.name(), name), rom, |b, rom| {
+                b.iter_batched_ref(|| (emulator(rom), Backend::new(kind)), |(emu, backend)| {
+                    for _ in 0..FRAMES {
+                        backend.run_frame(emu);
+                    }
+                }, BatchSize::SmallInput)
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+//! Any rom runs on the interpreter without panicking
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust8_core::emu::Emulator;
+use rust8_core::rng::Rng;
+
+/// frames each rom runs for
+const FRAMES: usize = 60;
+
+fuzz_target!(|rom: &[u8]| {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::from_seed(1);
+    if emu.load_rom(rom).is_err() {
+        return;
+    }
+    for _ in 0..FRAMES {
+        emu.run_frame();
+    }
+});
@@ -0,0 +1,34 @@
+//! Any rom and key sequence runs under every quirk preset and backend without panicking.
+//!
+//! Inputs are a quirk preset, backend, tickrate and frame count byte each, then a little endian
+//! keypad mask for each frame and then the rom.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+    let (header, rest) = data.split_at(4);
+    let keys = rest.len().min(header[3] as usize * 2);
+    let (keys, rom) = rest.split_at(keys);
+
+    let mut emu = Emulator::new();
+    emu.rng = Rng::from_seed(1);
+    emu.quirks = Quirks::PRESETS[header[0] as usize % Quirks::PRESETS.len()].1;
+    emu.tickrate = u32::from(header[2]);
+    if emu.load_rom(rom).is_err() {
+        return;
+    }
+    let mut backend = Backend::new(BackendKind::ALL[header[1] as usize % BackendKind::ALL.len()]);
+    for mask in keys.chunks_exact(2) {
+        emu.keyboard.set_keypad(u16::from_le_bytes([mask[0], mask[1]]));
+        backend.run_frame(&mut emu);
+    }
+});
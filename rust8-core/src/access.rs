@@ -0,0 +1,106 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A per address record of how a running rom uses memory.
+//!
+//! With std, give an emulator a map with `emu.access = Some(Box::default())` and every instruction fetched,
+//! sprite byte drawn and byte moved by Fx33, Fx55 or Fx65 is counted against its address, whichever
+//! backend runs it. Telling code from data this way is exact for the paths the rom actually took.
+//! The map also keeps the address and height of every sprite drawn, which sprite counts alone can't
+//! separate when sprites are packed together.
+
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+/// Ways an instruction touches memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// fetched as one of the two bytes of an instruction
+    Execute,
+    /// read by DRW as sprite data
+    Sprite,
+    /// read by Fx65
+    Read,
+    /// written by Fx33 or Fx55
+    Write,
+}
+
+impl Access {
+    pub const ALL: [Access; 4] = [Access::Execute, Access::Sprite, Access::Read, Access::Write];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::Execute => "executed",
+            Access::Sprite => "sprite",
+            Access::Read => "read",
+            Access::Write => "written",
+        }
+    }
+}
+
+/// How many times each address was touched in each way, counts saturate
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct AccessMap {
+    counts: [[u32; 4096]; 4],
+    sprites: BTreeSet<(u16, u8)>,
+}
+
+#[cfg(feature = "std")]
+impl AccessMap {
+    pub fn new() -> Self {
+        AccessMap { counts: [[0; 4096]; 4], sprites: BTreeSet::new() }
+    }
+
+    pub fn count(&self, access: Access, address: u16) -> u32 {
+        self.counts[access as usize][address as usize & 0xFFF]
+    }
+
+    /// every address's count for one kind of access
+    pub fn counts(&self, access: Access) -> &[u32; 4096] {
+        &self.counts[access as usize]
+    }
+
+    /// whether anything at all touched address
+    pub fn touched(&self, address: u16) -> bool {
+        Access::ALL.iter().any(|&access| self.count(access, address) > 0)
+    }
+
+    /// the address and height of every sprite drawn
+    pub fn sprites(&self) -> &BTreeSet<(u16, u8)> {
+        &self.sprites
+    }
+
+    pub fn clear(&mut self) {
+        self.counts = [[0; 4096]; 4];
+        self.sprites.clear();
+    }
+
+    pub(crate) fn record(&mut self, access: Access, address: usize) {
+        let count = &mut self.counts[access as usize][address & 0xFFF];
+        *count = count.saturating_add(1);
+    }
+
+    pub(crate) fn record_sprite(&mut self, address: u16, height: u8) {
+        let _ = self.sprites.insert((address & 0xFFF, height));
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for AccessMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The 8xyn arithmetic and logic instructions as pure functions of Vx and Vy.
+//!
+//! Every operation wraps. The value VF should take is returned separately from the result so it can
+//! be written after Vx, which makes the flag win when x is F.
+
+/// What an 8xyn instruction leaves in Vx, and in VF if it sets it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output {
+    pub value: u8,
+    pub flag: Option<u8>,
+}
+
+impl Output {
+    fn value(value: u8) -> Self {
+        Output { value, flag: None }
+    }
+
+    fn flagged(value: u8, flag: bool) -> Self {
+        Output { value, flag: Some(flag as u8) }
+    }
+
+    /// with the logic quirk the bitwise instructions clear VF
+    pub fn reset_flag(self, reset: bool) -> Self {
+        if reset { Output { flag: Some(0), ..self } } else { self }
+    }
+}
+
+/// 8xy1
+pub fn or(vx: u8, vy: u8) -> Output {
+    Output::value(vx | vy)
+}
+
+/// 8xy2
+pub fn and(vx: u8, vy: u8) -> Output {
+    Output::value(vx & vy)
+}
+
+/// 8xy3
+pub fn xor(vx: u8, vy: u8) -> Output {
+    Output::value(vx ^ vy)
+}
+
+/// 8xy4, VF is the carry
+pub fn add(vx: u8, vy: u8) -> Output {
+    let (value, carry) = vx.overflowing_add(vy);
+    Output::flagged(value, carry)
+}
+
+/// 8xy5, VF is 1 unless there's a borrow
+pub fn sub(vx: u8, vy: u8) -> Output {
+    let (value, borrow) = vx.overflowing_sub(vy);
+    Output::flagged(value, !borrow)
+}
+
+/// 8xy7, Vy - Vx with VF 1 unless there's a borrow
+pub fn subn(vx: u8, vy: u8) -> Output {
+    sub(vy, vx)
+}
+
+/// 8xy6, VF is the bit shifted out
+pub fn shr(value: u8) -> Output {
+    Output::flagged(value >> 1, value & 0x1 != 0)
+}
+
+/// 8xyE, VF is the bit shifted out
+pub fn shl(value: u8) -> Output {
+    Output::flagged(value << 1, value & 0x80 != 0)
+}
@@ -0,0 +1,152 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Picking how instructions are executed at runtime.
+
+use std::boxed::Box;
+use std::fmt;
+use std::str::FromStr;
+use std::string::{String, ToString};
+
+use crate::decode::DecodeCache;
+use crate::emu::Emulator;
+use crate::jit::Jit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// fetch and decode every instruction
+    Interpreter,
+    /// predecode memory, see DecodeCache
+    Cached,
+    /// compile basic blocks, see Jit
+    Jit,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 3] = [BackendKind::Interpreter, BackendKind::Cached, BackendKind::Jit];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::Interpreter => "interpreter",
+            BackendKind::Cached => "cached",
+            BackendKind::Jit => "jit",
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A backend name that isn't interpreter, cached or jit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownBackend(pub String);
+
+impl fmt::Display for UnknownBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown backend {:?}, expected interpreter, cached or jit", self.0)
+    }
+}
+
+impl std::error::Error for UnknownBackend {}
+
+impl FromStr for BackendKind {
+    type Err = UnknownBackend;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BackendKind::ALL.iter()
+            .copied()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| UnknownBackend(name.to_string()))
+    }
+}
+
+/// The state a backend keeps alongside an emulator, use one per emulator
+pub enum Backend {
+    Interpreter,
+    Cached(Box<DecodeCache>),
+    Jit(Box<Jit>),
+}
+
+impl Backend {
+    pub fn new(kind: BackendKind) -> Self {
+        match kind {
+            BackendKind::Interpreter => Backend::Interpreter,
+            BackendKind::Cached => Backend::Cached(Box::default()),
+            BackendKind::Jit => Backend::Jit(Box::default()),
+        }
+    }
+
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            Backend::Interpreter => BackendKind::Interpreter,
+            Backend::Cached(_) => BackendKind::Cached,
+            Backend::Jit(_) => BackendKind::Jit,
+        }
+    }
+
+    /// Run one 60hz frame of emu
+    pub fn run_frame(&mut self, emu: &mut Emulator) {
+        match self {
+            Backend::Interpreter => emu.run_frame(),
+            Backend::Cached(cache) => emu.run_frame_cached(cache),
+            Backend::Jit(jit) => emu.run_frame_jit(jit),
+        }
+    }
+
+    /// Run that many 60hz frames of emu back to back
+    pub fn run_frames(&mut self, emu: &mut Emulator, frames: u32) {
+        for _ in 0..frames {
+            self.run_frame(emu);
+        }
+    }
+
+    /// Execute a single instruction of emu, leaving the timers and input alone
+    pub fn tick(&mut self, emu: &mut Emulator) {
+        match self {
+            Backend::Interpreter => emu.tick(),
+            Backend::Cached(cache) => cache.tick(emu),
+            Backend::Jit(jit) => jit.run(emu, 1),
+        }
+    }
+
+    /// Catch up on memory the last instruction wrote, needed before swapping emu's memory out between frames
+    pub fn sync(&mut self, emu: &mut Emulator) {
+        match self {
+            Backend::Interpreter => {
+                let _ = emu.code_writes.take();
+            }
+            Backend::Cached(cache) => cache.sync(emu),
+            Backend::Jit(jit) => jit.sync(emu),
+        }
+    }
+
+    /// Forget anything derived from memory, needed after writing to the emulator's memory directly
+    pub fn invalidate(&mut self) {
+        match self {
+            Backend::Interpreter => {}
+            Backend::Cached(cache) => cache.invalidate(),
+            Backend::Jit(jit) => jit.invalidate(),
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::new(BackendKind::Interpreter)
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Pacing emulated frames against the host's clock.
+//!
+//! Roms expect 60 frames a second. A frontend tells a `FrameClock` how much time passed since it
+//! last asked, and runs as many frames as that's worth at the clock's speed with `Backend::run_frames`
+//! or `Emulator::run_frame`. Time left over from a partial frame carries over to the next call, so
+//! speeds below 1x run a frame every few calls and a host that can't refresh at exactly 60hz still
+//! averages the right rate.
+
+use core::convert::TryFrom;
+use core::fmt;
+use core::time::Duration;
+
+/// nanoseconds in a 60hz frame
+const FRAME_NANOS: u64 = 1_000_000_000 / 60;
+/// frames of lag a clock will catch up on at 1x, anything older is dropped
+const MAX_BEHIND: u64 = 4;
+
+/// How fast emulation runs compared to 60 frames a second, in steps of 0.25x from 0.25x to 8x
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Speed {
+    quarters: u32,
+}
+
+impl Speed {
+    pub const SLOWEST: Speed = Speed { quarters: 1 };
+    pub const NORMAL: Speed = Speed { quarters: 4 };
+    pub const FASTEST: Speed = Speed { quarters: 32 };
+
+    /// a speed of quarters/4 times normal, None outside 0.25x to 8x
+    pub fn from_quarters(quarters: u32) -> Option<Speed> {
+        if (Speed::SLOWEST.quarters..=Speed::FASTEST.quarters).contains(&quarters) {
+            Some(Speed { quarters })
+        } else {
+            None
+        }
+    }
+
+    pub fn quarters(self) -> u32 {
+        self.quarters
+    }
+
+    /// twice as fast, up to 8x
+    pub fn faster(self) -> Speed {
+        Speed { quarters: (self.quarters * 2).min(Speed::FASTEST.quarters) }
+    }
+
+    /// half as fast, down to 0.25x
+    pub fn slower(self) -> Speed {
+        Speed { quarters: (self.quarters / 2).max(Speed::SLOWEST.quarters) }
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::NORMAL
+    }
+}
+
+/// Written as a multiplier like `0.25x` or `2x`
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.quarters % 4 {
+            0 => write!(f, "{}x", self.quarters / 4),
+            2 => write!(f, "{}.5x", self.quarters / 4),
+            quarter => write!(f, "{}.{}x", self.quarters / 4, quarter * 25),
+        }
+    }
+}
+
+/// Counts the frames owed for time passing on the host
+#[derive(Debug, Clone, Default)]
+pub struct FrameClock {
+    pub speed: Speed,
+    /// emulated time owed but not yet a whole frame, in quarters of a nanosecond
+    owed: u64,
+}
+
+impl FrameClock {
+    pub fn new(speed: Speed) -> Self {
+        FrameClock { speed, owed: 0 }
+    }
+
+    /// Frames to run for elapsed time passing. After a long stall, like the window being dragged,
+    /// only the last few frames' worth are caught up on instead of running everything at once.
+    pub fn frames(&mut self, elapsed: Duration) -> u32 {
+        let elapsed = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let quarters = u64::from(self.speed.quarters);
+        let frame = 4 * FRAME_NANOS;
+        // at least one whole frame even at 0.25x
+        let max = MAX_BEHIND * FRAME_NANOS * quarters;
+        self.owed = self.owed.saturating_add(elapsed.saturating_mul(quarters)).min(max);
+        let frames = self.owed / frame;
+        self.owed %= frame;
+        frames as u32
+    }
+
+    /// forget any partial frame, for after a pause
+    pub fn reset(&mut self) {
+        self.owed = 0;
+    }
+}
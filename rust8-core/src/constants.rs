@@ -37,3 +37,6 @@ pub const FONTSET_START: u16 = 0x50;
 pub const DISPLAY_WIDTH: u8 = 64;
 
 pub const DISPLAY_HEIGHT: u8 = 32;
+
+/// instructions per 60hz frame when nothing better is known about a rom
+pub const DEFAULT_TICKRATE: u32 = 15;
@@ -13,9 +13,14 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use core::fmt;
+
+use crate::access::Access;
+use crate::alu;
 use crate::emu::*;
 use crate::constants::{FONTSET_START, DISPLAY_HEIGHT, DISPLAY_WIDTH};
 
+#[allow(clippy::upper_case_acronyms)]
 pub trait CPU {
     fn cls(&mut self);
     fn ret(&mut self);
@@ -58,14 +63,14 @@ impl CPU for Emulator {
     fn cls(&mut self) {
         self.display.vram = [false; 64 * 32];
         self.display.update_display();
-        self.pc += 2;
+        self.advance();
     }
 
 
-    /// 0x00EE - Return from a function
+    /// 0x00EE - Return from a function to the instruction after its call
     fn ret(&mut self) {
-        self.sp -= 1;
-        self.pc = self.stack[self.sp as usize];
+        self.sp = self.sp.wrapping_sub(1) & 0xF;
+        self.pc = self.stack[self.sp as usize] & 0xFFF;
     }
 
     /// 0x1nnn - Jump to location nnn
@@ -74,205 +79,221 @@ impl CPU for Emulator {
         self.pc = nnn;
     }
 
-    /// 0x2nnn - Call function at nnn
+    /// 0x2nnn - Call function at nnn, pushing the address of the next instruction
+    /// The stack wraps around, a 17th nested call overwrites the first
     fn call_addr(&mut self) {
-        self.stack[self.sp as usize] = self.pc;
-        self.sp += 1;
+        self.stack[self.sp as usize & 0xF] = (self.pc + 2) & 0xFFF;
+        self.sp = (self.sp + 1) & 0xF;
         self.pc = self.get_nnn();
     }
 
     /// 0x3xkk - Skip next operation if register x is equal to kk
     fn se_vx_byte(&mut self) {
         if self.registers[self.get_x() as usize] == self.get_nn() {
-            self.pc += 2;
+            self.advance();
         }
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0x4xkk - Skip next operation if register x is not equal to kk
     fn sne_vx_byte(&mut self) {
         if self.registers[self.get_x() as usize] != self.get_nn() {
-            self.pc += 2;
+            self.advance();
         }
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0x5xy0 - Skip next operation if register x is equal to register y
     fn se_vx_vy(&mut self) {
         if self.registers[self.get_x() as usize] == self.registers[self.get_y() as usize] {
-            self.pc += 2;
+            self.advance();
         }
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0x6xkk - Load byte kk into register x
     fn ld_vx_byte(&mut self) {
         self.registers[self.get_x() as usize] = self.get_nn();
-        self.pc += 2;
+        self.advance();
     }
 
 
-    /// 0x7xkk - Add byte kk to register x
+    /// 0x7xkk - Add byte kk to register x, wrapping without touching register F
     fn add_vx_byte(&mut self) {
-        self.registers[self.get_x() as usize] += self.get_nn();
-        self.pc += 2;
+        self.registers[self.get_x() as usize] = self.vx().wrapping_add(self.get_nn());
+        self.advance();
     }
 
     /// 0x8xy0 - Load register y into register x
     fn ld_vx_vy(&mut self) {
         self.registers[self.get_x() as usize] = self.registers[self.get_y() as usize];
-        self.pc += 2;
+        self.advance();
     }
 
 
     /// 0x8xy1 - Set register x to bitwise or with register y
+    /// With the logic quirk register F is cleared
     fn or_vx_vy(&mut self) {
-        self.registers[self.get_x() as usize] |= self.registers[self.get_y() as usize];
-        self.pc += 2;
+        let output = alu::or(self.vx(), self.vy()).reset_flag(self.quirks.logic);
+        self.write_alu(output);
     }
 
     /// 0x8xy2 - Set register x to bitwise and with register y
+    /// With the logic quirk register F is cleared
     fn and_vx_vy(&mut self) {
-        self.registers[self.get_x() as usize] &= self.registers[self.get_y() as usize];
-        self.pc += 2;
+        let output = alu::and(self.vx(), self.vy()).reset_flag(self.quirks.logic);
+        self.write_alu(output);
     }
 
     /// 0x8xy3 - Set register x to bitwise xor with register y
+    /// With the logic quirk register F is cleared
     fn xor_vx_vy(&mut self) {
-        self.registers[self.get_x() as usize] ^= self.registers[self.get_y() as usize];
-        self.pc += 2;
+        let output = alu::xor(self.vx(), self.vy()).reset_flag(self.quirks.logic);
+        self.write_alu(output);
     }
 
     /// 0x8xy4 - Add register y to register x, set register F to 1 if carry
     fn add_vx_vy(&mut self) {
-        let sum: u16 = self.registers[self.get_x() as usize] as u16 + self.registers[self.get_y() as usize] as u16;
-        self.registers[0xf] = if sum > 255 { 1 } else { 0 };
-        self.registers[self.get_x() as usize] = (sum & 0xff) as u8;
-        self.pc += 2;
+        self.write_alu(alu::add(self.vx(), self.vy()));
     }
 
-    /// 0x8xy5 - Subtract register y from register x, set register F to 1 if register x > register y
+    /// 0x8xy5 - Subtract register y from register x, set register F to 1 if there's no borrow
     fn sub_vx_vy(&mut self) {
-        self.registers[0xf] = if self.registers[self.get_x() as usize] > self.registers[self.get_y() as usize] { 1 } else { 0 };
-        self.registers[self.get_x() as usize] -= self.registers[self.get_y() as usize];
-        self.pc += 2;
+        self.write_alu(alu::sub(self.vx(), self.vy()));
     }
 
-    /// 0x8xy6 - Set register f to lsb and shift register x right 1
+    /// 0x8xy6 - Set register f to lsb and shift register y right 1 into register x
+    /// With the shift quirk register x is shifted in place
     fn shr_vx_vy(&mut self) {
-        self.registers[0xf] = self.registers[self.get_x() as usize] & 0x1;
-        self.registers[self.get_x() as usize] >>= 1;
-        self.pc += 2;
+        self.write_alu(alu::shr(self.shift_source()));
     }
 
-    /// 0x8xy7 - subtract Vx from Vy and store in Vx, if Vy > Vx then Vf = 1
+    /// 0x8xy7 - subtract Vx from Vy and store in Vx, if there's no borrow then Vf = 1
     fn subn_vx_vy(&mut self) {
-        self.registers[0xf] = if self.registers[self.get_y() as usize] > self.registers[self.get_x() as usize] { 1 } else { 0 };
-        self.registers[self.get_x() as usize] = self.registers[self.get_y() as usize] - self.registers[self.get_x() as usize];
-        self.pc += 2;
+        self.write_alu(alu::subn(self.vx(), self.vy()));
     }
 
-    /// 0x8xy8 - Set register f to msb and shift register x left 1
+    /// 0x8xyE - Set register f to msb and shift register y left 1 into register x
+    /// With the shift quirk register x is shifted in place
     fn shl_vx_vy(&mut self) {
-        self.registers[0xf] = (self.registers[self.get_x() as usize] & 0x80) >> 7;
-        self.registers[self.get_x() as usize] <<= 1;
-        self.pc += 2;
+        self.write_alu(alu::shl(self.shift_source()));
     }
 
 
     /// 0x9xy0 - Skip next operation if Vx != Vy
     fn sne_vx_vy(&mut self) {
         if self.registers[self.get_x() as usize] != self.registers[self.get_y() as usize] {
-            self.pc += 2;
+            self.advance();
         }
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0xAnnn - Index is set to nnn
     fn ld_i_addr(&mut self) {
         self.index = self.get_nnn();
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0xBnnn - PC is set to V0 + nnn
+    /// With the jump quirk this is Bxnn and PC is set to Vx + xnn
     fn jp_v0_addr(&mut self) {
-        self.pc = self.registers[0x0] as u16 + self.get_nnn();
+        let register = if self.quirks.jump { self.get_x() } else { 0x0 };
+        self.pc = (self.registers[register as usize] as u16 + self.get_nnn()) & 0xFFF;
     }
 
 
     /// 0xCxkk - Generate random byte, AND with kk, and then store in Vx
     fn rnd_vx_byte(&mut self) {
-        self.registers[self.get_x() as usize] = rand::random::<u8>() & self.get_nn();
-        self.pc += 2;
+        let random = match self.host.random {
+            Some(random) => random(),
+            None => self.rng.next_u8(),
+        };
+        self.registers[self.get_x() as usize] = random & self.get_nn();
+        self.advance();
     }
 
     /// 0XDxyn - Display n length sprite at memory location I at (Vx, Vy)
     /// VF is set if there is a collision
+    /// Sprites are clipped at the edges of the screen unless the wrap quirk is set
     fn drw_vx_vy_nibble(&mut self) {
         self.registers[0xf] = 0;
+        let origin_x = self.registers[self.get_x() as usize] % DISPLAY_WIDTH;
+        let origin_y = self.registers[self.get_y() as usize] % DISPLAY_HEIGHT;
+        self.record_sprite(self.index, self.get_n());
         for byte in 0..self.get_n() {
-            let y = (self.registers[self.get_y() as usize] + byte) % DISPLAY_HEIGHT;
+            if !self.quirks.wrap && origin_y + byte >= DISPLAY_HEIGHT {
+                break;
+            }
+            let y = (origin_y + byte) % DISPLAY_HEIGHT;
+            let address = self.index_address(byte as usize);
+            self.record(Access::Sprite, address);
             for bit in 0..8u8 {
-                let x = (self.registers[self.get_x() as usize] + bit) % DISPLAY_WIDTH;
-                let value = (self.memory[self.index as usize + byte as usize] >> (7 - bit)) & 1;
+                if !self.quirks.wrap && origin_x + bit >= DISPLAY_WIDTH {
+                    break;
+                }
+                let x = (origin_x + bit) % DISPLAY_WIDTH;
+                let value = (self.memory[address] >> (7 - bit)) & 1;
                 self.registers[0xf] |= value & if self.display.vram[y as usize * DISPLAY_WIDTH as usize + x as usize] { 1 } else { 0 };
                 self.display.vram[y as usize * DISPLAY_WIDTH as usize + x as usize] ^= value != 0;
             }
         }
         self.display.update_display();
-        self.pc += 2;
+        self.advance();
     }
 
-    /// 0xEx9E - skip if key is pressed
+    /// 0xEx9E - skip if the key in Vx is pressed
     fn skp_vx(&mut self) {
-        if self.keyboard.keypad[self.get_x() as usize] {
-            self.pc += 2
+        if self.keyboard.keypad[(self.vx() & 0xF) as usize] {
+            self.advance()
         }
-        self.pc += 2;
+        self.advance();
     }
 
-    /// 0xExA1 - skip if key is not pressed
+    /// 0xExA1 - skip if the key in Vx is not pressed
     fn sknp_vx(&mut self) {
-        if !self.keyboard.keypad[self.get_x() as usize] {
-            self.pc += 2
+        if !self.keyboard.keypad[(self.vx() & 0xF) as usize] {
+            self.advance()
         }
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0xFx07 - load delay into register vx
     fn ld_vx_dt(&mut self) {
         self.registers[self.get_x() as usize] = self.delay;
-        self.pc += 2;
+        self.advance();
     }
 
     /// 0xFx0A - wait for keypress and store value in Vx
+    /// Waiting leaves PC here so the instruction runs again until a key is pressed
     fn ld_vx_k(&mut self) {
-        self.registers[self.get_x() as usize] = self.keyboard.wait_for_keypress();
-        self.pc += 2;
+        if let Some(key) = self.keyboard.take_keypress() {
+            self.registers[self.get_x() as usize] = key;
+            self.advance();
+        }
     }
 
     /// 0xFx15 - set delay timer to value in Vx
     fn ld_dt_vx(&mut self) {
-        self.delay = self.registers[self.get_nn() as usize];
-        self.pc += 2;
+        self.delay = self.registers[self.get_x() as usize];
+        self.advance();
     }
 
     /// 0xFx18 - set sound timer to value in Vx
     fn ld_st_vx(&mut self) {
-        self.sound = self.registers[self.get_nn() as usize];
-        self.pc += 2;
+        self.sound = self.registers[self.get_x() as usize];
+        self.advance();
     }
     /// 0xFx1E - add Vx to I
     fn add_i_vx(&mut self) {
-        self.index += self.registers[self.get_x() as usize] as u16;
-        self.pc += 2;
+        self.index = self.index.wrapping_add(self.registers[self.get_x() as usize] as u16);
+        self.advance();
     }
 
     /// 0xFx29 - set index to location of (hex) digit sprite
-    /// Sprites are stored at 0x50 and are 5 bytes long
+    /// Sprites are stored at 0x50 and are 5 bytes long, only the low nibble of Vx counts
     fn ld_f_vx(&mut self) {
-        self.index = FONTSET_START + self.registers[self.get_x() as usize] as u16 * 5;
-        self.pc += 2;
+        self.index = FONTSET_START + (self.vx() & 0xF) as u16 * 5;
+        self.advance();
     }
 
 
@@ -280,94 +301,160 @@ impl CPU for Emulator {
     /// I stores digit in hundreds place, I + 1 holds the tens place, and I + 2 holds the ones place
     fn ld_b_vx(&mut self) {
         let val: u8 = self.registers[self.get_x() as usize];
-        self.memory[self.index as usize] = val / 100;
-        self.memory[self.index as usize + 1] = (val % 100) / 10;
-        self.memory[self.index as usize + 2] = val % 10;
-        self.pc += 2;
+        self.store(0, val / 100);
+        self.store(1, (val % 100) / 10);
+        self.store(2, val % 10);
+        self.advance();
     }
 
     /// 0xFx55 - Stores registers 0 to x in memory beginning at I
     fn ld_mem_vx(&mut self) {
         for i in 0..self.get_x() as usize + 1 {
-            self.memory[self.index as usize + i] = self.registers[i];
+            self.store(i, self.registers[i]);
         }
-        self.index += self.get_x() as u16 + 1;
-        self.pc += 2;
+        self.increment_index_after_transfer();
+        self.advance();
     }
 
     /// 0xFx65 - reads memory into registers 0 to x beginning at I
     fn ld_vx_mem(&mut self) {
         for i in 0..self.get_x() as usize + 1 {
-            self.registers[i] = self.memory[self.index as usize + i];
+            let address = self.index_address(i);
+            self.record(Access::Read, address);
+            self.registers[i] = self.memory[address];
         }
-        self.index += self.get_x() as u16 + 1;
-        self.pc += 2;
+        self.increment_index_after_transfer();
+        self.advance();
     }
 }
 
-pub fn mnemonic(instruction: u16) -> String {
-    let x = ((instruction & 0x0F00) >> 8) as u8;
-    let y = ((instruction & 0x00F0) >> 4) as u8;
+impl Emulator {
+    fn vx(&self) -> u8 {
+        self.registers[self.get_x() as usize]
+    }
 
-    let n: u8 = (instruction & 0x000f) as u8;
-    let nn: u8 = (instruction & 0x00ff) as u8;
-    let nnn = instruction & 0x0FFF;
+    fn vy(&self) -> u8 {
+        self.registers[self.get_y() as usize]
+    }
 
-    return match instruction >> 12 {
-        0x0 => {
-            match nn {
-                0xE0 => { format!("CLS") }
-                0xEE => { format!("RET") }
-                _ => { format!("INVALID") }
-            }
+    /// the register 8xy6 and 8xyE shift, depending on the shift quirk
+    fn shift_source(&self) -> u8 {
+        if self.quirks.shift { self.vx() } else { self.vy() }
+    }
+
+    /// store an 8xyn result, Vx first so that VF ends up holding the flag when x is F
+    fn write_alu(&mut self, output: alu::Output) {
+        self.registers[self.get_x() as usize] = output.value;
+        if let Some(flag) = output.flag {
+            self.registers[0xF] = flag;
         }
-        0x1 => { format!("JP {:#x}", nnn) }
-        0x2 => { format!("CALL {:#x}", nnn) }
-        0x3 => { format!("SE {:#x}, {:#x}", x, nn) }
-        0x4 => { format!("SNE {:#x}, {:#x}", x, nn) }
-        0x5 => { format!("SE Vx, Vy") }
-        0x6 => { format!("LD {:#x}, {:#x}", x, nn) }
-        0x7 => { format!("ADD {:#x}, {:#x}", x, nn) }
-        0x8 => {
-            match n {
-                0x0 => { format!("LD {:#x}, {:#x}", x, y) }
-                0x1 => { format!("OR {:#x}, {:#x}", x, y) }
-                0x2 => { format!("AND {:#x}, {:#x}", x, y) }
-                0x3 => { format!("XOR {:#x}, {:#x}", x, y) }
-                0x4 => { format!("ADD {:#x}, {:#x}", x, y) }
-                0x5 => { format!("SUB {:#x}, {:#x}", x, y) }
-                0x6 => { format!("SHR {:#x}, {:#x}", x, y) }
-                0x7 => { format!("SUBN {:#x}, {:#x}", x, y) }
-                0x8 => { format!("SHL {:#x}, {:#x}", x, y) }
-                _ => { format!("INVALID") }
-            }
+        self.advance();
+    }
+
+    /// move pc on to the next instruction, wrapping around the end of memory
+    pub(crate) fn advance(&mut self) {
+        self.pc = (self.pc + 2) & 0xFFF;
+    }
+
+    /// the address offset bytes past I, wrapping around the end of memory
+    fn index_address(&self, offset: usize) -> usize {
+        (self.index as usize + offset) & 0xFFF
+    }
+
+    /// write a byte offset bytes past I
+    fn store(&mut self, offset: usize, value: u8) {
+        let address = self.index_address(offset);
+        self.memory[address] = value;
+        self.code_writes.record(address, 1);
+        self.record(Access::Write, address);
+    }
+
+    /// advance I past the registers moved by Fx55 and Fx65, depending on the memory quirks
+    fn increment_index_after_transfer(&mut self) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
         }
-        0x9 => { format!("SNE {:#x}, {:#x}", x, y) }
-        0xA => { format!("LD I, {:#x}", nnn) }
-        0xB => { format!("JP V0, {:#x}", nnn) }
-        0xC => { format!("RND {:#x}, {:#x}", x, nn) }
-        0xD => { format!("DRW {:#x}, {:#x}, {:#x}", x, y, n) }
-        0xE => {
-            match nn {
-                0x9e => { format!("SKP {:#x}", x) }
-                0xA1 => { format!("SKNP {:#x}", x) }
-                _ => { format!("INVALID") }
-            }
+        self.index = self.index.wrapping_add(self.get_x() as u16);
+        if !self.quirks.memory_increment_by_x {
+            self.index = self.index.wrapping_add(1);
         }
-        0xF => {
-            match nn {
-                0x07 => { format!("LD {:#x}, DT", x) }
-                0x0A => { format!("LD {:#x}, K", x) }
-                0x15 => { format!("LD DT, {:#x}", x) }
-                0x18 => { format!("LD ST, {:#x}", x) }
-                0x1E => { format!("ADD I, {:#x}", x) }
-                0x29 => { format!("LD F, {:#x}", x) }
-                0x33 => { format!("LD B, {:#x}", x) }
-                0x55 => { format!("LD [I], {:#x}", x) }
-                0x65 => { format!("LD {:#x}, [I]", x) }
-                _ => { format!("INVALID") }
+    }
+}
+
+/// Assembly for an instruction, formats without allocating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mnemonic(pub u16);
+
+pub fn mnemonic(instruction: u16) -> Mnemonic {
+    Mnemonic(instruction)
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instruction = self.0;
+        let x = ((instruction & 0x0F00) >> 8) as u8;
+        let y = ((instruction & 0x00F0) >> 4) as u8;
+
+        let n: u8 = (instruction & 0x000f) as u8;
+        let nn: u8 = (instruction & 0x00ff) as u8;
+        let nnn = instruction & 0x0FFF;
+
+        match instruction >> 12 {
+            0x0 => {
+                match nn {
+                    0xE0 => { f.write_str("CLS") }
+                    0xEE => { f.write_str("RET") }
+                    _ => { f.write_str("INVALID") }
+                }
+            }
+            0x1 => { write!(f, "JP {:#x}", nnn) }
+            0x2 => { write!(f, "CALL {:#x}", nnn) }
+            0x3 => { write!(f, "SE {:#x}, {:#x}", x, nn) }
+            0x4 => { write!(f, "SNE {:#x}, {:#x}", x, nn) }
+            0x5 => { write!(f, "SE {:#x}, {:#x}", x, y) }
+            0x6 => { write!(f, "LD {:#x}, {:#x}", x, nn) }
+            0x7 => { write!(f, "ADD {:#x}, {:#x}", x, nn) }
+            0x8 => {
+                match n {
+                    0x0 => { write!(f, "LD {:#x}, {:#x}", x, y) }
+                    0x1 => { write!(f, "OR {:#x}, {:#x}", x, y) }
+                    0x2 => { write!(f, "AND {:#x}, {:#x}", x, y) }
+                    0x3 => { write!(f, "XOR {:#x}, {:#x}", x, y) }
+                    0x4 => { write!(f, "ADD {:#x}, {:#x}", x, y) }
+                    0x5 => { write!(f, "SUB {:#x}, {:#x}", x, y) }
+                    0x6 => { write!(f, "SHR {:#x}, {:#x}", x, y) }
+                    0x7 => { write!(f, "SUBN {:#x}, {:#x}", x, y) }
+                    0xE => { write!(f, "SHL {:#x}, {:#x}", x, y) }
+                    _ => { f.write_str("INVALID") }
+                }
             }
+            0x9 => { write!(f, "SNE {:#x}, {:#x}", x, y) }
+            0xA => { write!(f, "LD I, {:#x}", nnn) }
+            0xB => { write!(f, "JP V0, {:#x}", nnn) }
+            0xC => { write!(f, "RND {:#x}, {:#x}", x, nn) }
+            0xD => { write!(f, "DRW {:#x}, {:#x}, {:#x}", x, y, n) }
+            0xE => {
+                match nn {
+                    0x9e => { write!(f, "SKP {:#x}", x) }
+                    0xA1 => { write!(f, "SKNP {:#x}", x) }
+                    _ => { f.write_str("INVALID") }
+                }
+            }
+            0xF => {
+                match nn {
+                    0x07 => { write!(f, "LD {:#x}, DT", x) }
+                    0x0A => { write!(f, "LD {:#x}, K", x) }
+                    0x15 => { write!(f, "LD DT, {:#x}", x) }
+                    0x18 => { write!(f, "LD ST, {:#x}", x) }
+                    0x1E => { write!(f, "ADD I, {:#x}", x) }
+                    0x29 => { write!(f, "LD F, {:#x}", x) }
+                    0x33 => { write!(f, "LD B, {:#x}", x) }
+                    0x55 => { write!(f, "LD [I], {:#x}", x) }
+                    0x65 => { write!(f, "LD {:#x}, [I]", x) }
+                    _ => { f.write_str("INVALID") }
+                }
+            }
+            _ => { f.write_str("INVALID") }
         }
-        _ => { format!("INVALID") }
-    };
+    }
 }
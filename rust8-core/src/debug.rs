@@ -0,0 +1,464 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Running an emulator an instruction at a time for a debugger.
+//!
+//! A `Session` runs frames through a `Backend` the same as `Backend::run_frame`, but can stop between
+//! any two instructions of a frame and carry on from there later, so the timers still count down
+//! once per frame however the frame was stepped through. While it has breakpoints, which are
+//! `Expr`s, they're checked after every instruction and the session stops at the first that holds.
+//!
+//! A session that's recording can also run backwards. It keeps a save state every
+//! `SNAPSHOT_INTERVAL` instructions along with every change made to the keyboard between
+//! instructions, and goes back by loading the last snapshot before where it's going and replaying
+//! from there. Replaying only reproduces what happened when nothing else feeds the emulator, so
+//! recording needs Cxkk to use the emulator's own rng and keys to be set on `emu.keyboard`. Access
+//! maps and profiles count replayed instructions again.
+
+use std::boxed::Box;
+use std::collections::{BTreeMap, VecDeque};
+use std::error;
+use std::fmt;
+use std::mem;
+use std::vec;
+use std::vec::Vec;
+
+use crate::access::Access;
+use crate::backend::Backend;
+use crate::emu::Emulator;
+use crate::expr::{Expr, Register};
+use crate::state::STATE_SIZE;
+
+/// instructions between snapshots, the most replayed to go back one instruction
+const SNAPSHOT_INTERVAL: u64 = 1000;
+/// snapshots kept, about 4MB of states, older ones are dropped along with the history before them
+const MAX_SNAPSHOTS: usize = 1000;
+
+/// Something the host supplies that a recording couldn't replay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    /// Cxkk reads host.random instead of the emulator's rng
+    HostRandom,
+    /// the keypad is polled from host.input
+    HostInput,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::HostRandom => write!(f, "can't record with random numbers from the host, use the emulator's rng"),
+            RecordError::HostInput => write!(f, "can't record with input polled from the host, set keys on the emulator's keyboard"),
+        }
+    }
+}
+
+impl error::Error for RecordError {}
+
+/// whether running emu could be replayed, nothing the host supplies feeds it
+fn replayable(emu: &Emulator) -> Result<(), RecordError> {
+    if emu.host.random.is_some() {
+        return Err(RecordError::HostRandom);
+    }
+    if emu.host.input.is_some() {
+        return Err(RecordError::HostInput);
+    }
+    Ok(())
+}
+
+/// A register or byte of memory reverse_to_last_write looks for
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    Register(Register),
+    Memory(usize),
+}
+
+/// What telling whether an instruction wrote a place needs from before it ran
+#[derive(Debug, Clone, Copy)]
+struct Before {
+    pc: u16,
+    delay: u8,
+    sound: u8,
+    /// times the place was written, for memory
+    writes: u32,
+}
+
+impl Before {
+    fn of(emu: &Emulator, place: Place) -> Self {
+        let writes = match (place, &emu.access) {
+            (Place::Memory(address), Some(map)) => map.count(Access::Write, address as u16),
+            _ => 0,
+        };
+        Before { pc: emu.pc, delay: emu.delay, sound: emu.sound, writes }
+    }
+}
+
+impl Place {
+    fn of(expr: &Expr, emu: &Emulator) -> Option<Place> {
+        match expr {
+            Expr::Register(register) => Some(Place::Register(*register)),
+            Expr::Memory(address) => Some(Place::Memory(address.eval(emu)? as usize & 0xFFF)),
+            _ => None,
+        }
+    }
+
+    /// whether the instruction that just ran, emu.instruction, wrote this place
+    fn written(self, before: &Before, emu: &Emulator) -> bool {
+        let instruction = emu.instruction;
+        let x = (instruction >> 8 & 0xF) as u8;
+        let nn = instruction & 0xFF;
+        match self {
+            Place::Memory(_) => Before::of(emu, self).writes > before.writes,
+            Place::Register(Register::V(v)) => match instruction >> 12 {
+                0x6 | 0x7 | 0xC => v == x,
+                0x8 => match instruction & 0xF {
+                    0x0 => v == x,
+                    0x1..=0x3 => v == x || (v == 0xF && emu.quirks.logic),
+                    0x4..=0x7 | 0xE => v == x || v == 0xF,
+                    _ => false,
+                },
+                0xD => v == 0xF,
+                0xF => match nn {
+                    0x07 => v == x,
+                    // only once a key is pressed, until then it runs again
+                    0x0A => v == x && emu.pc != before.pc,
+                    0x65 => v <= x,
+                    _ => false,
+                },
+                _ => false,
+            },
+            Place::Register(Register::I) => match instruction >> 12 {
+                0xA => true,
+                0xF => matches!(nn, 0x1E | 0x29) || (matches!(nn, 0x55 | 0x65) && !emu.quirks.memory_leave_i_unchanged),
+                _ => false,
+            },
+            Place::Register(Register::Pc) => instruction & 0xF0FF != 0xF00A || emu.pc != before.pc,
+            Place::Register(Register::Sp) => instruction == 0x00EE || instruction >> 12 == 0x2,
+            // the timers also count down at the end of a frame
+            Place::Register(Register::Dt) => instruction & 0xF0FF == 0xF015 || emu.delay != before.delay,
+            Place::Register(Register::St) => instruction & 0xF0FF == 0xF018 || emu.sound != before.sound,
+        }
+    }
+}
+
+/// The keyboard between two instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Input {
+    keypad: [bool; 16],
+    pending: Option<u8>,
+}
+
+impl Input {
+    fn of(emu: &Emulator) -> Self {
+        Input { keypad: emu.keyboard.keypad, pending: emu.keyboard.pending }
+    }
+
+    fn apply(self, emu: &mut Emulator) {
+        emu.keyboard.keypad = self.keypad;
+        emu.keyboard.pending = self.pending;
+    }
+}
+
+struct Snapshot {
+    position: u64,
+    state: Box<[u8]>,
+    /// the session's place in the frame
+    remaining: Option<u32>,
+}
+
+struct History {
+    /// instructions executed since recording started
+    position: u64,
+    snapshots: VecDeque<Snapshot>,
+    /// the keyboard wherever it was changed before an instruction, by position
+    inputs: BTreeMap<u64, Input>,
+    /// the keyboard after the last instruction, to see changes before the next
+    last_input: Input,
+}
+
+impl History {
+    fn new(emu: &Emulator, remaining: Option<u32>) -> Self {
+        let mut history = History { position: 0, snapshots: VecDeque::new(), inputs: BTreeMap::new(), last_input: Input::of(emu) };
+        history.snapshot(emu, remaining);
+        history
+    }
+
+    fn snapshot(&mut self, emu: &Emulator, remaining: Option<u32>) {
+        let mut state = vec![0; STATE_SIZE].into_boxed_slice();
+        // the buffer is always big enough
+        let _ = emu.save_state(&mut state);
+        self.snapshots.push_back(Snapshot { position: self.position, state, remaining });
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            let _ = self.snapshots.pop_front();
+            let start = self.start();
+            self.inputs = self.inputs.split_off(&start);
+        }
+    }
+
+    /// the earliest position that can be gone back to
+    fn start(&self) -> u64 {
+        self.snapshots.front().map_or(self.position, |snapshot| snapshot.position)
+    }
+
+    /// the last snapshot at or before position
+    fn snapshot_before(&self, position: u64) -> Option<usize> {
+        self.snapshots.iter().rposition(|snapshot| snapshot.position <= position)
+    }
+}
+
+pub struct Session {
+    pub backend: Backend,
+    pub breakpoints: Vec<Expr>,
+    /// instructions left in the frame in progress, None between frames
+    remaining: Option<u32>,
+    history: Option<History>,
+}
+
+impl Session {
+    pub fn new(backend: Backend) -> Self {
+        Session { backend, breakpoints: Vec::new(), remaining: None, history: None }
+    }
+
+    /// whether a frame was started and hasn't finished
+    pub fn mid_frame(&self) -> bool {
+        self.remaining.is_some()
+    }
+
+    /// Start recording from emu's current state so the session can go back to it, throwing away
+    /// any earlier recording. Recording stops by itself if host.random or host.input is set later.
+    pub fn record(&mut self, emu: &Emulator) -> Result<(), RecordError> {
+        replayable(emu)?;
+        self.history = Some(History::new(emu, self.remaining));
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.history = None;
+    }
+
+    /// instructions executed since recording started, None when not recording
+    pub fn position(&self) -> Option<u64> {
+        self.history.as_ref().map(|history| history.position)
+    }
+
+    /// Call after changing emu's memory or registers by hand. Backends drop what they compiled,
+    /// and a recording starts over since replaying can't reproduce the change.
+    pub fn edited(&mut self, emu: &Emulator) {
+        self.backend.invalidate();
+        if self.history.is_some() {
+            let _ = self.record(emu);
+        }
+    }
+
+    /// Execute one instruction, starting a frame before it or finishing one after it the way
+    /// run_frame would. Returns whether the frame finished.
+    pub fn step(&mut self, emu: &mut Emulator) -> bool {
+        if self.history.is_some() && replayable(emu).is_err() {
+            self.history = None;
+        }
+        if let Some(history) = &mut self.history {
+            let input = Input::of(emu);
+            if input != history.last_input {
+                let _ = history.inputs.insert(history.position, input);
+            }
+        }
+        let finished = self.execute(emu);
+        if let Some(history) = &mut self.history {
+            history.position += 1;
+            history.last_input = Input::of(emu);
+            if history.snapshots.back().is_none_or(|last| history.position - last.position >= SNAPSHOT_INTERVAL) {
+                history.snapshot(emu, self.remaining);
+            }
+        }
+        finished
+    }
+
+    /// one instruction the way step does, without recording it
+    fn execute(&mut self, emu: &mut Emulator) -> bool {
+        let remaining = match self.remaining.take() {
+            Some(remaining) => remaining,
+            None => {
+                emu.begin_frame();
+                emu.tickrate
+            }
+        };
+        if remaining > 0 {
+            self.backend.tick(emu);
+        }
+        let remaining = remaining.saturating_sub(1);
+        if remaining == 0 || emu.waits_for_vblank() {
+            emu.end_frame();
+            true
+        } else {
+            self.remaining = Some(remaining);
+            false
+        }
+    }
+
+    /// Run to the end of the frame in progress, or a whole frame between frames, stopping early
+    /// if a breakpoint holds after an instruction. Returns the index of the breakpoint.
+    pub fn run_frame(&mut self, emu: &mut Emulator) -> Option<usize> {
+        if self.breakpoints.is_empty() && !self.mid_frame() && self.history.is_none() {
+            self.backend.run_frame(emu);
+            return None;
+        }
+        loop {
+            let finished = self.step(emu);
+            if let Some(hit) = self.breakpoint_hit(emu) {
+                return Some(hit);
+            }
+            if finished {
+                return None;
+            }
+        }
+    }
+
+    /// the index of the first breakpoint that holds for emu
+    pub fn breakpoint_hit(&self, emu: &Emulator) -> Option<usize> {
+        self.breakpoints.iter().position(|breakpoint| breakpoint.holds(emu))
+    }
+
+    /// Go back one instruction. Returns false at the start of the recording, or when not recording.
+    pub fn step_back(&mut self, emu: &mut Emulator) -> bool {
+        let mut history = match self.history.take() {
+            Some(history) => history,
+            None => return false,
+        };
+        let moved = history.position > history.start();
+        if moved {
+            let target = history.position - 1;
+            self.seek(&mut history, emu, target);
+        }
+        self.history = Some(history);
+        moved
+    }
+
+    /// Run backwards to the last place a breakpoint held before this one, or the start of the
+    /// recording if none did. Returns the index of the breakpoint like run_frame.
+    pub fn reverse_continue(&mut self, emu: &mut Emulator) -> Option<usize> {
+        let mut history = self.history.take()?;
+        let breakpoints = mem::take(&mut self.breakpoints);
+        let found = match history.position.checked_sub(1) {
+            Some(end) => self.search_back(&mut history, emu, end, |emu, _| breakpoints.iter().any(|breakpoint| breakpoint.holds(emu))),
+            None => None,
+        };
+        self.breakpoints = breakpoints;
+        let target = found.unwrap_or_else(|| history.start());
+        self.seek(&mut history, emu, target);
+        self.history = Some(history);
+        found.and_then(|_| self.breakpoint_hit(emu))
+    }
+
+    /// Run backwards to just before the last instruction that wrote place, a register or
+    /// `[address]` expr, so pc is that instruction. Writing the value it already held counts, and
+    /// an address is worked out once, from where the session is now. Goes to the start of the
+    /// recording if nothing wrote it. Returns whether something did.
+    pub fn reverse_to_last_write(&mut self, emu: &mut Emulator, place: &Expr) -> bool {
+        let place = match Place::of(place, emu) {
+            Some(place) => place,
+            None => return false,
+        };
+        let mut history = match self.history.take() {
+            Some(history) => history,
+            None => return false,
+        };
+        // writes to memory are counted by an access map, borrow one if there isn't one already
+        let borrowed = emu.access.is_none();
+        if borrowed {
+            emu.access = Some(Box::default());
+        }
+        let end = history.position;
+        let mut before = None;
+        let found = self.search_back(&mut history, emu, end, |emu, first| {
+            let wrote = !first && before.is_some_and(|before| place.written(&before, emu));
+            before = Some(Before::of(emu, place));
+            wrote
+        });
+        // found is just after the write, never the first position replayed
+        let target = found.map_or_else(|| history.start(), |found| found - 1);
+        self.seek(&mut history, emu, target);
+        if borrowed {
+            emu.access = None;
+        }
+        self.history = Some(history);
+        found.is_some()
+    }
+
+    /// Find the last position up to end where found holds, replaying a snapshot at a time from the
+    /// latest. found sees every position in order from each snapshot, and is told which positions
+    /// are the snapshot's, with nothing before them to compare against.
+    fn search_back(&mut self, history: &mut History, emu: &mut Emulator, mut end: u64, mut found: impl FnMut(&Emulator, bool) -> bool) -> Option<u64> {
+        let mut index = history.snapshot_before(end)?;
+        loop {
+            self.restore(history, emu, index);
+            let start = history.position;
+            let mut last = None;
+            loop {
+                if found(emu, history.position == start) {
+                    last = Some(history.position);
+                }
+                if history.position >= end {
+                    break;
+                }
+                self.replay(history, emu);
+            }
+            if last.is_some() || index == 0 {
+                return last;
+            }
+            // the snapshot's own position is checked again against the one before it
+            index -= 1;
+            end = start;
+        }
+    }
+
+    /// Go to position, forgetting everything recorded after it so running on from there records
+    /// what happens next instead
+    fn seek(&mut self, history: &mut History, emu: &mut Emulator, position: u64) {
+        if let Some(index) = history.snapshot_before(position) {
+            self.restore(history, emu, index);
+        }
+        while history.position < position {
+            self.replay(history, emu);
+        }
+        // leave the keyboard how it was before the next instruction ran
+        if let Some(&input) = history.inputs.get(&position) {
+            input.apply(emu);
+            history.last_input = input;
+        }
+        let _ = history.inputs.split_off(&(position + 1));
+        while history.snapshots.back().is_some_and(|snapshot| snapshot.position > position) {
+            let _ = history.snapshots.pop_back();
+        }
+    }
+
+    fn restore(&mut self, history: &mut History, emu: &mut Emulator, index: usize) {
+        let snapshot = &history.snapshots[index];
+        // save_state wrote it, so it loads
+        let _ = emu.load_state(&snapshot.state);
+        self.remaining = snapshot.remaining;
+        self.backend.invalidate();
+        history.position = snapshot.position;
+        history.last_input = Input::of(emu);
+    }
+
+    /// execute the next instruction with the keyboard as it was recorded
+    fn replay(&mut self, history: &mut History, emu: &mut Emulator) {
+        if let Some(&input) = history.inputs.get(&history.position) {
+            input.apply(emu);
+        }
+        let _ = self.execute(emu);
+        history.position += 1;
+        history.last_input = Input::of(emu);
+    }
+}
@@ -13,10 +13,24 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+#[cfg(feature = "std")]
+use std::boxed::Box;
+
+use crate::access::Access;
+#[cfg(feature = "std")]
+use crate::access::AccessMap;
 use crate::constants;
-use crate::cpu::{CPU, mnemonic};
+#[cfg(feature = "trace")]
+use crate::cpu::mnemonic;
+use crate::decode::{decode, DecodeCache, WriteLog};
 use crate::keyboard::Keyboard;
+#[cfg(feature = "std")]
+use crate::profile::Profile;
 use crate::display::Display;
+use crate::error::Error;
+use crate::host::Host;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
 
 pub struct Emulator {
     pub registers: [u8; 16],
@@ -30,75 +44,132 @@ pub struct Emulator {
     pub display: Display,
     pub instruction: u16,
     pub keyboard: Keyboard,
+    pub quirks: Quirks,
+    /// instructions executed per 60hz frame by run_frame
+    pub tickrate: u32,
+    pub rng: Rng,
+    pub host: Host,
+    /// memory written since a decode cache last looked
+    pub(crate) code_writes: WriteLog,
+    /// counts of how each address is used, only kept while this is Some
+    #[cfg(feature = "std")]
+    pub access: Option<Box<AccessMap>>,
+    /// instructions counted by call stack, only kept while this is Some
+    #[cfg(feature = "std")]
+    pub profile: Option<Box<Profile>>,
 }
 
 impl Emulator {
     pub fn tick(&mut self) {
-        self.instruction = ((self.memory[self.pc as usize] as u16) << 8) | self.memory[(self.pc + 1) as usize] as u16;
-
-        let n: u8 = (self.instruction & 0x000f) as u8;
-        let nn: u8 = (self.instruction & 0x00ff) as u8;
-
-        println!("{:#x} - {}", self.instruction, mnemonic(self.instruction));
-        match self.instruction >> 12 {
-            0x0 => {
-                match nn {
-                    0xE0 => { self.cls() }
-                    0xEE => { self.ret() }
-                    _ => { panic!("Invalid Opcode {:#X} at PC {:#x}", self.instruction, self.pc) }
-                }
-            }
-            0x1 => { self.jp_addr() }
-            0x2 => { self.call_addr() }
-            0x3 => { self.se_vx_byte() }
-            0x4 => { self.sne_vx_byte() }
-            0x5 => { self.se_vx_vy() }
-            0x6 => { self.ld_vx_byte() }
-            0x7 => { self.add_vx_byte() }
-            0x8 => {
-                match n {
-                    0x0 => { self.ld_vx_vy() }
-                    0x1 => { self.or_vx_vy() }
-                    0x2 => { self.and_vx_vy() }
-                    0x3 => { self.xor_vx_vy() }
-                    0x4 => { self.add_vx_vy() }
-                    0x5 => { self.sub_vx_vy() }
-                    0x6 => { self.shr_vx_vy() }
-                    0x7 => { self.subn_vx_vy() }
-                    0x8 => { self.shl_vx_vy() }
-                    _ => { panic!("Invalid Opcode {:#X} at PC {:#x}", self.instruction, self.pc) }
-                }
-            }
-            0x9 => { self.sne_vx_vy() }
-            0xA => { self.ld_i_addr() }
-            0xB => { self.jp_v0_addr() }
-            0xC => { self.rnd_vx_byte() }
-            0xD => { self.drw_vx_vy_nibble() }
-            0xE => {
-                match nn {
-                    0x9e => { self.skp_vx() }
-                    0xA1 => { self.sknp_vx() }
-                    _ => { panic!("Invalid Opcode {:#X} at PC {:#x}", self.instruction, self.pc) }
-                }
+        self.instruction = self.fetch();
+        self.record_execution();
+        #[cfg(feature = "trace")]
+        std::println!("{:#x} - {}", self.instruction, mnemonic(self.instruction));
+        decode(self.instruction)(self);
+    }
+
+    /// the two bytes at pc, wrapping around the end of memory
+    pub(crate) fn fetch(&self) -> u16 {
+        let pc = self.pc as usize & 0xFFF;
+        ((self.memory[pc] as u16) << 8) | self.memory[(pc + 1) & 0xFFF] as u16
+    }
+
+    /// count the instruction at pc as executed if there's an access map or a profile,
+    /// Emulator::instruction has to hold it already
+    #[inline(always)]
+    pub(crate) fn record_execution(&mut self) {
+        self.record(Access::Execute, self.pc as usize);
+        self.record(Access::Execute, self.pc as usize + 1);
+        #[cfg(feature = "std")]
+        if let Some(profile) = &mut self.profile {
+            profile.record(self.pc, self.sp, self.instruction);
+        }
+    }
+
+    /// whether executions are being counted at all
+    #[cfg(feature = "std")]
+    pub(crate) fn recording(&self) -> bool {
+        self.access.is_some() || self.profile.is_some()
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn record(&mut self, access: Access, address: usize) {
+        if let Some(map) = &mut self.access {
+            map.record(access, address);
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub(crate) fn record(&mut self, _access: Access, _address: usize) {}
+
+    /// note a sprite drawn from address if there's an access map
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn record_sprite(&mut self, address: u16, height: u8) {
+        if let Some(map) = &mut self.access {
+            map.record_sprite(address, height);
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub(crate) fn record_sprite(&mut self, _address: u16, _height: u8) {}
+
+    /// Run one 60hz frame: execute tickrate instructions and then count down the timers
+    pub fn run_frame(&mut self) {
+        self.frame(|emu, budget| emu.run_ticks(budget, Emulator::tick));
+    }
+
+    /// Run one 60hz frame like run_frame, executing instructions through a decode cache
+    pub fn run_frame_cached(&mut self, cache: &mut DecodeCache) {
+        self.frame(|emu, budget| emu.run_ticks(budget, |emu| cache.tick(emu)));
+    }
+
+    /// everything in a frame besides executing instructions, which run does given the tickrate
+    pub(crate) fn frame(&mut self, run: impl FnOnce(&mut Emulator, u32)) {
+        self.begin_frame();
+        run(self, self.tickrate);
+        self.end_frame();
+    }
+
+    /// poll input for a frame about to run
+    pub(crate) fn begin_frame(&mut self) {
+        if let Some(input) = self.host.input {
+            self.keyboard.set_keypad(input());
+        }
+    }
+
+    /// count down the timers and present the display after a frame's instructions
+    pub(crate) fn end_frame(&mut self) {
+        #[cfg(feature = "std")]
+        if let Some(profile) = &mut self.profile {
+            profile.end_frame();
+        }
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+        if let Some(present) = self.host.present {
+            if self.display.dirty {
+                present(&self.display.receive_change());
             }
-            0xF => {
-                match nn {
-                    0x07 => { self.ld_vx_dt() }
-                    0x0A => { self.ld_vx_k() }
-                    0x15 => { self.ld_dt_vx() }
-                    0x18 => { self.ld_st_vx() }
-                    0x1E => { self.add_i_vx() }
-                    0x29 => { self.ld_f_vx() }
-                    0x33 => { self.ld_b_vx() }
-                    0x55 => { self.ld_mem_vx() }
-                    0x65 => { self.ld_vx_mem() }
-                    _ => { panic!("Invalid Opcode {:#X} at PC {:#x}", self.instruction, self.pc) }
-                }
+        }
+    }
+
+    fn run_ticks(&mut self, budget: u32, mut tick: impl FnMut(&mut Emulator)) {
+        for _ in 0..budget {
+            tick(self);
+            if self.waits_for_vblank() {
+                break;
             }
-            _ => { panic!("Invalid Opcode {:#X} at PC {:#x}", self.instruction, self.pc) }
         }
     }
 
+    /// with the vblank quirk a draw waits for the next frame, so nothing else runs this frame
+    pub fn waits_for_vblank(&self) -> bool {
+        self.quirks.vblank && self.instruction >> 12 == 0xD
+    }
+
     // return lower 12 bits of an opcode
     pub fn get_nnn(&self) -> u16 { self.instruction & 0x0FFF }
 
@@ -122,12 +193,14 @@ impl Emulator {
         ((self.instruction & 0x00F0) >> 4) as u8
     }
 
-    pub fn load_rom(&mut self, rom: Vec<u8>) {
-        let mut index = 0x200;
-        for byte in rom {
-            self.memory[index] = byte;
-            index += 1;
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Error> {
+        let end = 0x200 + rom.len();
+        if end > self.memory.len() {
+            return Err(Error::RomTooLarge(rom.len()));
         }
+        self.memory[0x200..end].copy_from_slice(rom);
+        self.code_writes.record(0x200, rom.len());
+        Ok(())
     }
 
     pub fn new() -> Self {
@@ -143,6 +216,15 @@ impl Emulator {
             display: Display::new(),
             instruction: 0,
             keyboard: Keyboard::new(),
+            quirks: Quirks::default(),
+            tickrate: constants::DEFAULT_TICKRATE,
+            rng: Rng::default(),
+            host: Host::default(),
+            code_writes: WriteLog::default(),
+            #[cfg(feature = "std")]
+            access: None,
+            #[cfg(feature = "std")]
+            profile: None,
         };
         for i in 0..constants::FONTSET.len() {
             state.memory[constants::FONTSET_START as usize + i] = constants::FONTSET[i];
@@ -150,3 +232,9 @@ impl Emulator {
         state
     }
 }
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// the rom is this many bytes, more than fits in memory above 0x200
+    RomTooLarge(usize),
+    /// a save state buffer is too small to hold or read a state
+    StateBufferTooSmall(usize),
+    /// a save state is corrupt or from an incompatible version of rust8
+    InvalidState,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RomTooLarge(len) => write!(f, "rom is {} bytes, too large to fit in memory", len),
+            Error::StateBufferTooSmall(len) => write!(f, "save state buffer is {} bytes, too small for a state", len),
+            Error::InvalidState => write!(f, "save state is invalid or from a different version"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
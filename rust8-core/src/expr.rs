@@ -0,0 +1,330 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Small expressions over emulator state, for conditional breakpoints and watches.
+//!
+//! An expression is made of numbers in decimal, `0x` hex or `0b` binary, the registers `V0`-`VF`,
+//! `I`, `PC`, `SP`, `DT` and `ST` in any case, `[address]` for the byte in memory at an address,
+//! parentheses, the unary `!`, `-` and `~`, and C's binary operators with C's precedence:
+//! `* / %`, `+ -`, `<< >>`, `< <= > >=`, `== !=`, `&`, `^`, `|`, `&&` and `||`.
+//! Everything is an unsigned 32 bit number that wraps, comparisons and logic give 1 or 0, and an
+//! expression holds when it's nonzero. So `V3 == 0x10 && [I] != 0` or `PC == 0x2A4` make breakpoints.
+
+use std::boxed::Box;
+use std::fmt;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::emu::Emulator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `!`, 1 for 0 and 0 for anything else
+    Not,
+    Negate,
+    /// `~`, every bit flipped
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+/// binary operators from the loosest binding to the tightest
+const PRECEDENCE: [&[(&str, BinaryOp)]; 10] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+/// every operator token, longest first so `<=` isn't read as `<`
+const OPERATORS: [&str; 22] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "|", "^", "&", "<", ">", "+", "-", "*", "/", "%", "!", "~", "(", ")",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(u32),
+    Register(Register),
+    /// the byte in memory at an address, which wraps at 4096
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser { tokens: tokenize(source)?, pos: 0, end: source.len() };
+        let expr = parser.expr(0)?;
+        match parser.tokens.get(parser.pos) {
+            Some(&(column, _)) => Err(ParseError { column, message: "expected an operator" }),
+            None => Ok(expr),
+        }
+    }
+
+    /// The expression's value for emu's current state, None if it divides by zero
+    pub fn eval(&self, emu: &Emulator) -> Option<u32> {
+        Some(match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => match *register {
+                Register::V(x) => u32::from(emu.registers[x as usize]),
+                Register::I => u32::from(emu.index),
+                Register::Pc => u32::from(emu.pc),
+                Register::Sp => u32::from(emu.sp),
+                Register::Dt => u32::from(emu.delay),
+                Register::St => u32::from(emu.sound),
+            },
+            Expr::Memory(address) => u32::from(emu.memory[address.eval(emu)? as usize & 0xFFF]),
+            Expr::Unary(op, operand) => {
+                let value = operand.eval(emu)?;
+                match op {
+                    UnaryOp::Not => u32::from(value == 0),
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Complement => !value,
+                }
+            }
+            // short circuit so the right side can't divide by zero when it doesn't matter
+            Expr::Binary(BinaryOp::And, left, right) => u32::from(left.eval(emu)? != 0 && right.eval(emu)? != 0),
+            Expr::Binary(BinaryOp::Or, left, right) => u32::from(left.eval(emu)? != 0 || right.eval(emu)? != 0),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(emu)?, right.eval(emu)?);
+                match op {
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    BinaryOp::Div => left.checked_div(right)?,
+                    BinaryOp::Rem => left.checked_rem(right)?,
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Shl => left.checked_shl(right).unwrap_or(0),
+                    BinaryOp::Shr => left.checked_shr(right).unwrap_or(0),
+                    BinaryOp::Lt => u32::from(left < right),
+                    BinaryOp::Le => u32::from(left <= right),
+                    BinaryOp::Gt => u32::from(left > right),
+                    BinaryOp::Ge => u32::from(left >= right),
+                    BinaryOp::Eq => u32::from(left == right),
+                    BinaryOp::Ne => u32::from(left != right),
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+                }
+            }
+        })
+    }
+
+    /// whether the expression is nonzero for emu's current state
+    pub fn holds(&self, emu: &Emulator) -> bool {
+        self.eval(emu).is_some_and(|value| value != 0)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Expr::parse(source)
+    }
+}
+
+/// Why an expression didn't parse, and where
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// byte offset into the source, the source's length for its end
+    pub column: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Register(Register),
+    Operator(&'static str),
+    OpenBracket,
+    CloseBracket,
+}
+
+/// tokens paired with the column they start at
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        let column = source.len() - rest.len();
+        let word: String = rest.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+        let (token, len) = if !word.is_empty() {
+            let token = match word.as_bytes()[0] {
+                b'0'..=b'9' => Token::Number(number(&word).ok_or(ParseError { column, message: "invalid number" })?),
+                _ => Token::Register(register(&word).ok_or(ParseError { column, message: "unknown register" })?),
+            };
+            (token, word.len())
+        } else if rest.starts_with('[') {
+            (Token::OpenBracket, 1)
+        } else if rest.starts_with(']') {
+            (Token::CloseBracket, 1)
+        } else {
+            let operator = OPERATORS.iter().find(|&&operator| rest.starts_with(operator))
+                .ok_or(ParseError { column, message: "unexpected character" })?;
+            (Token::Operator(operator), operator.len())
+        };
+        tokens.push((column, token));
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn number(word: &str) -> Option<u32> {
+    let lower = word.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+fn register(word: &str) -> Option<Register> {
+    let upper = word.to_ascii_uppercase();
+    Some(match upper.as_str() {
+        "I" => Register::I,
+        "PC" => Register::Pc,
+        "SP" => Register::Sp,
+        "DT" => Register::Dt,
+        "ST" => Register::St,
+        _ if upper.len() == 2 && upper.starts_with('V') => Register::V(u8::from_str_radix(&upper[1..], 16).ok()?),
+        _ => return None,
+    })
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// column reported for running out of tokens
+    end: usize,
+}
+
+impl Parser {
+    /// binary operators at a precedence level or tighter, left associative
+    fn expr(&mut self, level: usize) -> Result<Expr, ParseError> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut left = self.expr(level + 1)?;
+        while let Some(&(_, Token::Operator(operator))) = self.tokens.get(self.pos) {
+            let op = match PRECEDENCE[level].iter().find(|(name, _)| *name == operator) {
+                Some(&(_, op)) => op,
+                None => break,
+            };
+            self.pos += 1;
+            let right = self.expr(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let (column, token) = match self.tokens.get(self.pos) {
+            Some((column, token)) => (*column, token.clone()),
+            None => return Err(ParseError { column: self.end, message: "expected a value" }),
+        };
+        self.pos += 1;
+        Ok(match token {
+            Token::Number(value) => Expr::Number(value),
+            Token::Register(register) => Expr::Register(register),
+            Token::Operator("!") => Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)),
+            Token::Operator("-") => Expr::Unary(UnaryOp::Negate, Box::new(self.unary()?)),
+            Token::Operator("~") => Expr::Unary(UnaryOp::Complement, Box::new(self.unary()?)),
+            Token::Operator("(") => {
+                let expr = self.expr(0)?;
+                self.close(Token::Operator(")"), "expected )")?;
+                expr
+            }
+            Token::OpenBracket => {
+                let address = self.expr(0)?;
+                self.close(Token::CloseBracket, "expected ]")?;
+                Expr::Memory(Box::new(address))
+            }
+            _ => return Err(ParseError { column, message: "expected a value" }),
+        })
+    }
+
+    fn close(&mut self, token: Token, message: &'static str) -> Result<(), ParseError> {
+        match self.tokens.get(self.pos) {
+            Some((_, found)) if *found == token => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(&(column, _)) => Err(ParseError { column, message }),
+            None => Err(ParseError { column: self.end, message }),
+        }
+    }
+}
+
+/// Written the way the parser reads it, like `VA` or `PC`
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => f.write_str("I"),
+            Register::Pc => f.write_str("PC"),
+            Register::Sp => f.write_str("SP"),
+            Register::Dt => f.write_str("DT"),
+            Register::St => f.write_str("ST"),
+        }
+    }
+}
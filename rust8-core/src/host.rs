@@ -0,0 +1,28 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Callbacks a host can install instead of driving the emulator's fields directly.
+///
+/// These are plain function pointers so they work without std or an allocator, which suits
+/// firmware that keeps its peripherals in statics.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Host {
+    /// source of random bytes for Cxkk, replacing the emulator's seeded generator
+    pub random: Option<fn() -> u8>,
+    /// polled at the start of every frame, returns the keypad with bit n set while key n is down
+    pub input: Option<fn() -> u16>,
+    /// called at the end of every frame that changed the display
+    pub present: Option<fn(&[bool; 64 * 32])>,
+}
@@ -0,0 +1,151 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An experimental backend that compiles basic blocks into a compact IR.
+//!
+//! A block is the straight line of instructions from an address up to the first jump, call,
+//! return, skip, draw, key wait or memory write, which ends it. Blocks are made of the same ops
+//! the decode cache keeps, see `decode::Op`.
+//! A block that only jumps to itself spends the rest of the frame's budget at once.
+//! Fx33 and Fx55 end a block so the blocks they overwrite are thrown away before anything else runs,
+//! loading a rom or a save state throws away everything. Anything else that writes
+//! `Emulator::memory` directly has to call `Jit::invalidate`.
+
+use std::boxed::Box;
+use std::vec::Vec;
+
+use crate::decode::Op;
+use crate::emu::Emulator;
+
+/// longest block compiled, longer straight lines are split
+const MAX_BLOCK: usize = 32;
+
+struct Block {
+    ops: Box<[Op]>,
+    /// address just past the block's last instruction
+    end: usize,
+    /// the block is a jump to itself, running it again changes nothing
+    idle: bool,
+}
+
+/// Compiled blocks by start address, for one emulator at a time
+pub struct Jit {
+    blocks: Vec<Option<Block>>,
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        Jit { blocks: (0..4096).map(|_| None).collect() }
+    }
+
+    /// Throw away every block, needed after writing to the emulator's memory directly
+    pub fn invalidate(&mut self) {
+        for block in self.blocks.iter_mut() {
+            *block = None;
+        }
+    }
+
+    /// Execute up to budget instructions, stopping early after a draw with the vblank quirk
+    pub fn run(&mut self, emu: &mut Emulator, budget: u32) {
+        let mut remaining = budget as usize;
+        while remaining > 0 {
+            self.sync(emu);
+            let pc = emu.pc as usize & 0xFFF;
+            let block = match &mut self.blocks[pc] {
+                Some(block) => block,
+                empty => empty.get_or_insert(compile(&emu.memory, pc)),
+            };
+            for op in block.ops.iter().take(remaining) {
+                op.run(emu);
+            }
+            if block.idle {
+                // the jump would have run again for the rest of the budget
+                if emu.recording() {
+                    for _ in 1..remaining {
+                        emu.record_execution();
+                    }
+                }
+                remaining = 0;
+            } else {
+                remaining = remaining.saturating_sub(block.ops.len());
+            }
+            if emu.waits_for_vblank() {
+                break;
+            }
+        }
+    }
+
+    /// Throw away the blocks instructions have written over since the last block ran.
+    /// Only needed before swapping emu's memory out from under the jit, run does it otherwise.
+    pub fn sync(&mut self, emu: &mut Emulator) {
+        if let Some((start, end)) = emu.code_writes.take() {
+            self.invalidate_range(start, end);
+        }
+    }
+
+    /// drop the blocks overlapping start..end
+    fn invalidate_range(&mut self, start: usize, end: usize) {
+        let first = start.saturating_sub(2 * MAX_BLOCK);
+        for block in self.blocks[first..end.min(4096)].iter_mut() {
+            if block.as_ref().is_some_and(|block| block.end > start) {
+                *block = None;
+            }
+        }
+    }
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Emulator {
+    /// Run one 60hz frame like run_frame, executing compiled blocks
+    pub fn run_frame_jit(&mut self, jit: &mut Jit) {
+        self.frame(|emu, budget| jit.run(emu, budget));
+    }
+}
+
+fn compile(memory: &[u8; 4096], start: usize) -> Block {
+    let mut ops: Vec<Op> = Vec::new();
+    let mut address = start;
+    loop {
+        if address + 1 >= memory.len() {
+            // there's no whole instruction at the end of memory, leave it to the interpreter
+            ops.push(Op::INTERPRET);
+            address += 2;
+            break;
+        }
+        let instruction = u16::from(memory[address]) << 8 | u16::from(memory[address + 1]);
+        ops.push(Op::new(instruction));
+        address += 2;
+        if ends_block(instruction) || ops.len() == MAX_BLOCK {
+            break;
+        }
+    }
+    let idle = address == start + 2 && memory.get(start..start + 2) == Some(&(0x1000 | start as u16).to_be_bytes()[..]);
+    Block { ops: ops.into_boxed_slice(), end: address, idle }
+}
+
+/// whether an instruction can do anything but fall through to the next one, or writes memory
+fn ends_block(instruction: u16) -> bool {
+    match instruction >> 12 {
+        0x0 => instruction != 0x00E0,
+        0x1 | 0x2 | 0x3 | 0x4 | 0x5 | 0x9 | 0xB | 0xD | 0xE => true,
+        0xF => matches!(instruction & 0xFF, 0x0A | 0x33 | 0x55),
+        _ => false,
+    }
+}
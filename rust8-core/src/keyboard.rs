@@ -1,31 +1,39 @@
-use std::sync::mpsc::{Sender, Receiver};
-use std::sync::mpsc;
-
 #[derive(Debug)]
 pub struct Keyboard {
-    sender: Sender<u8>,
-    receiver: Receiver<u8>,
+    /// most recent press that hasn't been consumed by Fx0A yet
+    pub(crate) pending: Option<u8>,
     pub keypad: [bool; 16],
 }
 
 impl Keyboard {
-    pub fn wait_for_keypress(&self) -> u8 {
-        self.receiver.recv().unwrap()
+    /// return the most recently pressed key, if there has been a press since the last call
+    pub fn take_keypress(&mut self) -> Option<u8> {
+        self.pending.take()
     }
 
     pub fn press_key(&mut self, key: u8) {
-        self.sender.send(key).expect("the send didnt work, idk when it would fail but i need to add error handing to this");
+        self.pending = Some(key);
         self.keypad[key as usize] = true;
     }
     pub fn release_key(&mut self, key: u8) {
         self.keypad[key as usize] = false;
     }
 
+    /// press and release keys to match a bitmask with bit n set while key n is down
+    pub fn set_keypad(&mut self, keys: u16) {
+        for key in 0..16u8 {
+            let down = keys & (1 << key) != 0;
+            if down && !self.keypad[key as usize] {
+                self.press_key(key);
+            } else if !down && self.keypad[key as usize] {
+                self.release_key(key);
+            }
+        }
+    }
+
     pub fn new() -> Self {
-        let(_sender, _receiver): (Sender<u8>, Receiver<u8>) = mpsc::channel();
-        return Keyboard {
-            sender: _sender,
-            receiver: _receiver,
+        Keyboard {
+            pending: None,
             keypad: [false; 16]
         }
     }
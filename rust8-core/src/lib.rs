@@ -13,6 +13,13 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+//! A chip-8 interpreter.
+//!
+//! The crate is `no_std` and never allocates when built without its default `std` feature,
+//! hosts then supply input, randomness and a place to put frames through `host::Host`.
+//! The `trace` feature prints every instruction as it executes.
+
+#![no_std]
 #![forbid(unsafe_code)]
 #![deny(
 // missing_debug_implementations,
@@ -26,9 +33,30 @@ unused_results,
 warnings
 )]
 
+#[cfg(feature = "std")]
+extern crate std;
 
 pub mod emu;
 pub mod constants;
-mod cpu;
+pub mod error;
+pub mod host;
+pub mod quirks;
+pub mod rng;
+pub mod state;
+pub mod cpu;
+pub mod alu;
+pub mod access;
+pub mod clock;
+pub mod decode;
+#[cfg(feature = "std")]
+pub mod jit;
+#[cfg(feature = "std")]
+pub mod backend;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod expr;
+#[cfg(feature = "std")]
+pub mod debug;
 mod keyboard;
 mod display;
\ No newline at end of file
@@ -0,0 +1,189 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Instruction counts attributed to the subroutines that ran them.
+//!
+//! Give an emulator a profile with `emu.profile = Some(Box::default())` and every executed instruction
+//! is counted against the call stack it ran under, whichever backend runs it. Calls and returns are
+//! followed through the emulator's own stack pointer, so a subroutine is entered when sp grows and
+//! left when it shrinks, and named by the first address run after it grew. Code outside any call
+//! belongs to the root, which is named by `ROOT`. A profile attached with a call already in
+//! progress puts the open calls under the first address it sees.
+
+use std::vec;
+use std::vec::Vec;
+
+/// entry address the root of every stack is named by, where roms start
+pub const ROOT: u16 = 0x200;
+
+/// one place in the call tree, a subroutine called from a particular stack
+struct Node {
+    entry: u16,
+    parent: usize,
+    children: Vec<usize>,
+    /// times the subroutine was entered from here
+    calls: u64,
+    /// instructions run here and not in a callee
+    instructions: u64,
+}
+
+/// Totals for one subroutine over every stack it was called from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubroutineProfile {
+    pub entry: u16,
+    pub calls: u64,
+    /// instructions run in the subroutine itself
+    pub exclusive: u64,
+    /// instructions run in the subroutine and everything it called, recursion counted once
+    pub inclusive: u64,
+}
+
+/// A call tree of instruction counts and the DRW count of every frame
+pub struct Profile {
+    /// nodes[0] is the root, children always come after their parents
+    nodes: Vec<Node>,
+    current: usize,
+    depth: usize,
+    draws: Vec<u32>,
+    frame_draws: u32,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile {
+            nodes: vec![Node { entry: ROOT, parent: 0, children: Vec::new(), calls: 1, instructions: 0 }],
+            current: 0,
+            depth: 0,
+            draws: Vec::new(),
+            frame_draws: 0,
+        }
+    }
+
+    /// instructions counted so far
+    pub fn total(&self) -> u64 {
+        self.nodes.iter().map(|node| node.instructions).sum()
+    }
+
+    /// DRW instructions run in each frame finished so far
+    pub fn draws_per_frame(&self) -> &[u32] {
+        &self.draws
+    }
+
+    /// Every subroutine that ran with its totals, the root first and the rest by entry address
+    pub fn subroutines(&self) -> Vec<SubroutineProfile> {
+        let inclusive = self.inclusive();
+        let mut subroutines: Vec<SubroutineProfile> = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let position = match subroutines.iter().position(|subroutine| subroutine.entry == node.entry) {
+                Some(position) => position,
+                None => {
+                    subroutines.push(SubroutineProfile { entry: node.entry, calls: 0, exclusive: 0, inclusive: 0 });
+                    subroutines.len() - 1
+                }
+            };
+            let subroutine = &mut subroutines[position];
+            subroutine.calls += node.calls;
+            subroutine.exclusive += node.instructions;
+            if !self.recursive(index) {
+                subroutine.inclusive += inclusive[index];
+            }
+        }
+        subroutines[1..].sort_by_key(|subroutine| subroutine.entry);
+        subroutines
+    }
+
+    /// Every call stack that ran instructions of its own, as entry addresses from the root down, with
+    /// how many it ran. This is what flamegraph tools call folded stacks.
+    pub fn stacks(&self) -> Vec<(Vec<u16>, u64)> {
+        self.nodes.iter().enumerate().filter(|(_, node)| node.instructions > 0).map(|(index, node)| {
+            let mut stack = vec![node.entry];
+            let mut index = index;
+            while index != 0 {
+                index = self.nodes[index].parent;
+                stack.push(self.nodes[index].entry);
+            }
+            stack.reverse();
+            (stack, node.instructions)
+        }).collect()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Profile::new();
+    }
+
+    /// count the instruction at pc, run with sp entries on the stack
+    pub(crate) fn record(&mut self, pc: u16, sp: u8, instruction: u16) {
+        let sp = sp as usize;
+        while self.depth > sp {
+            self.current = self.nodes[self.current].parent;
+            self.depth -= 1;
+        }
+        while self.depth < sp {
+            self.current = self.enter(pc);
+            self.depth += 1;
+        }
+        self.nodes[self.current].instructions += 1;
+        if instruction >> 12 == 0xD {
+            self.frame_draws += 1;
+        }
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.draws.push(self.frame_draws);
+        self.frame_draws = 0;
+    }
+
+    /// the child of the current node for a call to entry, made on the first call
+    fn enter(&mut self, entry: u16) -> usize {
+        let current = self.current;
+        let found = self.nodes[current].children.iter().copied().find(|&child| self.nodes[child].entry == entry);
+        let child = found.unwrap_or_else(|| {
+            self.nodes.push(Node { entry, parent: current, children: Vec::new(), calls: 0, instructions: 0 });
+            let child = self.nodes.len() - 1;
+            self.nodes[current].children.push(child);
+            child
+        });
+        self.nodes[child].calls += 1;
+        child
+    }
+
+    /// instructions run under each node, its own and its callees'
+    fn inclusive(&self) -> Vec<u64> {
+        let mut inclusive: Vec<u64> = self.nodes.iter().map(|node| node.instructions).collect();
+        for index in (1..self.nodes.len()).rev() {
+            inclusive[self.nodes[index].parent] += inclusive[index];
+        }
+        inclusive
+    }
+
+    /// whether a node's subroutine is already somewhere above it
+    fn recursive(&self, index: usize) -> bool {
+        let entry = self.nodes[index].entry;
+        let mut ancestor = index;
+        while ancestor != 0 {
+            ancestor = self.nodes[ancestor].parent;
+            if self.nodes[ancestor].entry == entry {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
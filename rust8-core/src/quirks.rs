@@ -0,0 +1,112 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Behaviours that differ between chip-8 interpreters.
+///
+/// Field names follow the quirks in the community chip-8-database so entries can be applied directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6 and 8xyE shift Vx in place instead of shifting Vy into Vx
+    pub shift: bool,
+    /// Fx55 and Fx65 increment I by x instead of x + 1
+    pub memory_increment_by_x: bool,
+    /// Fx55 and Fx65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    /// sprites wrap around the edges of the screen instead of being clipped
+    pub wrap: bool,
+    /// Bnnn jumps to nnn + Vx, where x is the high nibble of nnn, instead of nnn + V0
+    pub jump: bool,
+    /// drawing waits for the vertical blank so at most one sprite is drawn per frame
+    pub vblank: bool,
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0
+    pub logic: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter
+    pub const CHIP8: Quirks = Quirks {
+        shift: false,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        wrap: false,
+        jump: false,
+        vblank: true,
+        logic: true,
+    };
+
+    /// Modern chip-8 as implemented by most emulators
+    pub const MODERN: Quirks = Quirks {
+        shift: false,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        wrap: false,
+        jump: false,
+        vblank: false,
+        logic: false,
+    };
+
+    /// SUPER-CHIP 1.1 on the HP48
+    pub const SCHIP: Quirks = Quirks {
+        shift: true,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: true,
+        wrap: false,
+        jump: true,
+        vblank: false,
+        logic: false,
+    };
+
+    /// XO-CHIP
+    pub const XOCHIP: Quirks = Quirks {
+        shift: false,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        wrap: true,
+        jump: false,
+        vblank: false,
+        logic: false,
+    };
+
+    /// What rust8 did before quirks were configurable
+    pub const RUST8: Quirks = Quirks {
+        shift: true,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        wrap: true,
+        jump: false,
+        vblank: false,
+        logic: false,
+    };
+
+    /// Every preset with the name from_name knows it by
+    pub const PRESETS: [(&'static str, Quirks); 5] = [
+        ("rust8", Quirks::RUST8),
+        ("chip8", Quirks::CHIP8),
+        ("modern", Quirks::MODERN),
+        ("schip", Quirks::SCHIP),
+        ("xochip", Quirks::XOCHIP),
+    ];
+
+    /// Look up a preset by name: rust8, chip8, modern, schip or xochip
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        Quirks::PRESETS.iter().find(|&&(preset, _)| preset == name).map(|&(_, quirks)| quirks)
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::RUST8
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Small deterministic random number generator used by Cxkk.
+///
+/// The host picks the seed, so the same seed and inputs always play out the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so nudge it onto a usable state
+        Rng { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    /// internal state, from_seed with this value recreates the generator exactly
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// xorshift64*, the top byte is the best distributed
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+impl Default for Rng {
+    /// seeded from the clock where there is one
+    fn default() -> Self {
+        Rng::from_seed(clock_seed())
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+fn clock_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// without a clock hosts are expected to pass their own seed
+#[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+fn clock_seed() -> u64 {
+    0
+}
@@ -0,0 +1,181 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Save states, a fixed size snapshot of everything that changes while a rom runs.
+//!
+//! Configuration such as quirks and tickrate isn't part of the state, it belongs to the host.
+
+use crate::emu::Emulator;
+use crate::error::Error;
+use crate::rng::Rng;
+
+const MAGIC: &[u8; 4] = b"R8ST";
+const VERSION: u8 = 1;
+
+/// Size in bytes of every save state
+pub const STATE_SIZE: usize = 4 + 1 // magic and version
+    + 16 + 4096 // registers and memory
+    + 2 + 2 + 1 + 16 * 2 // index, pc, sp and stack
+    + 1 + 1 + 2 // delay, sound and instruction
+    + 64 * 32 / 8 // vram, one bit per pixel
+    + 2 + 1 // keypad and pending keypress
+    + 8; // rng
+
+impl Emulator {
+    /// Write a save state into the first STATE_SIZE bytes of out
+    pub fn save_state(&self, out: &mut [u8]) -> Result<(), Error> {
+        if out.len() < STATE_SIZE {
+            return Err(Error::StateBufferTooSmall(out.len()));
+        }
+        let mut writer = Writer { out, pos: 0 };
+        writer.bytes(MAGIC);
+        writer.u8(VERSION);
+        writer.bytes(&self.registers);
+        writer.bytes(&self.memory);
+        writer.u16(self.index);
+        writer.u16(self.pc);
+        writer.u8(self.sp);
+        for &address in self.stack.iter() {
+            writer.u16(address);
+        }
+        writer.u8(self.delay);
+        writer.u8(self.sound);
+        writer.u16(self.instruction);
+        for pixels in self.display.vram.chunks(8) {
+            writer.u8(pixels.iter().fold(0, |byte, &pixel| byte << 1 | pixel as u8));
+        }
+        writer.u16(self.keyboard.keypad.iter().rev().fold(0, |bits, &down| bits << 1 | down as u16));
+        writer.u8(self.keyboard.pending.unwrap_or(NO_KEYPRESS));
+        writer.u64(self.rng.state());
+        Ok(())
+    }
+
+    /// Restore a save state written by save_state, the emulator is untouched if the state is invalid
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Error> {
+        if state.len() < STATE_SIZE {
+            return Err(Error::StateBufferTooSmall(state.len()));
+        }
+        let mut reader = Reader { state, pos: 0 };
+        if reader.bytes(4) != MAGIC || reader.u8() != VERSION {
+            return Err(Error::InvalidState);
+        }
+
+        let mut registers = [0; 16];
+        registers.copy_from_slice(reader.bytes(16));
+        let mut memory = [0; 4096];
+        memory.copy_from_slice(reader.bytes(4096));
+        let index = reader.u16();
+        let pc = reader.u16();
+        let sp = reader.u8();
+        let mut stack = [0; 16];
+        for address in stack.iter_mut() {
+            *address = reader.u16();
+        }
+        let delay = reader.u8();
+        let sound = reader.u8();
+        let instruction = reader.u16();
+        let mut vram = [false; 64 * 32];
+        for pixels in vram.chunks_mut(8) {
+            let byte = reader.u8();
+            for (bit, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = byte & (0x80 >> bit) != 0;
+            }
+        }
+        let keypad_bits = reader.u16();
+        let pending = reader.u8();
+        let rng = reader.u64();
+
+        if sp as usize >= stack.len() || pc > 0xFFF || (pending != NO_KEYPRESS && pending > 0xF) || rng == 0 {
+            return Err(Error::InvalidState);
+        }
+
+        self.registers = registers;
+        self.memory = memory;
+        self.code_writes.record(0, memory.len());
+        self.index = index;
+        self.pc = pc;
+        self.sp = sp;
+        self.stack = stack;
+        self.delay = delay;
+        self.sound = sound;
+        self.instruction = instruction;
+        self.display.vram = vram;
+        self.display.update_display();
+        for (key, down) in self.keyboard.keypad.iter_mut().enumerate() {
+            *down = keypad_bits & (1 << key) != 0;
+        }
+        self.keyboard.pending = if pending == NO_KEYPRESS { None } else { Some(pending) };
+        self.rng = Rng::from_seed(rng);
+        Ok(())
+    }
+}
+
+/// stored in place of the pending keypress when there isn't one
+const NO_KEYPRESS: u8 = 0xFF;
+
+/// little endian writer, callers check the buffer is at least STATE_SIZE
+struct Writer<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.out[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+/// little endian reader, callers check the buffer is at least STATE_SIZE
+struct Reader<'a> {
+    state: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.state[self.pos..self.pos + len];
+        self.pos += len;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2));
+        u16::from_le_bytes(bytes)
+    }
+
+    fn u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8));
+        u64::from_le_bytes(bytes)
+    }
+}
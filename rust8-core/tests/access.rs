@@ -0,0 +1,34 @@
+//! Every backend has to record the same accesses as the interpreter
+
+use rust8_core::access::Access;
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::emu::Emulator;
+use rust8_core::rng::Rng;
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+#[test]
+fn backends_record_the_same_accesses() {
+    let maps: Vec<_> = BackendKind::ALL.iter().map(|&kind| {
+        let mut emu = Emulator::new();
+        emu.rng = Rng::from_seed(5);
+        emu.load_rom(MAZE).unwrap();
+        emu.access = Some(Box::default());
+        let mut backend = Backend::new(kind);
+        for _ in 0..120 {
+            backend.run_frame(&mut emu);
+        }
+        emu.access.take().unwrap()
+    }).collect();
+    for access in Access::ALL.iter() {
+        for map in &maps[1..] {
+            assert!(map.counts(*access)[..] == maps[0].counts(*access)[..], "{} counts differ", access.name());
+        }
+    }
+    for map in &maps[1..] {
+        assert!(map.sprites() == maps[0].sprites(), "sprites differ");
+    }
+    assert!(maps[0].sprites().iter().all(|&(address, height)| (address == 0x21E || address == 0x222) && height == 4));
+    assert!(maps[0].count(Access::Execute, 0x200) == 1);
+    assert!(maps[0].count(Access::Sprite, 0x21E) + maps[0].count(Access::Sprite, 0x222) > 0);
+}
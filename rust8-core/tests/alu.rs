@@ -0,0 +1,99 @@
+//! Every 8xyn instruction and 7xkk against a reference written in wider integers, for every value
+//! of Vx and Vy, under every quirk preset, including x or y being F
+
+use rust8_core::cpu::CPU;
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+
+/// what Vx and VF should become, VF None when the instruction leaves it alone
+type Reference = fn(vx: u8, vy: u8, quirks: &Quirks) -> (u8, Option<u8>);
+
+fn logic(value: u8, quirks: &Quirks) -> (u8, Option<u8>) {
+    (value, if quirks.logic { Some(0) } else { None })
+}
+
+fn shift_source(vx: u8, vy: u8, quirks: &Quirks) -> u16 {
+    u16::from(if quirks.shift { vx } else { vy })
+}
+
+struct Case {
+    name: &'static str,
+    opcode: u16,
+    execute: fn(&mut Emulator),
+    reference: Reference,
+}
+
+const TABLE: &[Case] = &[
+    Case { name: "LD", opcode: 0x8000, execute: Emulator::ld_vx_vy, reference: |_, vy, _| (vy, None) },
+    Case { name: "OR", opcode: 0x8001, execute: Emulator::or_vx_vy, reference: |vx, vy, quirks| logic(vx | vy, quirks) },
+    Case { name: "AND", opcode: 0x8002, execute: Emulator::and_vx_vy, reference: |vx, vy, quirks| logic(vx & vy, quirks) },
+    Case { name: "XOR", opcode: 0x8003, execute: Emulator::xor_vx_vy, reference: |vx, vy, quirks| logic(vx ^ vy, quirks) },
+    Case { name: "ADD", opcode: 0x8004, execute: Emulator::add_vx_vy, reference: |vx, vy, _| {
+        let sum = u16::from(vx) + u16::from(vy);
+        ((sum % 256) as u8, Some((sum > 255) as u8))
+    } },
+    Case { name: "SUB", opcode: 0x8005, execute: Emulator::sub_vx_vy, reference: |vx, vy, _| {
+        let difference = i16::from(vx) - i16::from(vy);
+        (difference.rem_euclid(256) as u8, Some((difference >= 0) as u8))
+    } },
+    Case { name: "SHR", opcode: 0x8006, execute: Emulator::shr_vx_vy, reference: |vx, vy, quirks| {
+        let source = shift_source(vx, vy, quirks);
+        ((source / 2) as u8, Some((source % 2) as u8))
+    } },
+    Case { name: "SUBN", opcode: 0x8007, execute: Emulator::subn_vx_vy, reference: |vx, vy, _| {
+        let difference = i16::from(vy) - i16::from(vx);
+        (difference.rem_euclid(256) as u8, Some((difference >= 0) as u8))
+    } },
+    Case { name: "SHL", opcode: 0x800E, execute: Emulator::shl_vx_vy, reference: |vx, vy, quirks| {
+        let shifted = shift_source(vx, vy, quirks) * 2;
+        ((shifted % 256) as u8, Some((shifted > 255) as u8))
+    } },
+    // the byte added is Vy's value, 7xkk only ever uses x
+    Case { name: "ADD byte", opcode: 0x7000, execute: Emulator::add_vx_byte, reference: |vx, vy, _| ((u16::from(vx) + u16::from(vy)) as u8, None) },
+];
+
+/// Vx then Vy register numbers
+const REGISTERS: [(u16, u16); 4] = [(0x1, 0x2), (0xF, 0x2), (0x2, 0xF), (0x3, 0x3)];
+
+/// what every other register holds beforehand, so an untouched VF can be told apart
+const UNTOUCHED: u8 = 0xA5;
+
+#[test]
+fn alu_matches_reference() {
+    let mut emu = Emulator::new();
+    for (preset, quirks) in &Quirks::PRESETS {
+        emu.quirks = *quirks;
+        for &Case { name, opcode, execute, reference } in TABLE {
+            for &(x, y) in &REGISTERS {
+                for vx in 0..=255u8 {
+                    for vy in 0..=255u8 {
+                        if x == y && vx != vy {
+                            continue;
+                        }
+                        emu.registers = [UNTOUCHED; 16];
+                        emu.registers[y as usize] = vy;
+                        emu.registers[x as usize] = vx;
+                        emu.instruction = if opcode == 0x7000 { opcode | x << 8 | u16::from(vy) } else { opcode | x << 8 | y << 4 };
+                        emu.pc = 0x200;
+                        execute(&mut emu);
+
+                        let (value, flag) = reference(vx, vy, quirks);
+                        let expected_vf = match (x, flag) {
+                            (0xF, Some(flag)) => flag,
+                            (0xF, None) => value,
+                            (_, Some(flag)) => flag,
+                            (_, None) if y == 0xF => vy,
+                            (_, None) => UNTOUCHED,
+                        };
+                        let context = || format!("{} V{:X}={:#04x} V{:X}={:#04x} with {}", name, x, vx, y, vy, preset);
+                        if x != 0xF {
+                            assert_eq!(emu.registers[x as usize], value, "Vx after {}", context());
+                        }
+                        assert_eq!(emu.registers[0xF], expected_vf, "VF after {}", context());
+                        assert_eq!(emu.pc, 0x202, "PC after {}", context());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use rust8_core::clock::{FrameClock, Speed};
+
+/// a host refreshing at 60hz
+const REFRESH: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+fn frames_over(clock: &mut FrameClock, updates: u32) -> Vec<u32> {
+    (0..updates).map(|_| clock.frames(REFRESH)).collect()
+}
+
+#[test]
+fn speeds() {
+    let speeds: Vec<String> = (0..7).scan(Speed::SLOWEST, |speed, _| {
+        let name = speed.to_string();
+        *speed = speed.faster();
+        Some(name)
+    }).collect();
+    assert_eq!(speeds, ["0.25x", "0.5x", "1x", "2x", "4x", "8x", "8x"]);
+    assert_eq!(Speed::NORMAL.slower().slower().slower(), Speed::SLOWEST);
+    assert_eq!(Speed::from_quarters(3).unwrap().to_string(), "0.75x");
+    assert_eq!(Speed::from_quarters(6).unwrap().to_string(), "1.5x");
+    assert!(Speed::from_quarters(0).is_none());
+    assert!(Speed::from_quarters(33).is_none());
+}
+
+#[test]
+fn frames_follow_the_speed() {
+    let mut clock = FrameClock::default();
+    assert_eq!(frames_over(&mut clock, 3), [1, 1, 1]);
+    clock.speed = Speed::NORMAL.faster();
+    assert_eq!(frames_over(&mut clock, 3), [2, 2, 2]);
+    clock.speed = Speed::SLOWEST;
+    assert_eq!(frames_over(&mut clock, 8), [0, 0, 0, 1, 0, 0, 0, 1]);
+}
+
+#[test]
+fn partial_frames_carry_over() {
+    let mut clock = FrameClock::new(Speed::NORMAL);
+    // a 144hz host still gets 60 frames a second
+    let frames: u32 = (0..144).map(|_| clock.frames(Duration::from_secs(1) / 144)).sum();
+    assert!((59..=60).contains(&frames), "{}", frames);
+}
+
+#[test]
+fn stalls_are_not_caught_up() {
+    let mut clock = FrameClock::new(Speed::NORMAL);
+    assert_eq!(clock.frames(Duration::from_secs(5)), 4);
+    clock.speed = Speed::FASTEST;
+    assert_eq!(clock.frames(Duration::from_secs(5)), 32);
+    clock.speed = Speed::SLOWEST;
+    assert_eq!(clock.frames(Duration::from_secs(5)), 1);
+    assert_eq!(clock.frames(REFRESH / 2), 0);
+    clock.reset();
+    assert_eq!(frames_over(&mut clock, 4), [0, 0, 0, 1]);
+}
@@ -0,0 +1,40 @@
+//! Fixtures and helpers shared between the integration tests, each test crate uses some of them
+
+#![allow(dead_code)]
+
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+use rust8_core::state::STATE_SIZE;
+
+pub const MAZE: &[u8] = include_bytes!("../../../roms/maze.ch8");
+
+/// runs ADD V2, 1 and then overwrites it with ADD V2, 5 through Fx55, twice around the loop
+pub const SELF_MODIFYING: [u8; 20] = [
+    0x60, 0x72, // LD V0, 0x72
+    0x61, 0x05, // LD V1, 0x05
+    0xA2, 0x08, // LD I, 0x208
+    0x73, 0x01, // ADD V3, 1
+    0x72, 0x01, // ADD V2, 1
+    0xF1, 0x55, // LD [I], V1
+    0x33, 0x02, // SE V3, 2
+    0x12, 0x04, // JP 0x204
+    0x12, 0x10, // JP 0x210
+    0x00, 0x00,
+];
+
+/// an emulator with rom loaded, the quirks given and its random numbers seeded
+pub fn emulator(rom: &[u8], quirks: Quirks) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::from_seed(7);
+    emu.quirks = quirks;
+    emu.load_rom(rom).unwrap();
+    emu
+}
+
+/// everything that changes while a rom runs, as a save state
+pub fn snapshot(emu: &Emulator) -> Vec<u8> {
+    let mut state = vec![0; STATE_SIZE];
+    emu.save_state(&mut state).unwrap();
+    state
+}
@@ -0,0 +1,366 @@
+//! Every CPU method pinned down by a table of (state before, instruction, state after), run under
+//! every quirk preset, through the method itself and through tick on every backend
+
+use std::ptr;
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::constants::FONTSET_START;
+use rust8_core::cpu::{mnemonic, CPU};
+use rust8_core::decode::{decode, Execute};
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+mod common;
+
+use common::snapshot;
+
+struct Case {
+    instruction: u16,
+    execute: fn(&mut Emulator),
+    /// set up the state the instruction runs in, pc is always 0x200
+    before: fn(&mut Emulator),
+    /// turn a copy of the state before into the state expected after
+    after: fn(&mut Emulator, &Quirks),
+}
+
+fn nothing(_: &mut Emulator) {}
+
+fn next(emu: &mut Emulator, _: &Quirks) {
+    emu.pc = 0x202;
+}
+
+fn skip(emu: &mut Emulator, _: &Quirks) {
+    emu.pc = 0x204;
+}
+
+fn pixel(emu: &mut Emulator, x: usize, y: usize) -> &mut bool {
+    &mut emu.display.vram[y * 64 + x]
+}
+
+/// I after Fx55 or Fx65 moved registers 0 to x
+fn index_after_transfer(index: u16, x: u16, quirks: &Quirks) -> u16 {
+    match (quirks.memory_leave_i_unchanged, quirks.memory_increment_by_x) {
+        (true, _) => index,
+        (false, true) => index + x,
+        (false, false) => index + x + 1,
+    }
+}
+
+const TABLE: &[Case] = &[
+    // 0x00E0
+    Case { instruction: 0x00E0, execute: Emulator::cls,
+        before: |emu| { *pixel(emu, 3, 4) = true; *pixel(emu, 63, 31) = true; },
+        after: |emu, q| { emu.display.vram = [false; 64 * 32]; next(emu, q) } },
+    // 0x00EE
+    Case { instruction: 0x00EE, execute: Emulator::ret,
+        before: |emu| { emu.stack[0] = 0x300; emu.stack[1] = 0x402; emu.sp = 2; },
+        after: |emu, _| { emu.sp = 1; emu.pc = 0x402; } },
+    // 0x1nnn
+    Case { instruction: 0x1345, execute: Emulator::jp_addr,
+        before: nothing,
+        after: |emu, _| emu.pc = 0x345 },
+    // 0x2nnn
+    Case { instruction: 0x2345, execute: Emulator::call_addr,
+        before: |emu| { emu.stack[0] = 0x300; emu.sp = 1; },
+        after: |emu, _| { emu.stack[1] = 0x202; emu.sp = 2; emu.pc = 0x345; } },
+    // 0x3xkk
+    Case { instruction: 0x3342, execute: Emulator::se_vx_byte,
+        before: |emu| emu.registers[3] = 0x42,
+        after: skip },
+    Case { instruction: 0x3342, execute: Emulator::se_vx_byte,
+        before: |emu| emu.registers[3] = 0x43,
+        after: next },
+    // 0x4xkk
+    Case { instruction: 0x4342, execute: Emulator::sne_vx_byte,
+        before: |emu| emu.registers[3] = 0x42,
+        after: next },
+    Case { instruction: 0x4342, execute: Emulator::sne_vx_byte,
+        before: |emu| emu.registers[3] = 0x43,
+        after: skip },
+    // 0x5xy0
+    Case { instruction: 0x5340, execute: Emulator::se_vx_vy,
+        before: |emu| { emu.registers[3] = 9; emu.registers[4] = 9; },
+        after: skip },
+    Case { instruction: 0x5340, execute: Emulator::se_vx_vy,
+        before: |emu| { emu.registers[3] = 9; emu.registers[4] = 8; },
+        after: next },
+    // 0x6xkk
+    Case { instruction: 0x6A7F, execute: Emulator::ld_vx_byte,
+        before: |emu| emu.registers[0xA] = 1,
+        after: |emu, q| { emu.registers[0xA] = 0x7F; next(emu, q) } },
+    // 0x7xkk wraps and leaves VF alone
+    Case { instruction: 0x7302, execute: Emulator::add_vx_byte,
+        before: |emu| { emu.registers[3] = 0xFF; emu.registers[0xF] = 7; },
+        after: |emu, q| { emu.registers[3] = 1; next(emu, q) } },
+    // 0x8xy0
+    Case { instruction: 0x8340, execute: Emulator::ld_vx_vy,
+        before: |emu| { emu.registers[3] = 1; emu.registers[4] = 2; },
+        after: |emu, q| { emu.registers[3] = 2; next(emu, q) } },
+    // 0x8xy1 to 0x8xy3 clear VF with the logic quirk
+    Case { instruction: 0x8341, execute: Emulator::or_vx_vy,
+        before: |emu| { emu.registers[3] = 0b1100; emu.registers[4] = 0b1010; emu.registers[0xF] = 1; },
+        after: |emu, q| { emu.registers[3] = 0b1110; emu.registers[0xF] = !q.logic as u8; next(emu, q) } },
+    Case { instruction: 0x8342, execute: Emulator::and_vx_vy,
+        before: |emu| { emu.registers[3] = 0b1100; emu.registers[4] = 0b1010; emu.registers[0xF] = 1; },
+        after: |emu, q| { emu.registers[3] = 0b1000; emu.registers[0xF] = !q.logic as u8; next(emu, q) } },
+    Case { instruction: 0x8343, execute: Emulator::xor_vx_vy,
+        before: |emu| { emu.registers[3] = 0b1100; emu.registers[4] = 0b1010; emu.registers[0xF] = 1; },
+        after: |emu, q| { emu.registers[3] = 0b0110; emu.registers[0xF] = !q.logic as u8; next(emu, q) } },
+    // 0x8xy4
+    Case { instruction: 0x8344, execute: Emulator::add_vx_vy,
+        before: |emu| { emu.registers[3] = 0xF0; emu.registers[4] = 0x20; },
+        after: |emu, q| { emu.registers[3] = 0x10; emu.registers[0xF] = 1; next(emu, q) } },
+    Case { instruction: 0x8F44, execute: Emulator::add_vx_vy,
+        before: |emu| { emu.registers[0xF] = 0x10; emu.registers[4] = 0x20; },
+        after: |emu, q| { emu.registers[0xF] = 0; next(emu, q) } },
+    // 0x8xy5
+    Case { instruction: 0x8345, execute: Emulator::sub_vx_vy,
+        before: |emu| { emu.registers[3] = 0x20; emu.registers[4] = 0x20; },
+        after: |emu, q| { emu.registers[3] = 0; emu.registers[0xF] = 1; next(emu, q) } },
+    Case { instruction: 0x8345, execute: Emulator::sub_vx_vy,
+        before: |emu| { emu.registers[3] = 0x10; emu.registers[4] = 0x20; emu.registers[0xF] = 1; },
+        after: |emu, q| { emu.registers[3] = 0xF0; emu.registers[0xF] = 0; next(emu, q) } },
+    // 0x8xy6 shifts Vy, or Vx in place with the shift quirk
+    Case { instruction: 0x8346, execute: Emulator::shr_vx_vy,
+        before: |emu| { emu.registers[3] = 0b1000; emu.registers[4] = 0b0011; },
+        after: |emu, q| {
+            emu.registers[3] = if q.shift { 0b0100 } else { 0b0001 };
+            emu.registers[0xF] = !q.shift as u8;
+            next(emu, q)
+        } },
+    // 0x8xy7
+    Case { instruction: 0x8347, execute: Emulator::subn_vx_vy,
+        before: |emu| { emu.registers[3] = 0x10; emu.registers[4] = 0x30; },
+        after: |emu, q| { emu.registers[3] = 0x20; emu.registers[0xF] = 1; next(emu, q) } },
+    Case { instruction: 0x8347, execute: Emulator::subn_vx_vy,
+        before: |emu| { emu.registers[3] = 0x30; emu.registers[4] = 0x10; emu.registers[0xF] = 1; },
+        after: |emu, q| { emu.registers[3] = 0xE0; emu.registers[0xF] = 0; next(emu, q) } },
+    // 0x8xyE shifts Vy, or Vx in place with the shift quirk
+    Case { instruction: 0x834E, execute: Emulator::shl_vx_vy,
+        before: |emu| { emu.registers[3] = 0b0100_0001; emu.registers[4] = 0b1000_0011; },
+        after: |emu, q| {
+            emu.registers[3] = if q.shift { 0b1000_0010 } else { 0b0000_0110 };
+            emu.registers[0xF] = !q.shift as u8;
+            next(emu, q)
+        } },
+    // 0x9xy0
+    Case { instruction: 0x9340, execute: Emulator::sne_vx_vy,
+        before: |emu| { emu.registers[3] = 9; emu.registers[4] = 8; },
+        after: skip },
+    Case { instruction: 0x9340, execute: Emulator::sne_vx_vy,
+        before: |emu| { emu.registers[3] = 9; emu.registers[4] = 9; },
+        after: next },
+    // 0xAnnn
+    Case { instruction: 0xA123, execute: Emulator::ld_i_addr,
+        before: nothing,
+        after: |emu, q| { emu.index = 0x123; next(emu, q) } },
+    // 0xBnnn adds V0, or Vx of Bxnn with the jump quirk
+    Case { instruction: 0xB345, execute: Emulator::jp_v0_addr,
+        before: |emu| { emu.registers[0] = 0x10; emu.registers[3] = 0x20; },
+        after: |emu, q| emu.pc = if q.jump { 0x365 } else { 0x355 } },
+    // 0xCxkk
+    Case { instruction: 0xC30F, execute: Emulator::rnd_vx_byte,
+        before: nothing,
+        after: |emu, q| { emu.registers[3] = emu.rng.next_u8() & 0x0F; next(emu, q) } },
+    // 0xDxyn XORs a sprite in and sets VF on collision
+    Case { instruction: 0xD342, execute: Emulator::drw_vx_vy_nibble,
+        before: |emu| {
+            emu.registers[3] = 2;
+            emu.registers[4] = 5;
+            emu.index = 0x300;
+            emu.memory[0x300] = 0b1010_0000;
+            emu.memory[0x301] = 0b0100_0000;
+            *pixel(emu, 4, 5) = true;
+        },
+        after: |emu, q| {
+            *pixel(emu, 2, 5) = true;
+            *pixel(emu, 4, 5) = false;
+            *pixel(emu, 3, 6) = true;
+            emu.registers[0xF] = 1;
+            next(emu, q)
+        } },
+    // sprites at the edges clip, or wrap with the wrap quirk
+    Case { instruction: 0xD342, execute: Emulator::drw_vx_vy_nibble,
+        before: |emu| {
+            emu.registers[3] = 63 + 64;
+            emu.registers[4] = 31;
+            emu.registers[0xF] = 1;
+            emu.index = 0x300;
+            emu.memory[0x300] = 0b1100_0000;
+            emu.memory[0x301] = 0b1000_0000;
+        },
+        after: |emu, q| {
+            *pixel(emu, 63, 31) = true;
+            if q.wrap {
+                *pixel(emu, 0, 31) = true;
+                *pixel(emu, 63, 0) = true;
+            }
+            emu.registers[0xF] = 0;
+            next(emu, q)
+        } },
+    // 0xEx9E
+    Case { instruction: 0xE39E, execute: Emulator::skp_vx,
+        before: |emu| { emu.registers[3] = 0xA; emu.keyboard.set_keypad(1 << 0xA); },
+        after: skip },
+    Case { instruction: 0xE39E, execute: Emulator::skp_vx,
+        before: |emu| { emu.registers[3] = 0xA; emu.keyboard.set_keypad(1 << 3); },
+        after: next },
+    // 0xExA1
+    Case { instruction: 0xE3A1, execute: Emulator::sknp_vx,
+        before: |emu| { emu.registers[3] = 0xA; emu.keyboard.set_keypad(1 << 0xA); },
+        after: next },
+    Case { instruction: 0xE3A1, execute: Emulator::sknp_vx,
+        before: |emu| { emu.registers[3] = 0xA; emu.keyboard.set_keypad(1 << 3); },
+        after: skip },
+    // 0xFx07
+    Case { instruction: 0xF307, execute: Emulator::ld_vx_dt,
+        before: |emu| emu.delay = 0x33,
+        after: |emu, q| { emu.registers[3] = 0x33; next(emu, q) } },
+    // 0xFx0A waits in place until a key is pressed
+    Case { instruction: 0xF30A, execute: Emulator::ld_vx_k,
+        before: nothing,
+        after: |_, _| {} },
+    Case { instruction: 0xF30A, execute: Emulator::ld_vx_k,
+        before: |emu| emu.keyboard.set_keypad(1 << 7),
+        after: |emu, q| { let _ = emu.keyboard.take_keypress(); emu.registers[3] = 7; next(emu, q) } },
+    // 0xFx15
+    Case { instruction: 0xF315, execute: Emulator::ld_dt_vx,
+        before: |emu| emu.registers[3] = 0x44,
+        after: |emu, q| { emu.delay = 0x44; next(emu, q) } },
+    // 0xFx18
+    Case { instruction: 0xF318, execute: Emulator::ld_st_vx,
+        before: |emu| emu.registers[3] = 0x55,
+        after: |emu, q| { emu.sound = 0x55; next(emu, q) } },
+    // 0xFx1E
+    Case { instruction: 0xF31E, execute: Emulator::add_i_vx,
+        before: |emu| { emu.index = 0x300; emu.registers[3] = 0x21; },
+        after: |emu, q| { emu.index = 0x321; next(emu, q) } },
+    // 0xFx29 only looks at the low nibble
+    Case { instruction: 0xF329, execute: Emulator::ld_f_vx,
+        before: |emu| emu.registers[3] = 0x1A,
+        after: |emu, q| { emu.index = FONTSET_START + 0xA * 5; next(emu, q) } },
+    // 0xFx33
+    Case { instruction: 0xF333, execute: Emulator::ld_b_vx,
+        before: |emu| { emu.index = 0x300; emu.registers[3] = 234; },
+        after: |emu, q| { emu.memory[0x300..0x303].copy_from_slice(&[2, 3, 4]); next(emu, q) } },
+    // 0xFx55 moves I on depending on the memory quirks
+    Case { instruction: 0xF255, execute: Emulator::ld_mem_vx,
+        before: |emu| { emu.index = 0x300; emu.registers[..4].copy_from_slice(&[1, 2, 3, 4]); },
+        after: |emu, q| {
+            emu.memory[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+            emu.index = index_after_transfer(0x300, 2, q);
+            next(emu, q)
+        } },
+    // 0xFx65 moves I on depending on the memory quirks
+    Case { instruction: 0xF265, execute: Emulator::ld_vx_mem,
+        before: |emu| { emu.index = 0x300; emu.memory[0x300..0x304].copy_from_slice(&[1, 2, 3, 4]); },
+        after: |emu, q| {
+            emu.registers[..3].copy_from_slice(&[1, 2, 3]);
+            emu.index = index_after_transfer(0x300, 2, q);
+            next(emu, q)
+        } },
+];
+
+/// every method of the CPU trait
+const METHODS: [(&str, Execute); 34] = [
+    ("cls", Emulator::cls), ("ret", Emulator::ret), ("jp_addr", Emulator::jp_addr), ("call_addr", Emulator::call_addr),
+    ("se_vx_byte", Emulator::se_vx_byte), ("sne_vx_byte", Emulator::sne_vx_byte), ("se_vx_vy", Emulator::se_vx_vy),
+    ("ld_vx_byte", Emulator::ld_vx_byte), ("add_vx_byte", Emulator::add_vx_byte), ("ld_vx_vy", Emulator::ld_vx_vy),
+    ("or_vx_vy", Emulator::or_vx_vy), ("and_vx_vy", Emulator::and_vx_vy), ("xor_vx_vy", Emulator::xor_vx_vy),
+    ("add_vx_vy", Emulator::add_vx_vy), ("sub_vx_vy", Emulator::sub_vx_vy), ("shr_vx_vy", Emulator::shr_vx_vy),
+    ("subn_vx_vy", Emulator::subn_vx_vy), ("shl_vx_vy", Emulator::shl_vx_vy), ("sne_vx_vy", Emulator::sne_vx_vy),
+    ("ld_i_addr", Emulator::ld_i_addr), ("jp_v0_addr", Emulator::jp_v0_addr), ("rnd_vx_byte", Emulator::rnd_vx_byte),
+    ("drw_vx_vy_nibble", Emulator::drw_vx_vy_nibble), ("skp_vx", Emulator::skp_vx), ("sknp_vx", Emulator::sknp_vx),
+    ("ld_vx_dt", Emulator::ld_vx_dt), ("ld_vx_k", Emulator::ld_vx_k), ("ld_dt_vx", Emulator::ld_dt_vx),
+    ("ld_st_vx", Emulator::ld_st_vx), ("add_i_vx", Emulator::add_i_vx), ("ld_f_vx", Emulator::ld_f_vx),
+    ("ld_b_vx", Emulator::ld_b_vx), ("ld_mem_vx", Emulator::ld_mem_vx), ("ld_vx_mem", Emulator::ld_vx_mem),
+];
+
+/// the name of a CPU method, for messages
+fn method_name(execute: Execute) -> &'static str {
+    METHODS.iter().find(|&&(_, method)| ptr::fn_addr_eq(method, execute)).map_or("not a CPU method", |&(name, _)| name)
+}
+
+fn emulator(case: &Case, quirks: Quirks) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.quirks = quirks;
+    emu.rng = Rng::from_seed(11);
+    emu.memory[0x200..0x202].copy_from_slice(&case.instruction.to_be_bytes());
+    (case.before)(&mut emu);
+    emu
+}
+
+fn assert_state(actual: &Emulator, expected: &Emulator, context: &str) {
+    assert_eq!(actual.registers, expected.registers, "registers after {}", context);
+    assert_eq!(actual.index, expected.index, "I after {}", context);
+    assert_eq!(actual.pc, expected.pc, "PC after {}", context);
+    assert_eq!((actual.sp, actual.stack), (expected.sp, expected.stack), "stack after {}", context);
+    assert_eq!((actual.delay, actual.sound), (expected.delay, expected.sound), "timers after {}", context);
+    assert!(actual.memory[..] == expected.memory[..], "memory after {}", context);
+    assert!(actual.display.vram[..] == expected.display.vram[..], "display after {}", context);
+    assert!(snapshot(actual) == snapshot(expected), "state after {}", context);
+}
+
+#[test]
+fn every_method_has_a_case() {
+    for &(name, method) in METHODS.iter() {
+        assert!(TABLE.iter().any(|case| ptr::fn_addr_eq(case.execute, method)), "no case for {}", name);
+    }
+    // a case's method has to be the one its instruction runs
+    for case in TABLE {
+        let decoded = decode(case.instruction);
+        assert!(ptr::fn_addr_eq(decoded, case.execute), "{:04X} runs {} but its case calls {}",
+            case.instruction, method_name(decoded), method_name(case.execute));
+    }
+}
+
+#[test]
+fn opcodes_conform() {
+    for (preset, quirks) in Quirks::PRESETS.iter() {
+        for case in TABLE {
+            let mut expected = emulator(case, *quirks);
+            (case.after)(&mut expected, quirks);
+            expected.instruction = case.instruction;
+
+            let mut actual = emulator(case, *quirks);
+            actual.instruction = case.instruction;
+            (case.execute)(&mut actual);
+            let context = format!("{} ({:04X} {}) with {}", method_name(case.execute), case.instruction, mnemonic(case.instruction), preset);
+            assert_state(&actual, &expected, &context);
+
+            for &kind in BackendKind::ALL.iter() {
+                let mut actual = emulator(case, *quirks);
+                Backend::new(kind).tick(&mut actual);
+                assert_state(&actual, &expected, &format!("{} on {}", context, kind));
+            }
+        }
+    }
+}
+
+#[test]
+fn subroutines_return_after_their_call() {
+    let rom = [
+        0x22, 0x06, // CALL 0x206
+        0x61, 0x01, // LD V1, 1
+        0x12, 0x04, // JP 0x204
+        0x60, 0x01, // LD V0, 1
+        0x00, 0xEE, // RET
+    ];
+    for &kind in BackendKind::ALL.iter() {
+        let mut emu = Emulator::new();
+        emu.load_rom(&rom).unwrap();
+        let mut backend = Backend::new(kind);
+        backend.run_frame(&mut emu);
+        assert_eq!((emu.registers[0], emu.registers[1], emu.sp, emu.pc), (1, 1, 0, 0x204), "{}", kind);
+    }
+}
+
+#[test]
+fn mnemonics_name_every_instruction() {
+    assert_eq!(mnemonic(0x834E).to_string(), "SHL 0x3, 0x4");
+    assert_eq!(mnemonic(0x8348).to_string(), "INVALID");
+    for case in TABLE {
+        assert_ne!(mnemonic(case.instruction).to_string(), "INVALID", "{}", method_name(case.execute));
+    }
+}
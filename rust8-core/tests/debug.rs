@@ -0,0 +1,242 @@
+//! A debug session has to run frames exactly like the backends do, however they're stepped through
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::debug::{RecordError, Session};
+use rust8_core::emu::Emulator;
+use rust8_core::expr::Expr;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+mod common;
+
+use common::{emulator, snapshot, MAZE};
+
+fn maze(quirks: Quirks) -> Emulator {
+    emulator(MAZE, quirks)
+}
+
+#[test]
+fn stepping_matches_running_frames() {
+    for &quirks in [Quirks::CHIP8, Quirks::MODERN].iter() {
+        for &kind in BackendKind::ALL.iter() {
+            let mut expected = maze(quirks);
+            let mut backend = Backend::new(kind);
+            let mut emu = maze(quirks);
+            let mut session = Session::new(Backend::new(kind));
+            for _ in 0..30 {
+                backend.run_frame(&mut expected);
+                while !session.step(&mut emu) {}
+                assert!(snapshot(&emu) == snapshot(&expected), "{:?} {:?}", kind, quirks);
+            }
+        }
+    }
+}
+
+#[test]
+fn breakpoints_stop_mid_frame_and_resume() {
+    let mut expected = maze(Quirks::MODERN);
+    for _ in 0..20 {
+        expected.run_frame();
+    }
+
+    let mut emu = maze(Quirks::MODERN);
+    let mut session = Session::new(Backend::default());
+    // every draw
+    session.breakpoints.push(Expr::parse("PC == 0x20C").unwrap());
+    let mut frames = 0;
+    let mut hits = 0;
+    while frames < 20 {
+        match session.run_frame(&mut emu) {
+            Some(hit) => {
+                assert_eq!(hit, 0);
+                assert_eq!(emu.pc, 0x20C);
+                // a hit on a frame's last instruction finishes the frame anyway
+                if session.mid_frame() {
+                    hits += 1;
+                } else {
+                    frames += 1;
+                }
+            }
+            None => frames += 1,
+        }
+    }
+    assert!(hits > 0);
+    assert!(snapshot(&emu) == snapshot(&expected));
+}
+
+#[test]
+fn no_breakpoints_runs_whole_frames() {
+    let mut emu = maze(Quirks::MODERN);
+    let mut session = Session::new(Backend::new(BackendKind::Jit));
+    assert_eq!(session.run_frame(&mut emu), None);
+    assert!(!session.mid_frame());
+    session.breakpoints.push(Expr::parse("[0x3FF] == 1").unwrap());
+    assert_eq!(session.run_frame(&mut emu), None);
+}
+
+/// waits for a key into V0, counts presses in V1 and loops
+const KEYS: &[u8] = &[0xF0, 0x0A, 0x71, 0x01, 0x12, 0x00];
+
+fn keys() -> Emulator {
+    let mut emu = Emulator::new();
+    emu.rng = Rng::from_seed(7);
+    emu.load_rom(KEYS).unwrap();
+    emu
+}
+
+#[test]
+fn step_back_retraces_steps_with_input() {
+    for &kind in BackendKind::ALL.iter() {
+        let mut emu = keys();
+        let mut session = Session::new(Backend::new(kind));
+        session.record(&emu).unwrap();
+        // past a few snapshots, pressing a different key now and then, with each state taken as
+        // the next instruction sees it
+        let mut states = Vec::new();
+        for step in 0..2500u32 {
+            if step % 97 == 0 {
+                emu.keyboard.press_key((step / 97 % 16) as u8);
+            } else if step % 97 == 40 {
+                emu.keyboard.release_key((step / 97 % 16) as u8);
+            }
+            states.push(snapshot(&emu));
+            let _ = session.step(&mut emu);
+        }
+        assert!(emu.registers[1] > 10);
+
+        while session.step_back(&mut emu) {
+            let position = session.position().unwrap() as usize;
+            assert!(snapshot(&emu) == states[position], "{:?} {}", kind, position);
+        }
+        assert_eq!(session.position(), Some(0));
+    }
+}
+
+#[test]
+fn reverse_continue_goes_back_through_breakpoint_hits() {
+    let mut emu = maze(Quirks::MODERN);
+    let mut session = Session::new(Backend::default());
+    session.record(&emu).unwrap();
+    session.breakpoints.push(Expr::parse("PC == 0x20C").unwrap());
+    let mut hits = Vec::new();
+    let mut states = Vec::new();
+    for _ in 0..40 {
+        if session.run_frame(&mut emu).is_some() {
+            hits.push(session.position().unwrap());
+            states.push(snapshot(&emu));
+        }
+    }
+    assert!(hits.len() > 5);
+
+    while let Some(expected) = hits.pop() {
+        let expected_state = states.pop().unwrap();
+        if session.position() == Some(expected) {
+            continue;
+        }
+        assert_eq!(session.reverse_continue(&mut emu), Some(0));
+        assert_eq!(session.position(), Some(expected));
+        assert!(snapshot(&emu) == expected_state);
+    }
+    assert_eq!(session.reverse_continue(&mut emu), None);
+    assert_eq!(session.position(), Some(0));
+}
+
+#[test]
+fn reverse_to_last_write_stops_before_the_write() {
+    let mut emu = maze(Quirks::MODERN);
+    let mut session = Session::new(Backend::default());
+    session.record(&emu).unwrap();
+    let watch = Expr::parse("V0").unwrap();
+    // maze only writes V0 with ADD V0, 4 and LD V0, 0
+    let writes_v0 = |instruction| instruction == 0x7004 || instruction == 0x6000;
+    let mut executed = vec![0];
+    while executed.len() < 3000 {
+        let _ = session.step(&mut emu);
+        executed.push(emu.instruction);
+    }
+
+    let mut position = executed.len() - 1;
+    loop {
+        let expected = (1..=position).rev().find(|&p| writes_v0(executed[p]));
+        let found = session.reverse_to_last_write(&mut emu, &watch);
+        assert_eq!(found, expected.is_some());
+        position = session.position().unwrap() as usize;
+        assert_eq!(position, expected.map_or(0, |p| p - 1));
+        if !found {
+            break;
+        }
+        // the instruction at pc is the one that writes it
+        let _ = session.step(&mut emu);
+        assert!(writes_v0(emu.instruction));
+        assert!(session.step_back(&mut emu));
+        assert_eq!(session.position(), Some(position as u64));
+    }
+}
+
+#[test]
+fn rewriting_the_same_value_is_a_write() {
+    // LD V0, 5 three times, then spin
+    let mut emu = emulator(&[0x60, 0x05, 0x60, 0x05, 0x60, 0x05, 0x12, 0x06], Quirks::MODERN);
+    let mut session = Session::new(Backend::default());
+    session.record(&emu).unwrap();
+    for _ in 0..6 {
+        let _ = session.step(&mut emu);
+    }
+    let watch = Expr::parse("V0").unwrap();
+    for &(position, pc) in &[(2, 0x204), (1, 0x202), (0, 0x200)] {
+        assert!(session.reverse_to_last_write(&mut emu, &watch));
+        assert_eq!((session.position(), emu.pc), (Some(position), pc));
+    }
+    assert!(!session.reverse_to_last_write(&mut emu, &watch));
+    // only registers and memory are written
+    assert!(!session.reverse_to_last_write(&mut emu, &Expr::parse("V0 + 1").unwrap()));
+}
+
+#[test]
+fn memory_writes_are_found_without_an_access_map() {
+    // store V0, still 0, over the 0 at 0x300 twice and then spin
+    let mut emu = emulator(&[0xA3, 0x00, 0xF0, 0x55, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x08], Quirks::MODERN);
+    let mut session = Session::new(Backend::default());
+    session.record(&emu).unwrap();
+    for _ in 0..6 {
+        let _ = session.step(&mut emu);
+    }
+    let watch = Expr::parse("[0x300]").unwrap();
+    assert!(session.reverse_to_last_write(&mut emu, &watch));
+    assert_eq!((session.position(), emu.pc), (Some(3), 0x206));
+    assert!(session.reverse_to_last_write(&mut emu, &watch));
+    assert_eq!((session.position(), emu.pc), (Some(1), 0x202));
+    assert!(!session.reverse_to_last_write(&mut emu, &watch));
+    assert!(emu.access.is_none());
+}
+
+#[test]
+fn recording_needs_determinism() {
+    let mut emu = keys();
+    let mut session = Session::new(Backend::default());
+    emu.host.random = Some(|| 4);
+    assert_eq!(session.record(&emu), Err(RecordError::HostRandom));
+    emu.host.random = None;
+    emu.host.input = Some(|| 0);
+    assert_eq!(session.record(&emu), Err(RecordError::HostInput));
+    assert!(!session.step_back(&mut emu));
+}
+
+#[test]
+fn recording_stops_once_the_host_feeds_the_emulator() {
+    for &host_random in &[true, false] {
+        let mut emu = keys();
+        let mut session = Session::new(Backend::default());
+        session.record(&emu).unwrap();
+        let _ = session.step(&mut emu);
+        assert_eq!(session.position(), Some(1));
+        if host_random {
+            emu.host.random = Some(|| 4);
+        } else {
+            emu.host.input = Some(|| 0);
+        }
+        let _ = session.step(&mut emu);
+        assert_eq!(session.position(), None);
+        assert!(!session.step_back(&mut emu));
+    }
+}
@@ -0,0 +1,58 @@
+//! The decode cache has to behave exactly like the plain interpreter
+
+use rust8_core::decode::DecodeCache;
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+
+mod common;
+
+use common::{snapshot, MAZE, SELF_MODIFYING};
+
+fn emulator(rom: &[u8]) -> Emulator {
+    common::emulator(rom, Quirks::default())
+}
+
+fn assert_same(interpreted: &Emulator, cached: &Emulator) {
+    assert!(snapshot(interpreted) == snapshot(cached), "pc {:#x} and {:#x}", interpreted.pc, cached.pc);
+}
+
+#[test]
+fn matches_interpreter_on_maze() {
+    let mut interpreted = emulator(MAZE);
+    let mut cached = emulator(MAZE);
+    let mut cache = Box::new(DecodeCache::new());
+    for _ in 0..600 {
+        interpreted.run_frame();
+        cached.run_frame_cached(&mut cache);
+        assert_same(&interpreted, &cached);
+    }
+}
+
+#[test]
+fn sees_self_modifying_code() {
+    let mut interpreted = emulator(&SELF_MODIFYING);
+    let mut cached = emulator(&SELF_MODIFYING);
+    let mut cache = Box::new(DecodeCache::new());
+    for _ in 0..20 {
+        interpreted.tick();
+        cache.tick(&mut cached);
+        assert_same(&interpreted, &cached);
+    }
+    assert_eq!(cached.registers[2], 6);
+}
+
+#[test]
+fn loading_a_rom_invalidates() {
+    let mut emu = emulator(MAZE);
+    let mut cache = Box::new(DecodeCache::new());
+    emu.run_frame_cached(&mut cache);
+
+    emu.load_rom(&SELF_MODIFYING).unwrap();
+    emu.pc = 0x200;
+    emu.registers = [0; 16];
+    for _ in 0..20 {
+        cache.tick(&mut emu);
+    }
+    assert_eq!(emu.registers[2], 6);
+}
+
@@ -0,0 +1,73 @@
+use rust8_core::emu::Emulator;
+use rust8_core::expr::{Expr, ParseError};
+
+fn emulator() -> Emulator {
+    let mut emu = Emulator::new();
+    emu.registers[0x3] = 0x10;
+    emu.registers[0xF] = 1;
+    emu.index = 0x300;
+    emu.memory[0x300] = 0xAB;
+    emu.memory[0x005] = 0x77;
+    emu.pc = 0x2A4;
+    emu.sp = 2;
+    emu.delay = 0;
+    emu.sound = 9;
+    emu
+}
+
+#[test]
+fn evaluates() {
+    let emu = emulator();
+    let cases: &[(&str, u32)] = &[
+        ("V3 == 0x10 && [I] != 0", 1),
+        ("v3 == 16 && [i] == 0", 0),
+        ("DT == 0", 1),
+        ("sp > 10", 0),
+        ("PC", 0x2A4),
+        ("[I]", 0xAB),
+        ("[0x1005]", 0x77),
+        ("VF + ST * 2", 19),
+        ("(VF + ST) * 2", 20),
+        ("1 + 2 << 1", 6),
+        ("1 | 2 ^ 3 & 1", 3),
+        ("0b1010 >> 1", 5),
+        ("-1", u32::MAX),
+        ("~0 == -1", 1),
+        ("!V3 || !0", 1),
+        ("10 % 4 - 3", u32::MAX),
+        ("1 << 40", 0),
+        ("0 && 1 / 0", 0),
+    ];
+    for &(source, value) in cases {
+        let expr = Expr::parse(source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+        assert_eq!(expr.eval(&emu), Some(value), "{}", source);
+    }
+}
+
+#[test]
+fn division_by_zero_has_no_value() {
+    let emu = emulator();
+    let expr: Expr = "V0 / DT == 1".parse().unwrap();
+    assert_eq!(expr.eval(&emu), None);
+    assert!(!expr.holds(&emu));
+    assert!(Expr::parse("1 || 1 % 0").unwrap().holds(&emu));
+}
+
+#[test]
+fn errors() {
+    let cases: &[(&str, usize, &str)] = &[
+        ("", 0, "expected a value"),
+        ("V3 ==", 5, "expected a value"),
+        ("VG == 1", 0, "unknown register"),
+        ("0x == 1", 0, "invalid number"),
+        ("[I", 2, "expected ]"),
+        ("(1 + 2", 6, "expected )"),
+        ("1 2", 2, "expected an operator"),
+        ("V1 = 2", 3, "unexpected character"),
+        ("99999999999", 0, "invalid number"),
+    ];
+    for &(source, column, message) in cases {
+        assert_eq!(Expr::parse(source), Err(ParseError { column, message }), "{}", source);
+    }
+    assert_eq!(Expr::parse("[I").unwrap_err().to_string(), "expected ] at column 3");
+}
@@ -0,0 +1,92 @@
+//! Every backend has to leave the emulator exactly as the interpreter does
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+mod common;
+
+use common::{emulator, snapshot, MAZE, SELF_MODIFYING};
+
+/// run rom for frames on every backend, comparing against the interpreter after each frame
+fn assert_backends_agree(rom: &[u8], quirks: Quirks, frames: usize) {
+    for &kind in &[BackendKind::Cached, BackendKind::Jit] {
+        let mut reference = emulator(rom, quirks);
+        let mut emu = emulator(rom, quirks);
+        let mut backend = Backend::new(kind);
+        for frame in 0..frames {
+            reference.run_frame();
+            backend.run_frame(&mut emu);
+            assert!(snapshot(&reference) == snapshot(&emu), "{} diverged on frame {}", kind, frame);
+        }
+    }
+}
+
+#[test]
+fn maze() {
+    for &quirks in &[Quirks::RUST8, Quirks::CHIP8, Quirks::SCHIP] {
+        assert_backends_agree(MAZE, quirks, 300);
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    assert_backends_agree(&SELF_MODIFYING, Quirks::RUST8, 2);
+    let mut emu = emulator(&SELF_MODIFYING, Quirks::RUST8);
+    Backend::new(BackendKind::Jit).run_frame(&mut emu);
+    assert_eq!(emu.registers[2], 6);
+}
+
+/// a random instruction, mostly ones that keep running, with jumps and writes kept near the program
+fn random_instruction(rng: &mut Rng, len: u16) -> u16 {
+    let byte = |rng: &mut Rng| u16::from(rng.next_u8());
+    let x = byte(rng) & 0xF;
+    let y = byte(rng) & 0xF;
+    let nn = byte(rng);
+    let target = 0x200 + (byte(rng) % len) * 2;
+    match byte(rng) % 16 {
+        0 => 0x1000 | target,
+        1 => 0x3000 | x << 8 | nn,
+        2 => 0x4000 | x << 8 | nn,
+        3 => 0x5000 | x << 8 | y << 4,
+        4 | 5 => 0x6000 | x << 8 | nn,
+        6 => 0x8000 | x << 8 | y << 4 | (byte(rng) % 5),
+        7 => 0x9000 | x << 8 | y << 4,
+        8 => 0xA000 | target,
+        9 => 0xC000 | x << 8 | nn,
+        10 => 0xD000 | x << 8 | y << 4 | (byte(rng) % 15 + 1),
+        11 => 0xE000 | x << 8 | if byte(rng) % 2 == 0 { 0x9E } else { 0xA1 },
+        12 => 0xF029 | x << 8,
+        // writes land on the program itself, whatever they make has to run the same everywhere
+        13 => 0xF055 | (x % 4) << 8,
+        14 => 0xF065 | (x % 4) << 8,
+        _ => 0x00E0,
+    }
+}
+
+#[test]
+fn random_programs() {
+    let mut rng = Rng::from_seed(0x5eed);
+    for _ in 0..200 {
+        let len = 48;
+        let rom: Vec<u8> = (0..len).flat_map(|_| random_instruction(&mut rng, len).to_be_bytes()).collect();
+        for &quirks in &[Quirks::RUST8, Quirks::CHIP8] {
+            assert_backends_agree(&rom, quirks, 20);
+        }
+    }
+}
+
+#[test]
+fn pc_past_the_end_of_memory_wraps() {
+    // pc is public, a host can point it anywhere
+    let mut reference = emulator(&SELF_MODIFYING, Quirks::RUST8);
+    reference.pc = 0xF200;
+    reference.tick();
+    for &kind in &[BackendKind::Cached, BackendKind::Jit] {
+        let mut emu = emulator(&SELF_MODIFYING, Quirks::RUST8);
+        emu.pc = 0xF200;
+        Backend::new(kind).tick(&mut emu);
+        assert!(snapshot(&reference) == snapshot(&emu), "{} diverged", kind);
+        assert_eq!(emu.registers[0], 0x72);
+    }
+}
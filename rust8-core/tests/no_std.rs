@@ -0,0 +1,43 @@
+//! Builds the core without std, on the host so it always runs and for a bare metal arm target
+//! the way firmware would use it when that target is installed with
+//! `rustup target add thumbv7em-none-eabihf`.
+
+use std::path::Path;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+/// cargo build the core without its default features, for the host or a target
+fn build(target: Option<&str>) -> bool {
+    let mut cargo = Command::new(env!("CARGO"));
+    let _ = cargo
+        .args(["build", "--quiet", "--offline", "--no-default-features", "--manifest-path", concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")])
+        .args(["--target-dir", concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std")]);
+    if let Some(target) = target {
+        let _ = cargo.args(["--target", target]);
+    }
+    cargo.status().expect("cargo runs").success()
+}
+
+fn target_installed(target: &str) -> bool {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    match Command::new(rustc).args(["--print", "sysroot"]).output() {
+        Ok(output) => Path::new(String::from_utf8_lossy(&output.stdout).trim()).join("lib/rustlib").join(target).exists(),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn builds_without_std() {
+    // warnings are errors in the core, so this catches std only code left unused as well
+    assert!(build(None), "rust8-core failed to build without std");
+}
+
+#[test]
+fn builds_for_bare_metal_arm() {
+    if !target_installed(TARGET) {
+        eprintln!("{} isn't installed, skipping the cross build", TARGET);
+        return;
+    }
+    assert!(build(Some(TARGET)), "rust8-core failed to build for {} without std", TARGET);
+}
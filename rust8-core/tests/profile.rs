@@ -0,0 +1,78 @@
+//! Profiles attribute instructions to the call stack they ran under, the same on every backend
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::emu::Emulator;
+use rust8_core::profile::{Profile, SubroutineProfile};
+
+/// main calls 206 which calls 20C, then draws and idles
+const NESTED: [u8; 16] = [
+    0x22, 0x06, // 200 CALL 0x206
+    0xD0, 0x01, // 202 DRW V0, V0, 1
+    0x12, 0x04, // 204 JP 0x204
+    0x22, 0x0C, // 206 CALL 0x20C
+    0x60, 0x01, // 208 LD V0, 1
+    0x00, 0xEE, // 20A RET
+    0x61, 0x02, // 20C LD V1, 2
+    0x00, 0xEE, // 20E RET
+];
+
+/// 206 calls itself until V0 counts down to 0
+const RECURSIVE: [u8; 14] = [
+    0x60, 0x03, // 200 LD V0, 3
+    0x22, 0x06, // 202 CALL 0x206
+    0x12, 0x04, // 204 JP 0x204
+    0x70, 0xFF, // 206 ADD V0, 0xFF
+    0x30, 0x00, // 208 SE V0, 0
+    0x22, 0x06, // 20A CALL 0x206
+    0x00, 0xEE, // 20C RET
+];
+
+fn profile(rom: &[u8], kind: BackendKind, frames: u32) -> Box<Profile> {
+    let mut emu = Emulator::new();
+    emu.quirks.vblank = false;
+    emu.load_rom(rom).unwrap();
+    emu.profile = Some(Box::default());
+    let mut backend = Backend::new(kind);
+    for _ in 0..frames {
+        backend.run_frame(&mut emu);
+    }
+    emu.profile.take().unwrap()
+}
+
+#[test]
+fn nested_calls() {
+    let profile = profile(&NESTED, BackendKind::Interpreter, 2);
+    assert_eq!(profile.total(), 30);
+    assert_eq!(profile.subroutines(), vec![
+        SubroutineProfile { entry: 0x200, calls: 1, exclusive: 25, inclusive: 30 },
+        SubroutineProfile { entry: 0x206, calls: 1, exclusive: 3, inclusive: 5 },
+        SubroutineProfile { entry: 0x20C, calls: 1, exclusive: 2, inclusive: 2 },
+    ]);
+    assert_eq!(profile.stacks(), vec![
+        (vec![0x200], 25),
+        (vec![0x200, 0x206], 3),
+        (vec![0x200, 0x206, 0x20C], 2),
+    ]);
+    assert_eq!(profile.draws_per_frame(), &[1, 0]);
+}
+
+#[test]
+fn recursion_is_counted_once_inclusively() {
+    let profile = profile(&RECURSIVE, BackendKind::Interpreter, 1);
+    let subroutine = profile.subroutines()[1];
+    assert_eq!(subroutine, SubroutineProfile { entry: 0x206, calls: 3, exclusive: 11, inclusive: 11 });
+    let depths: Vec<usize> = profile.stacks().iter().map(|(stack, _)| stack.len()).collect();
+    assert_eq!(depths, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn backends_profile_the_same() {
+    for rom in [&NESTED[..], &RECURSIVE[..]].iter() {
+        let expected = profile(rom, BackendKind::Interpreter, 3);
+        for &kind in BackendKind::ALL.iter() {
+            let profile = profile(rom, kind, 3);
+            assert_eq!(profile.stacks(), expected.stacks(), "{:?}", kind);
+            assert_eq!(profile.draws_per_frame(), expected.draws_per_frame(), "{:?}", kind);
+        }
+    }
+}
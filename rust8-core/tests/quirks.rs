@@ -0,0 +1,111 @@
+//! Each quirk flipped on its own from the modern preset, and how run_frame spends a frame
+
+use rust8_core::constants::DEFAULT_TICKRATE;
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+
+/// an emulator with rom loaded and only the quirks changed by quirk from modern
+fn emulator(rom: &[u8], quirk: impl Fn(&mut Quirks)) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.quirks = Quirks::MODERN;
+    quirk(&mut emu.quirks);
+    emu.load_rom(rom).unwrap();
+    emu
+}
+
+fn run(rom: &[u8], quirk: impl Fn(&mut Quirks), instructions: usize) -> Emulator {
+    let mut emu = emulator(rom, quirk);
+    for _ in 0..instructions {
+        emu.tick();
+    }
+    emu
+}
+
+#[test]
+fn shift() {
+    // LD V1, 0x03; LD V2, 0x10; SHR V1, V2
+    let rom = [0x61, 0x03, 0x62, 0x10, 0x81, 0x26];
+    let emu = run(&rom, |_| {}, 3);
+    assert_eq!((emu.registers[1], emu.registers[0xF]), (0x08, 0));
+    let emu = run(&rom, |quirks| quirks.shift = true, 3);
+    assert_eq!((emu.registers[1], emu.registers[0xF]), (0x01, 1));
+}
+
+#[test]
+fn memory_increment() {
+    // LD I, 0x300; LD [I], V2
+    let rom = [0xA3, 0x00, 0xF2, 0x55];
+    assert_eq!(run(&rom, |_| {}, 2).index, 0x303);
+    assert_eq!(run(&rom, |quirks| quirks.memory_increment_by_x = true, 2).index, 0x302);
+    assert_eq!(run(&rom, |quirks| quirks.memory_leave_i_unchanged = true, 2).index, 0x300);
+}
+
+#[test]
+fn wrap() {
+    // the top row of the 0 glyph is 4 pixels wide: LD V1, 62; LD F, V0; DRW V1, V0, 1
+    let rom = [0x61, 62, 0xF0, 0x29, 0xD1, 0x01];
+    let emu = run(&rom, |_| {}, 3);
+    assert_eq!(emu.display.vram[62..64], [true, true]);
+    assert_eq!(emu.display.vram[..2], [false, false]);
+    let emu = run(&rom, |quirks| quirks.wrap = true, 3);
+    assert_eq!(emu.display.vram[62..64], [true, true]);
+    assert_eq!(emu.display.vram[..2], [true, true]);
+}
+
+#[test]
+fn jump() {
+    // LD V0, 1; LD V3, 5; JP V0, 0x300
+    let rom = [0x60, 0x01, 0x63, 0x05, 0xB3, 0x00];
+    assert_eq!(run(&rom, |_| {}, 3).pc, 0x301);
+    assert_eq!(run(&rom, |quirks| quirks.jump = true, 3).pc, 0x305);
+}
+
+#[test]
+fn logic() {
+    // LD VF, 1; OR V1, V2
+    let rom = [0x6F, 0x01, 0x81, 0x21];
+    assert_eq!(run(&rom, |_| {}, 2).registers[0xF], 1);
+    assert_eq!(run(&rom, |quirks| quirks.logic = true, 2).registers[0xF], 0);
+}
+
+#[test]
+fn vblank() {
+    // two draws and a loop, with the quirk a draw ends the frame
+    let rom = [0xD0, 0x01, 0xD0, 0x01, 0x12, 0x04];
+    let mut emu = emulator(&rom, |_| {});
+    emu.run_frame();
+    assert_eq!(emu.pc, 0x204);
+    let mut emu = emulator(&rom, |quirks| quirks.vblank = true);
+    emu.run_frame();
+    assert_eq!(emu.pc, 0x202);
+    emu.run_frame();
+    assert_eq!(emu.pc, 0x204);
+}
+
+#[test]
+fn frames_run_tickrate_instructions_then_count_down_timers() {
+    // ADD V0, 1 over and over
+    let rom = [0x70, 0x01].repeat(100);
+    let mut emu = emulator(&rom, |_| {});
+    assert_eq!(emu.tickrate, DEFAULT_TICKRATE);
+    emu.delay = 5;
+    emu.sound = 1;
+    emu.run_frame();
+    assert_eq!(u32::from(emu.registers[0]), DEFAULT_TICKRATE);
+    assert_eq!((emu.delay, emu.sound), (4, 0));
+
+    emu.tickrate = 4;
+    emu.run_frame();
+    assert_eq!(u32::from(emu.registers[0]), DEFAULT_TICKRATE + 4);
+    emu.run_frame();
+    assert_eq!((emu.delay, emu.sound), (2, 0));
+}
+
+#[test]
+fn presets_by_name() {
+    for &(name, quirks) in Quirks::PRESETS.iter() {
+        assert_eq!(Quirks::from_name(name), Some(quirks));
+    }
+    assert_eq!(Quirks::from_name("superchip"), None);
+    assert_eq!(Quirks::from_name("CHIP8"), None);
+}
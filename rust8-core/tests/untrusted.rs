@@ -0,0 +1,106 @@
+//! Roms that used to panic the core, each one has to run on every backend
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::emu::Emulator;
+
+fn run(rom: &[u8], frames: usize) -> Vec<Emulator> {
+    BackendKind::ALL.iter().map(|&kind| {
+        let mut emu = Emulator::new();
+        emu.load_rom(rom).unwrap();
+        let mut backend = Backend::new(kind);
+        for _ in 0..frames {
+            backend.run_frame(&mut emu);
+        }
+        emu
+    }).collect()
+}
+
+#[test]
+fn invalid_instructions_are_skipped() {
+    for emu in run(&[0x00, 0x00, 0x81, 0x2F, 0xE0, 0x00, 0xF0, 0xFF, 0x60, 0x07, 0x12, 0x0A], 1) {
+        assert_eq!(emu.registers[0], 7);
+    }
+}
+
+#[test]
+fn stack_wraps_both_ways() {
+    // RET with nothing on the stack, then recurse forever
+    for emu in run(&[0x00, 0xEE], 1) {
+        assert!(emu.sp < 16);
+    }
+    for emu in run(&[0x22, 0x00], 2) {
+        assert!(emu.sp < 16);
+    }
+}
+
+#[test]
+fn index_wraps_at_the_end_of_memory() {
+    let rom = [
+        0xAF, 0xFF, // LD I, 0xFFF
+        0x60, 0xFF, // LD V0, 0xFF
+        0xF0, 0x33, // LD B, V0
+        0x12, 0x06, // JP 0x206
+    ];
+    for emu in run(&rom, 1) {
+        assert_eq!(emu.memory[0xFFF..], [2]);
+        assert_eq!(emu.memory[..2], [5, 5]);
+    }
+    let rom = [
+        0xAF, 0xFF, // LD I, 0xFFF
+        0xFF, 0x55, // LD [I], VF
+        0xFF, 0x65, // LD VF, [I]
+        0xD0, 0x0F, // DRW V0, V0, 15
+        0xF0, 0x1E, // ADD I, V0
+        0x70, 0xFF, // ADD V0, 0xFF
+        0x12, 0x02, // JP 0x202
+    ];
+    let emus = run(&rom, 600);
+    for emu in &emus[1..] {
+        assert_eq!(emu.index, emus[0].index);
+        assert!(emu.memory[..] == emus[0].memory[..]);
+    }
+}
+
+#[test]
+fn pc_wraps_at_the_end_of_memory() {
+    // JP V0, 0xFFF lands on the odd last byte and runs straight on into address 0
+    for emu in run(&[0x60, 0x00, 0xBF, 0xFF], 4) {
+        assert!(emu.pc < 0x1000);
+    }
+}
+
+#[test]
+fn arithmetic_wraps() {
+    let rom = [
+        0x70, 0xFF, // ADD V0, 0xFF
+        0x81, 0x05, // SUB V1, V0
+        0x82, 0x07, // SUBN V2, V0
+        0x12, 0x00, // JP 0x200
+    ];
+    for &kind in BackendKind::ALL.iter() {
+        let mut emu = Emulator::new();
+        emu.load_rom(&rom).unwrap();
+        // once around the loop a frame
+        emu.tickrate = 4;
+        let mut backend = Backend::new(kind);
+
+        backend.run_frame(&mut emu);
+        assert_eq!(emu.registers[..3], [0xFF, 0x01, 0xFF], "{}", kind);
+        assert_eq!(emu.registers[0xF], 1, "{}", kind);
+        backend.run_frame(&mut emu);
+        assert_eq!(emu.registers[..3], [0xFE, 0x03, 0xFF], "{}", kind);
+        assert_eq!(emu.registers[0xF], 0, "{}", kind);
+
+        // V0 goes all the way around several times
+        let (mut v0, mut v1, mut v2) = (0xFEu8, 0x03u8, 0xFFu8);
+        for frame in 2..1000 {
+            backend.run_frame(&mut emu);
+            v0 = v0.wrapping_add(0xFF);
+            v1 = v1.wrapping_sub(v0);
+            let no_borrow = v0 >= v2;
+            v2 = v0.wrapping_sub(v2);
+            assert_eq!(emu.registers[..3], [v0, v1, v2], "{} on frame {}", kind, frame);
+            assert_eq!(emu.registers[0xF], no_borrow as u8, "{} on frame {}", kind, frame);
+        }
+    }
+}
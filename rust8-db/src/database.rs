@@ -0,0 +1,187 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use sha1::{Digest, Sha1};
+
+use crate::schema::{Platform, Program, QuirkOverrides, Rom};
+use crate::DbError;
+
+const BUNDLED_PROGRAMS: &str = include_str!("../data/programs.json");
+const BUNDLED_PLATFORMS: &str = include_str!("../data/platforms.json");
+
+/// return the lowercase hex sha1 of a rom, the key used by the database
+pub fn rom_hash(rom: &[u8]) -> String {
+    Sha1::digest(rom).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Everything the database knows about a single rom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub hash: String,
+    pub title: String,
+    pub file: Option<String>,
+    /// the first platform listed for the rom that the database has quirks for
+    pub platform: Option<String>,
+    pub tickrate: Option<u32>,
+    pub quirks: Quirks,
+    /// logical button names such as "up" or "a" mapped to keypad keys
+    pub keys: HashMap<String, u8>,
+    /// rgb colors for unset and set pixels
+    pub colors: Option<([u8; 3], [u8; 3])>,
+}
+
+impl RomInfo {
+    /// Configure an emulator with the quirks and speed for this rom
+    pub fn apply(&self, emu: &mut Emulator) {
+        emu.quirks = self.quirks;
+        if let Some(tickrate) = self.tickrate {
+            emu.tickrate = tickrate;
+        }
+    }
+}
+
+/// Rom settings keyed by sha1
+#[derive(Debug, Clone, Default)]
+pub struct Database {
+    programs: Vec<Program>,
+    platforms: HashMap<String, Platform>,
+    /// rom hash -> index into programs
+    hashes: HashMap<String, usize>,
+}
+
+impl Database {
+    /// An empty database
+    pub fn new() -> Self {
+        Database::default()
+    }
+
+    /// The database shipped with rust8
+    pub fn bundled() -> Self {
+        let mut db = Database::new();
+        db.add_platforms(BUNDLED_PLATFORMS).expect("bundled platforms.json is valid");
+        db.add_programs(BUNDLED_PROGRAMS).expect("bundled programs.json is valid");
+        db
+    }
+
+    /// Add the contents of a chip-8-database programs.json, later entries win for the same rom
+    pub fn add_programs(&mut self, json: &str) -> Result<(), DbError> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+        for program in programs {
+            for (hash, rom) in &program.roms {
+                if let Some(colors) = &rom.colors {
+                    for color in &colors.pixels {
+                        let _ = parse_color(color)?;
+                    }
+                }
+                let _ = self.hashes.insert(hash.to_lowercase(), self.programs.len());
+            }
+            self.programs.push(program);
+        }
+        Ok(())
+    }
+
+    /// Add the contents of a chip-8-database platforms.json, replacing platforms with the same id
+    pub fn add_platforms(&mut self, json: &str) -> Result<(), DbError> {
+        let platforms: Vec<Platform> = serde_json::from_str(json)?;
+        for platform in platforms {
+            let _ = self.platforms.insert(platform.id.clone(), platform);
+        }
+        Ok(())
+    }
+
+    /// Add programs.json and platforms.json from a directory, either may be missing
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), DbError> {
+        if let Some(json) = read_optional(&dir.as_ref().join("platforms.json"))? {
+            self.add_platforms(&json)?;
+        }
+        if let Some(json) = read_optional(&dir.as_ref().join("programs.json"))? {
+            self.add_programs(&json)?;
+        }
+        Ok(())
+    }
+
+    /// Look up a rom by its contents
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        self.lookup_hash(&rom_hash(rom))
+    }
+
+    /// Look up a rom by its sha1
+    pub fn lookup_hash(&self, hash: &str) -> Option<RomInfo> {
+        let hash = hash.to_lowercase();
+        let program = &self.programs[*self.hashes.get(&hash)?];
+        let rom = &program.roms.iter().find(|(key, _)| key.to_lowercase() == hash)?.1;
+        let platform = rom.platforms.iter().find_map(|id| self.platforms.get(id));
+
+        let mut quirks = Quirks::default();
+        if let Some(platform) = platform {
+            apply_overrides(&mut quirks, &platform.quirks);
+            if let Some(overrides) = rom.quirky_platforms.get(&platform.id) {
+                apply_overrides(&mut quirks, overrides);
+            }
+        }
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            file: rom.file.clone(),
+            platform: platform.map(|platform| platform.id.clone()),
+            tickrate: rom.tickrate.or_else(|| platform.and_then(|platform| platform.default_tickrate)),
+            quirks,
+            keys: rom.keys.clone(),
+            colors: colors(rom),
+            hash,
+        })
+    }
+}
+
+fn apply_overrides(quirks: &mut Quirks, overrides: &QuirkOverrides) {
+    quirks.shift = overrides.shift.unwrap_or(quirks.shift);
+    quirks.memory_increment_by_x = overrides.memory_increment_by_x.unwrap_or(quirks.memory_increment_by_x);
+    quirks.memory_leave_i_unchanged = overrides.memory_leave_i_unchanged.unwrap_or(quirks.memory_leave_i_unchanged);
+    quirks.wrap = overrides.wrap.unwrap_or(quirks.wrap);
+    quirks.jump = overrides.jump.unwrap_or(quirks.jump);
+    quirks.vblank = overrides.vblank.unwrap_or(quirks.vblank);
+    quirks.logic = overrides.logic.unwrap_or(quirks.logic);
+}
+
+/// the first two pixel colors, colors were validated when the rom was added
+fn colors(rom: &Rom) -> Option<([u8; 3], [u8; 3])> {
+    match rom.colors.as_ref()?.pixels.as_slice() {
+        [off, on, ..] => Some((parse_color(off).ok()?, parse_color(on).ok()?)),
+        _ => None,
+    }
+}
+
+/// parse a #rrggbb color
+fn parse_color(color: &str) -> Result<[u8; 3], DbError> {
+    let invalid = || DbError::InvalidColor(color.to_string());
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6).ok_or_else(invalid)?;
+    let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>, DbError> {
+    match fs::read_to_string(path) {
+        Ok(json) => Ok(Some(json)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Per rom settings looked up by the sha1 of the rom.
+//!
+//! The database uses the `programs.json` and `platforms.json` formats from the community
+//! chip-8-database, so its files can be dropped in to extend the small bundled set.
+
+#![forbid(unsafe_code)]
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod database;
+mod error;
+mod schema;
+
+pub use crate::database::{Database, RomInfo, rom_hash};
+pub use crate::error::DbError;
@@ -0,0 +1,70 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The subset of the chip-8-database schema rust8 cares about, unknown fields are ignored
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Program {
+    pub title: String,
+    #[serde(default)]
+    pub roms: HashMap<String, Rom>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rom {
+    pub file: Option<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// quirk overrides for this rom on a given platform
+    #[serde(default)]
+    pub quirky_platforms: HashMap<String, QuirkOverrides>,
+    pub tickrate: Option<u32>,
+    /// logical button names such as "up" or "a" mapped to keypad keys
+    #[serde(default)]
+    pub keys: HashMap<String, u8>,
+    pub colors: Option<Colors>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Colors {
+    #[serde(default)]
+    pub pixels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Platform {
+    pub id: String,
+    pub default_tickrate: Option<u32>,
+    #[serde(default)]
+    pub quirks: QuirkOverrides,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuirkOverrides {
+    pub shift: Option<bool>,
+    pub memory_increment_by_x: Option<bool>,
+    pub memory_leave_i_unchanged: Option<bool>,
+    pub wrap: Option<bool>,
+    pub jump: Option<bool>,
+    pub vblank: Option<bool>,
+    pub logic: Option<bool>,
+}
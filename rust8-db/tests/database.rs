@@ -0,0 +1,111 @@
+//! Looking roms up in the bundled database and in user directories layered over it
+
+use std::env;
+use std::fs;
+use std::process;
+
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_db::{rom_hash, Database, DbError};
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+const MAZE_HASH: &str = "8b70080adbac44513ec60005734a816372b845ec";
+
+#[test]
+fn bundled_lookup_by_hash() {
+    assert_eq!(rom_hash(MAZE), MAZE_HASH);
+    let db = Database::bundled();
+    let info = db.lookup(MAZE).unwrap();
+    assert_eq!(info.title, "Maze");
+    assert_eq!(info.file.as_deref(), Some("maze.ch8"));
+    assert_eq!(info.platform.as_deref(), Some("originalChip8"));
+    assert_eq!(info.tickrate, Some(15));
+    assert_eq!(info.quirks, Quirks::CHIP8);
+    assert_eq!(info.colors, Some(([0, 0, 0], [0xFF, 0xFF, 0xFF])));
+
+    assert_eq!(db.lookup_hash(&MAZE_HASH.to_uppercase()), Some(info));
+    assert_eq!(db.lookup(&[0x12, 0x00]), None);
+}
+
+#[test]
+fn platform_quirks_and_rom_overrides() {
+    let mut db = Database::bundled();
+    db.add_programs(r##"[{
+        "title": "Quirky",
+        "roms": {
+            "00000000000000000000000000000000000000aa": {
+                "platforms": ["unknownPlatform", "superchip"],
+                "quirkyPlatforms": { "superchip": { "wrap": true, "jump": false } },
+                "keys": { "up": 5 }
+            },
+            "00000000000000000000000000000000000000bb": {}
+        }
+    }]"##).unwrap();
+
+    // the first platform the database knows, with the rom's overrides on top
+    let info = db.lookup_hash("00000000000000000000000000000000000000aa").unwrap();
+    assert_eq!(info.platform.as_deref(), Some("superchip"));
+    assert_eq!(info.tickrate, Some(30));
+    assert_eq!(info.quirks, Quirks { wrap: true, jump: false, ..Quirks::SCHIP });
+    assert_eq!(info.keys["up"], 5);
+
+    // nothing known about the platform leaves rust8's defaults
+    let info = db.lookup_hash("00000000000000000000000000000000000000bb").unwrap();
+    assert_eq!((info.platform, info.tickrate, info.quirks), (None, None, Quirks::default()));
+}
+
+#[test]
+fn user_dir_beats_bundled() {
+    let dir = env::temp_dir().join(format!("rust8-db-test-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("programs.json"), format!(r#"[{{
+        "title": "My Maze",
+        "roms": {{ "{}": {{ "platforms": ["fastChip8"], "tickrate": 40 }} }}
+    }}]"#, MAZE_HASH)).unwrap();
+    fs::write(dir.join("platforms.json"), r#"[{
+        "id": "fastChip8",
+        "defaultTickrate": 100,
+        "quirks": { "shift": true }
+    }]"#).unwrap();
+
+    let mut db = Database::bundled();
+    let added = db.add_dir(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    added.unwrap();
+
+    let info = db.lookup(MAZE).unwrap();
+    assert_eq!(info.title, "My Maze");
+    assert_eq!(info.platform.as_deref(), Some("fastChip8"));
+    assert_eq!(info.tickrate, Some(40));
+    assert_eq!(info.quirks, Quirks { shift: true, ..Quirks::default() });
+
+    // a directory without either file adds nothing
+    db.add_dir(env::temp_dir().join("rust8-db-test-missing")).unwrap();
+}
+
+#[test]
+fn malformed_json_is_an_error() {
+    let mut db = Database::new();
+    assert!(matches!(db.add_programs("[{\"title\": "), Err(DbError::Json(_))));
+    assert!(matches!(db.add_programs("{\"title\": \"not a list\"}"), Err(DbError::Json(_))));
+    assert!(matches!(db.add_platforms("[{\"name\": \"no id\"}]"), Err(DbError::Json(_))));
+    let bad_color = r##"[{"title": "t", "roms": {"aa": {"colors": {"pixels": ["#000000", "white"]}}}}]"##;
+    assert!(matches!(db.add_programs(bad_color), Err(DbError::InvalidColor(color)) if color == "white"));
+}
+
+#[test]
+fn apply_sets_quirks_and_tickrate() {
+    let mut info = Database::bundled().lookup(MAZE).unwrap();
+    let mut emu = Emulator::new();
+    info.apply(&mut emu);
+    assert_eq!(emu.quirks, Quirks::CHIP8);
+    assert_eq!(emu.tickrate, 15);
+
+    // without a tickrate the emulator keeps its own
+    info.tickrate = None;
+    info.quirks = Quirks::XOCHIP;
+    emu.tickrate = 7;
+    info.apply(&mut emu);
+    assert_eq!(emu.quirks, Quirks::XOCHIP);
+    assert_eq!(emu.tickrate, 7);
+}
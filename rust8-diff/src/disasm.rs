@@ -0,0 +1,52 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use rust8_core::cpu::mnemonic;
+
+/// A few instructions either side of an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    /// the address being looked at
+    pub address: u16,
+    /// address and instruction for each line
+    pub lines: Vec<(u16, u16)>,
+}
+
+impl Context {
+    /// Disassemble lines instructions before and after address, stopping at the ends of memory
+    pub fn around(memory: &[u8], address: u16, lines: u16) -> Self {
+        let first = address.saturating_sub(lines * 2);
+        let lines = (first..=address.saturating_add(lines * 2))
+            .step_by(2)
+            .filter_map(|at| match memory.get(at as usize..at as usize + 2) {
+                Some(&[high, low]) => Some((at, u16::from(high) << 8 | u16::from(low))),
+                _ => None,
+            })
+            .collect();
+        Context { address, lines }
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &(at, instruction) in &self.lines {
+            let marker = if at == self.address { '>' } else { ' ' };
+            writeln!(f, "{} {:#05x}  {:04x}  {}", marker, at, instruction, mnemonic(instruction))?;
+        }
+        Ok(())
+    }
+}
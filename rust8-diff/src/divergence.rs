@@ -0,0 +1,157 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use rust8_core::emu::Emulator;
+
+use crate::disasm::Context;
+
+/// One way the two emulators disagree, with side a's value first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    Register(u8, u8, u8),
+    Index(u16, u16),
+    Pc(u16, u16),
+    Sp(u8, u8),
+    Stack(usize, u16, u16),
+    Delay(u8, u8),
+    Sound(u8, u8),
+    Memory(usize, u8, u8),
+    /// a pixel at x, y
+    Vram(usize, usize, bool, bool),
+    /// whether each side's frame was over after this many instructions
+    FrameEnd(u32, bool, bool),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Register(x, a, b) => write!(f, "V{:X}: {:#04x} != {:#04x}", x, a, b),
+            Difference::Index(a, b) => write!(f, "I: {:#05x} != {:#05x}", a, b),
+            Difference::Pc(a, b) => write!(f, "PC: {:#05x} != {:#05x}", a, b),
+            Difference::Sp(a, b) => write!(f, "SP: {} != {}", a, b),
+            Difference::Stack(level, a, b) => write!(f, "stack[{}]: {:#05x} != {:#05x}", level, a, b),
+            Difference::Delay(a, b) => write!(f, "DT: {} != {}", a, b),
+            Difference::Sound(a, b) => write!(f, "ST: {} != {}", a, b),
+            Difference::Memory(address, a, b) => write!(f, "memory[{:#05x}]: {:#04x} != {:#04x}", address, a, b),
+            Difference::Vram(x, y, a, b) => write!(f, "pixel ({}, {}): {} != {}", x, y, a, b),
+            Difference::FrameEnd(ran, a, b) => write!(f, "frame over after {} instructions: {} != {}", ran, a, b),
+        }
+    }
+}
+
+/// Every difference between two emulators' state
+pub fn compare(a: &Emulator, b: &Emulator) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    for (x, (&va, &vb)) in a.registers.iter().zip(b.registers.iter()).enumerate() {
+        if va != vb {
+            differences.push(Difference::Register(x as u8, va, vb));
+        }
+    }
+    if a.index != b.index {
+        differences.push(Difference::Index(a.index, b.index));
+    }
+    if a.pc != b.pc {
+        differences.push(Difference::Pc(a.pc, b.pc));
+    }
+    if a.sp != b.sp {
+        differences.push(Difference::Sp(a.sp, b.sp));
+    }
+    for (level, (&sa, &sb)) in a.stack.iter().zip(b.stack.iter()).enumerate() {
+        if sa != sb {
+            differences.push(Difference::Stack(level, sa, sb));
+        }
+    }
+    if a.delay != b.delay {
+        differences.push(Difference::Delay(a.delay, b.delay));
+    }
+    if a.sound != b.sound {
+        differences.push(Difference::Sound(a.sound, b.sound));
+    }
+    for (address, (&ma, &mb)) in a.memory.iter().zip(b.memory.iter()).enumerate() {
+        if ma != mb {
+            differences.push(Difference::Memory(address, ma, mb));
+        }
+    }
+    let width = rust8_core::constants::DISPLAY_WIDTH as usize;
+    for (pixel, (&pa, &pb)) in a.display.vram.iter().zip(b.display.vram.iter()).enumerate() {
+        if pa != pb {
+            differences.push(Difference::Vram(pixel % width, pixel / width, pa, pb));
+        }
+    }
+    differences
+}
+
+/// The first point where the two emulators disagreed
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// instructions executed by side a before the one that diverged
+    pub cycle: u64,
+    pub frame: u64,
+    pub differences: Vec<Difference>,
+    /// disassembly around the diverging instruction on each side
+    pub context: [Context; 2],
+}
+
+/// differences of one kind beyond this many are counted rather than listed
+const LISTED: usize = 8;
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "diverged at cycle {} (frame {})", self.cycle, self.frame)?;
+        let mut memory = 0;
+        let mut vram = 0;
+        for difference in &self.differences {
+            let count = match difference {
+                Difference::Memory(..) => &mut memory,
+                Difference::Vram(..) => &mut vram,
+                _ => {
+                    writeln!(f, "  {}", difference)?;
+                    continue;
+                }
+            };
+            *count += 1;
+            if *count <= LISTED {
+                writeln!(f, "  {}", difference)?;
+            }
+        }
+        for (name, count) in [("memory bytes", memory), ("pixels", vram)].iter() {
+            if *count > LISTED {
+                writeln!(f, "  ... {} {} differ in total", count, name)?;
+            }
+        }
+        for (side, context) in ["a", "b"].iter().zip(self.context.iter()) {
+            writeln!(f, "\n{}:", side)?;
+            write!(f, "{}", context)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why lockstep execution stopped
+#[derive(Debug, Clone)]
+pub enum Stop {
+    /// the two emulators disagree
+    Diverged(Box<Divergence>),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Diverged(divergence) => write!(f, "{}", divergence),
+        }
+    }
+}
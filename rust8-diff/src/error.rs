@@ -0,0 +1,63 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::io;
+
+use rust8_core::backend::UnknownBackend;
+use rust8_core::error::Error;
+
+#[derive(Debug)]
+pub enum DiffError {
+    Io(io::Error),
+    Emulator(Error),
+    Backend(UnknownBackend),
+    /// a quirk preset name that doesn't exist
+    UnknownQuirks(String),
+    /// a line of an input file, numbered from 1, that isn't `FRAME MASK`
+    Input(usize, String),
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::Io(e) => write!(f, "{}", e),
+            DiffError::Emulator(e) => write!(f, "{}", e),
+            DiffError::Backend(e) => write!(f, "{}", e),
+            DiffError::UnknownQuirks(name) => write!(f, "unknown quirks {}, expected rust8, chip8, modern, schip or xochip", name),
+            DiffError::Input(line, text) => write!(f, "input line {} isn't FRAME MASK: {}", line, text),
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+impl From<io::Error> for DiffError {
+    fn from(e: io::Error) -> Self {
+        DiffError::Io(e)
+    }
+}
+
+impl From<Error> for DiffError {
+    fn from(e: Error) -> Self {
+        DiffError::Emulator(e)
+    }
+}
+
+impl From<UnknownBackend> for DiffError {
+    fn from(e: UnknownBackend) -> Self {
+        DiffError::Backend(e)
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::DiffError;
+
+/// The keys held on each frame, read from lines of `FRAME MASK` where the hex key mask
+/// is held from that frame on. Blank lines and anything after a `#` are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Input {
+    /// frame and mask, sorted by frame
+    changes: Vec<(u64, u16)>,
+}
+
+impl Input {
+    pub fn parse(text: &str) -> Result<Self, DiffError> {
+        let mut changes = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || DiffError::Input(number + 1, line.to_string());
+            let mut fields = line.split_whitespace();
+            let frame = fields.next().and_then(|frame| frame.parse().ok()).ok_or_else(invalid)?;
+            let mask = fields.next()
+                .and_then(|mask| u16::from_str_radix(mask.trim_start_matches("0x"), 16).ok())
+                .ok_or_else(invalid)?;
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+            changes.push((frame, mask));
+        }
+        changes.sort_by_key(|&(frame, _)| frame);
+        Ok(Input { changes })
+    }
+
+    /// The key mask held on frame
+    pub fn keys(&self, frame: u64) -> u16 {
+        self.changes.iter().take_while(|&&(start, _)| start <= frame).last().map_or(0, |&(_, mask)| mask)
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Run one rom and input stream through two emulator configurations in lockstep and report the
+//! first instruction after which their registers, I, PC, stack, timers, memory or display disagree.
+//! By default each side runs whole frames through its backend and a frame that ends differently
+//! is run again an instruction at a time to find the one that diverged.
+//!
+//! Useful for checking a backend against the interpreter, or for finding where two quirk presets
+//! make a rom behave differently.
+
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod disasm;
+mod divergence;
+mod error;
+mod input;
+mod lockstep;
+
+pub use crate::disasm::Context;
+pub use crate::divergence::{compare, Difference, Divergence, Stop};
+pub use crate::error::DiffError;
+pub use crate::input::Input;
+pub use crate::lockstep::{Granularity, Lockstep, Side, DEFAULT_CONTEXT};
+
+use std::str::FromStr;
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_core::rng::Rng;
+
+/// How one side runs: a backend and a quirk preset, written `BACKEND[:QUIRKS]` like `jit:chip8`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub backend: BackendKind,
+    pub quirks: Quirks,
+}
+
+impl Config {
+    pub fn new(backend: BackendKind) -> Self {
+        Config { backend, quirks: Quirks::RUST8 }
+    }
+
+    /// An emulator with this configuration running rom, seeded so both sides draw the same random numbers
+    pub fn side(&self, rom: &[u8], seed: u64) -> Result<Side, DiffError> {
+        let mut emu = Emulator::new();
+        emu.rng = Rng::from_seed(seed);
+        emu.quirks = self.quirks;
+        emu.load_rom(rom)?;
+        Ok(Side::new(emu, Backend::new(self.backend)))
+    }
+}
+
+impl FromStr for Config {
+    type Err = DiffError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.splitn(2, ':');
+        let mut config = Config::new(parts.next().unwrap_or("").parse()?);
+        if let Some(name) = parts.next() {
+            config.quirks = Quirks::from_name(name).ok_or_else(|| DiffError::UnknownQuirks(name.to_string()))?;
+        }
+        Ok(config)
+    }
+}
+
+impl Lockstep {
+    /// Run up to frames frames of input, stopping early at the first divergence
+    pub fn run(&mut self, input: &Input, frames: u64) -> Result<(), Stop> {
+        while self.frame() < frames {
+            self.run_frame(input.keys(self.frame()))?;
+        }
+        Ok(())
+    }
+}
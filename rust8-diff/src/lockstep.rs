@@ -0,0 +1,190 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rust8_core::backend::Backend;
+use rust8_core::debug::Session;
+use rust8_core::emu::Emulator;
+use rust8_core::profile::Profile;
+use rust8_core::state::STATE_SIZE;
+
+use crate::disasm::Context;
+use crate::divergence::{compare, Difference, Divergence, Stop};
+
+/// instructions shown either side of the diverging one
+pub const DEFAULT_CONTEXT: u16 = 4;
+
+/// When the two sides are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    /// after every frame, each run whole by its backend, going back over a frame that diverged
+    /// an instruction at a time to find where
+    Frame,
+    /// after every instruction
+    Instruction,
+}
+
+/// One of the two configurations being compared
+pub struct Side {
+    pub emu: Emulator,
+    /// runs the backend a frame or an instruction at a time
+    pub session: Session,
+    /// instructions run so far this frame
+    ran: u32,
+    /// whether this frame is over for this side
+    done: bool,
+}
+
+impl Side {
+    pub fn new(emu: Emulator, backend: Backend) -> Self {
+        Side { emu, session: Session::new(backend), ran: 0, done: false }
+    }
+
+    fn start_frame(&mut self, keys: u16) {
+        self.emu.keyboard.set_keypad(keys);
+        self.ran = 0;
+        self.done = false;
+    }
+
+    /// run the next instruction of this frame if there is one
+    fn step(&mut self) {
+        if !self.done {
+            // a frame of no instructions only counts down the timers
+            self.ran += u32::from(self.emu.tickrate > 0);
+            self.done = self.session.step(&mut self.emu);
+        }
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = vec![0; STATE_SIZE];
+        self.emu.save_state(&mut state).expect("the buffer holds a state");
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+        self.emu.load_state(state).expect("the state was saved by this side");
+    }
+
+    /// instructions counted on this side so far
+    fn executed(&self) -> u64 {
+        self.emu.profile.as_ref().map_or(0, |profile| profile.total())
+    }
+}
+
+/// Two emulators fed the same input and run side by side, comparing them as often as granularity says
+pub struct Lockstep {
+    pub a: Side,
+    pub b: Side,
+    pub granularity: Granularity,
+    /// instructions either side of the diverging one to disassemble
+    pub context: u16,
+    cycle: u64,
+    frame: u64,
+}
+
+impl Lockstep {
+    pub fn new(mut a: Side, b: Side) -> Self {
+        // whole frames don't say how many instructions they ran, side a's profile counts them
+        let _ = a.emu.profile.get_or_insert_with(|| Box::new(Profile::new()));
+        Lockstep { a, b, granularity: Granularity::Frame, context: DEFAULT_CONTEXT, cycle: 0, frame: 0 }
+    }
+
+    /// instructions executed by side a so far
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// frames completed so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Hold keys on both sides and run one 60hz frame, stopping at the first difference
+    pub fn run_frame(&mut self, keys: u16) -> Result<(), Stop> {
+        match self.granularity {
+            Granularity::Frame => self.run_whole_frame(keys),
+            Granularity::Instruction => self.step_frame(keys),
+        }
+    }
+
+    fn run_whole_frame(&mut self, keys: u16) -> Result<(), Stop> {
+        let start = [self.a.save(), self.b.save()];
+        let executed = self.a.executed();
+        for side in [&mut self.a, &mut self.b].iter_mut() {
+            side.start_frame(keys);
+            let _ = side.session.run_frame(&mut side.emu);
+        }
+        let ran = self.a.executed() - executed;
+        let differences = compare(&self.a.emu, &self.b.emu);
+        if differences.is_empty() {
+            self.cycle += ran;
+            self.frame += 1;
+            return Ok(());
+        }
+        let (pc_a, pc_b) = (self.a.emu.pc, self.b.emu.pc);
+        let at_frame_end = Divergence { cycle: self.cycle + ran, ..self.divergence(pc_a, pc_b, differences) };
+
+        self.a.restore(&start[0]);
+        self.b.restore(&start[1]);
+        self.step_frame(keys)?;
+        // the frame only goes wrong run whole, a block or a shortcut that stepping doesn't take
+        Err(Stop::Diverged(Box::new(at_frame_end)))
+    }
+
+    /// run a frame an instruction at a time, comparing after each
+    fn step_frame(&mut self, keys: u16) -> Result<(), Stop> {
+        let cycle = self.cycle;
+        self.a.start_frame(keys);
+        self.b.start_frame(keys);
+        while !(self.a.done && self.b.done) {
+            let (pc_a, pc_b) = (self.a.emu.pc, self.b.emu.pc);
+            self.a.step();
+            self.b.step();
+            if self.a.done != self.b.done {
+                let end = Difference::FrameEnd(self.a.ran.max(self.b.ran), self.a.done, self.b.done);
+                return Err(self.diverged(pc_a, pc_b, vec![end]));
+            }
+            self.check(pc_a, pc_b)?;
+            self.cycle = cycle + u64::from(self.a.ran);
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn check(&self, pc_a: u16, pc_b: u16) -> Result<(), Stop> {
+        let differences = compare(&self.a.emu, &self.b.emu);
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(self.diverged(pc_a, pc_b, differences))
+        }
+    }
+
+    /// describe a divergence caused by the instructions at pc_a and pc_b
+    fn diverged(&self, pc_a: u16, pc_b: u16, differences: Vec<Difference>) -> Stop {
+        Stop::Diverged(Box::new(self.divergence(pc_a, pc_b, differences)))
+    }
+
+    fn divergence(&self, pc_a: u16, pc_b: u16, differences: Vec<Difference>) -> Divergence {
+        Divergence {
+            cycle: self.cycle,
+            frame: self.frame,
+            differences,
+            context: [
+                Context::around(&self.a.emu.memory, pc_a, self.context),
+                Context::around(&self.b.emu.memory, pc_b, self.context),
+            ],
+        }
+    }
+}
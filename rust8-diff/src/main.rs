@@ -0,0 +1,97 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use rust8_core::backend::BackendKind;
+use rust8_diff::{Config, Granularity, Input, Lockstep, DEFAULT_CONTEXT};
+
+struct Args {
+    a: Config,
+    b: Config,
+    frames: u64,
+    seed: u64,
+    input: Option<PathBuf>,
+    context: u16,
+    granularity: Granularity,
+    rom: PathBuf,
+}
+
+fn parse_args() -> Args {
+    let mut a = Config::new(BackendKind::Interpreter);
+    let mut b = Config::new(BackendKind::Jit);
+    let mut frames = 600;
+    let mut seed = 0;
+    let mut input = None;
+    let mut context = DEFAULT_CONTEXT;
+    let mut granularity = Granularity::Frame;
+    let mut rom = None;
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--a" => a = value().parse().unwrap_or_else(|e| fail(e)),
+            "--b" => b = value().parse().unwrap_or_else(|e| fail(e)),
+            "--frames" => frames = value().parse().unwrap_or_else(|_| usage()),
+            "--seed" => seed = value().parse().unwrap_or_else(|_| usage()),
+            "--input" => input = Some(PathBuf::from(value())),
+            "--context" => context = value().parse().unwrap_or_else(|_| usage()),
+            "--step" => granularity = Granularity::Instruction,
+            _ if arg.starts_with('-') || rom.is_some() => usage(),
+            _ => rom = Some(PathBuf::from(arg)),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+    Args { a, b, frames, seed, input, context, granularity, rom }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: rust8-diff [--a BACKEND[:QUIRKS]] [--b BACKEND[:QUIRKS]] [--frames N] [--seed N] [--input FILE] [--context N] [--step] ROM");
+    eprintln!("  BACKEND is interpreter, cached or jit and QUIRKS is rust8, chip8, modern, schip or xochip");
+    eprintln!("  --step compares after every instruction rather than every frame");
+    eprintln!("  the input file holds lines of FRAME MASK, holding the hex key mask from that frame on");
+    process::exit(2);
+}
+
+fn fail(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+fn main() {
+    let args = parse_args();
+    let rom = fs::read(&args.rom).unwrap_or_else(|e| fail(e));
+    let input = match &args.input {
+        Some(path) => Input::parse(&fs::read_to_string(path).unwrap_or_else(|e| fail(e))).unwrap_or_else(|e| fail(e)),
+        None => Input::default(),
+    };
+    let a = args.a.side(&rom, args.seed).unwrap_or_else(|e| fail(e));
+    let b = args.b.side(&rom, args.seed).unwrap_or_else(|e| fail(e));
+    let mut lockstep = Lockstep::new(a, b);
+    lockstep.context = args.context;
+    lockstep.granularity = args.granularity;
+
+    match lockstep.run(&input, args.frames) {
+        Ok(()) => println!("no divergence in {} frames ({} cycles)", lockstep.frame(), lockstep.cycle()),
+        Err(stop) => {
+            print!("{}", stop);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use rust8_core::backend::BackendKind;
+use rust8_diff::{Config, Difference, Granularity, Input, Lockstep, Stop};
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+fn lockstep(rom: &[u8], a: &str, b: &str) -> Lockstep {
+    let a: Config = a.parse().unwrap();
+    let b: Config = b.parse().unwrap();
+    Lockstep::new(a.side(rom, 3).unwrap(), b.side(rom, 3).unwrap())
+}
+
+const GRANULARITIES: [Granularity; 2] = [Granularity::Frame, Granularity::Instruction];
+
+#[test]
+fn backends_agree_on_maze() {
+    for &granularity in GRANULARITIES.iter() {
+        for &kind in &[BackendKind::Cached, BackendKind::Jit] {
+            let mut lockstep = lockstep(MAZE, "interpreter", kind.name());
+            lockstep.granularity = granularity;
+            assert!(lockstep.run(&Input::default(), 300).is_ok(), "{} diverged", kind);
+            assert_eq!(lockstep.frame(), 300);
+            assert_eq!(lockstep.cycle(), 300 * 15);
+        }
+    }
+}
+
+#[test]
+fn vblank_quirk_diverges_at_first_draw() {
+    let mut lockstep = lockstep(MAZE, "interpreter:rust8", "interpreter:chip8");
+    let divergence = match lockstep.run(&Input::default(), 10) {
+        Err(Stop::Diverged(divergence)) => divergence,
+        other => panic!("expected a divergence, got {:?}", other),
+    };
+    assert_eq!(divergence.frame, 0);
+    assert!(matches!(divergence.differences[..], [Difference::FrameEnd(_, false, true)]));
+    // the context window is centred on the draw
+    assert_eq!(divergence.context[1].address, 0x20C);
+    assert!(divergence.to_string().contains("> 0x20c  d014  DRW"));
+}
+
+#[test]
+fn memory_differences_are_reported() {
+    // V0 = 1 under shifting quirks that read Vy and 0 otherwise, then stored at 0x300
+    let rom = [
+        0x61, 0x02, // LD V1, 2
+        0x80, 0x16, // SHR V0, V1
+        0xA3, 0x00, // LD I, 0x300
+        0xF0, 0x55, // LD [I], V0
+        0x12, 0x08, // JP 0x208
+    ];
+    let mut lockstep = lockstep(&rom, "interpreter:chip8", "interpreter:schip");
+    let divergence = match lockstep.run(&Input::default(), 1) {
+        Err(Stop::Diverged(divergence)) => divergence,
+        other => panic!("expected a divergence, got {:?}", other),
+    };
+    assert_eq!(divergence.cycle, 1);
+    assert_eq!(divergence.context[0].address, 0x202);
+    assert!(divergence.differences.contains(&Difference::Register(0, 1, 0)));
+}
+
+#[test]
+fn whole_frames_are_stepped_through_to_find_the_cycle() {
+    // counts V2 up to 40 before shifting, so the quirks only disagree eight frames in
+    let rom = [
+        0x61, 0x02, // LD V1, 2
+        0x72, 0x01, // ADD V2, 1
+        0x32, 0x28, // SE V2, 40
+        0x12, 0x02, // JP 0x202
+        0x80, 0x16, // SHR V0, V1
+        0x12, 0x0A, // JP 0x20A
+    ];
+    for &granularity in GRANULARITIES.iter() {
+        let mut lockstep = lockstep(&rom, "jit:chip8", "jit:schip");
+        lockstep.granularity = granularity;
+        let divergence = match lockstep.run(&Input::default(), 10) {
+            Err(Stop::Diverged(divergence)) => divergence,
+            other => panic!("expected a divergence, got {:?}", other),
+        };
+        assert_eq!((divergence.frame, divergence.cycle), (8, 120), "{:?}", granularity);
+        assert_eq!(divergence.context[0].address, 0x208);
+    }
+}
+
+#[test]
+fn input_holds_keys_from_their_frame() {
+    let input = Input::parse("# frame mask\n\n10 0x20\n2 1  # key 0\n").unwrap();
+    assert_eq!(input.keys(0), 0);
+    assert_eq!(input.keys(2), 1);
+    assert_eq!(input.keys(9), 1);
+    assert_eq!(input.keys(10), 0x20);
+    assert!(Input::parse("5").is_err());
+    assert!(Input::parse("5 zz").is_err());
+}
+
+#[test]
+fn unknown_configs_are_errors() {
+    assert!("fast".parse::<Config>().is_err());
+    assert!("jit:chip9".parse::<Config>().is_err());
+    assert_eq!("cached:schip".parse::<Config>().unwrap().backend, BackendKind::Cached);
+}
@@ -0,0 +1,119 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rust8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rust8_core::emu::Emulator;
+use rust8_core::rng::Rng;
+use rust8_core::state::STATE_SIZE;
+
+use crate::error::GymError;
+use crate::spec::{EnvSpec, Reward};
+
+/// The display after a step, row by row
+pub type Observation = [bool; DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize];
+
+/// A rom wrapped up as a reinforcement learning environment
+pub struct Env {
+    emu: Emulator,
+    spec: EnvSpec,
+    /// keypad bitmask for each action
+    actions: Vec<u16>,
+    /// state to return to on reset
+    start: Box<[u8; STATE_SIZE]>,
+    /// value of the reward expression before the current step, for delta rewards
+    previous: i64,
+    frames: u64,
+}
+
+impl Env {
+    /// Wrap an emulator with a rom loaded and its quirks set up, every episode starts from its current state
+    pub fn new(emu: Emulator, spec: EnvSpec) -> Result<Self, GymError> {
+        if spec.actions.is_empty() {
+            return Err(GymError::NoActions);
+        }
+        if spec.frames_per_step == 0 {
+            return Err(GymError::NoFrames);
+        }
+        let mut actions = Vec::with_capacity(spec.actions.len());
+        for keys in &spec.actions {
+            let mut mask = 0u16;
+            for &key in keys {
+                if key >= 16 {
+                    return Err(GymError::InvalidKey(key));
+                }
+                mask |= 1 << key;
+            }
+            actions.push(mask);
+        }
+
+        let mut start = Box::new([0; STATE_SIZE]);
+        emu.save_state(&mut start[..]).expect("buffer is STATE_SIZE");
+        let mut env = Env { emu, spec, actions, start, previous: 0, frames: 0 };
+        let _ = env.reset(None);
+        Ok(env)
+    }
+
+    /// Number of actions, step takes an index below this
+    pub fn action_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Start a new episode, reseeding the random number generator if a seed is given
+    pub fn reset(&mut self, seed: Option<u64>) -> Observation {
+        self.emu.load_state(&self.start[..]).expect("state was saved by this emulator");
+        if let Some(seed) = seed {
+            self.emu.rng = Rng::from_seed(seed);
+        }
+        self.frames = 0;
+        self.previous = self.reward_value();
+        self.emu.display.vram
+    }
+
+    /// Hold the keys for an action for frames_per_step frames.
+    /// Returns the display, the reward and whether the episode is over.
+    pub fn step(&mut self, action: usize) -> Result<(Observation, f64, bool), GymError> {
+        let keys = *self.actions.get(action).ok_or(GymError::InvalidAction(action))?;
+        self.emu.keyboard.set_keypad(keys);
+        for _ in 0..self.spec.frames_per_step {
+            self.emu.run_frame();
+        }
+        self.frames += u64::from(self.spec.frames_per_step);
+
+        let value = self.reward_value();
+        let reward = match self.spec.reward {
+            Reward::Delta(_) => value - self.previous,
+            Reward::Value(_) => value,
+        };
+        self.previous = value;
+        let done = self.spec.done.holds(&self.emu) || self.spec.max_frames.is_some_and(|max| self.frames >= max);
+        Ok((self.emu.display.vram, reward as f64, done))
+    }
+
+    /// The emulator, for reading anything the observation leaves out
+    pub fn emulator(&self) -> &Emulator {
+        &self.emu
+    }
+
+    pub fn spec(&self) -> &EnvSpec {
+        &self.spec
+    }
+
+    /// an expression that divides by zero is worth nothing
+    fn reward_value(&self) -> i64 {
+        match &self.spec.reward {
+            Reward::Delta(expr) | Reward::Value(expr) => i64::from(expr.eval(&self.emu).unwrap_or(0)),
+        }
+    }
+}
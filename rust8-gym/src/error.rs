@@ -0,0 +1,41 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GymError {
+    /// an action holds a key outside 0-F
+    InvalidKey(u8),
+    /// step was given an action index past the end of the action list
+    InvalidAction(usize),
+    /// the spec has an empty action list
+    NoActions,
+    /// frames_per_step is 0
+    NoFrames,
+}
+
+impl fmt::Display for GymError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GymError::InvalidKey(key) => write!(f, "{:#x} is not a keypad key, expected 0-F", key),
+            GymError::InvalidAction(action) => write!(f, "there is no action {}", action),
+            GymError::NoActions => write!(f, "an environment needs at least one action"),
+            GymError::NoFrames => write!(f, "each step has to run at least one frame"),
+        }
+    }
+}
+
+impl std::error::Error for GymError {}
@@ -0,0 +1,62 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reinforcement learning environments on top of a headless emulator.
+//!
+//! An `EnvSpec` describes a game declaratively: which keys each action holds and how reward and
+//! the end of an episode are read out of memory with `rust8_core::expr` expressions, the same ones
+//! the debugger's breakpoints use. Specs deserialize from json or toml, so they can live alongside the roms.
+//!
+//! ```
+//! use rust8_core::emu::Emulator;
+//! use rust8_gym::{Env, EnvSpec};
+//!
+//! // counts up in V0, storing it as bcd at 0x300 with Fx33 each time around the three instruction loop
+//! let rom = [0x60, 0x00, 0xA3, 0x00, 0x70, 0x01, 0xF0, 0x33, 0x12, 0x04];
+//! let spec: EnvSpec = serde_json::from_str(r#"{
+//!     "reward": {"delta": "[0x300] * 100 + [0x301] * 10 + [0x302]"},
+//!     "done": "[0x302] >= 9"
+//! }"#).unwrap();
+//!
+//! let mut emu = Emulator::new();
+//! emu.load_rom(&rom).unwrap();
+//! let mut env = Env::new(emu, spec).unwrap();
+//! env.reset(Some(1));
+//! // 15 instructions a frame, two of them setting up the loop
+//! let (_observation, reward, done) = env.step(0).unwrap();
+//! assert_eq!((reward, done), (4.0, false));
+//! let (_observation, reward, done) = env.step(0).unwrap();
+//! assert_eq!((reward, done), (5.0, true));
+//! ```
+
+#![forbid(unsafe_code)]
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod env;
+mod error;
+mod spec;
+
+pub use crate::env::{Env, Observation};
+pub use crate::error::GymError;
+pub use crate::spec::{EnvSpec, Reward};
+pub use rust8_core::expr::Expr;
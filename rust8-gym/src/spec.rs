@@ -0,0 +1,78 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rust8_core::expr::Expr;
+use serde::{de, Deserialize, Deserializer};
+
+/// How a game is played as an environment, usually loaded from json or toml
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EnvSpec {
+    /// keypad keys held down for each action, by default doing nothing and then each key on its own
+    #[serde(default = "EnvSpec::default_actions")]
+    pub actions: Vec<Vec<u8>>,
+    pub reward: Reward,
+    /// the episode ends once this is nonzero
+    #[serde(default = "EnvSpec::never", deserialize_with = "expr")]
+    pub done: Expr,
+    /// frames run for each step with the action held
+    #[serde(default = "EnvSpec::one_frame")]
+    pub frames_per_step: u32,
+    /// cut an episode off after this many frames
+    #[serde(default)]
+    pub max_frames: Option<u64>,
+}
+
+impl EnvSpec {
+    /// a spec with the default actions that never ends on its own
+    pub fn new(reward: Reward) -> Self {
+        EnvSpec {
+            actions: EnvSpec::default_actions(),
+            reward,
+            done: EnvSpec::never(),
+            frames_per_step: EnvSpec::one_frame(),
+            max_frames: None,
+        }
+    }
+
+    fn default_actions() -> Vec<Vec<u8>> {
+        std::iter::once(Vec::new()).chain((0..16).map(|key| vec![key])).collect()
+    }
+
+    fn never() -> Expr {
+        Expr::Number(0)
+    }
+
+    fn one_frame() -> u32 {
+        1
+    }
+}
+
+/// Reward handed back from each step
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reward {
+    /// how much the expression changed over the step, such as a score
+    #[serde(deserialize_with = "expr")]
+    Delta(Expr),
+    /// the expression's value after the step
+    #[serde(deserialize_with = "expr")]
+    Value(Expr),
+}
+
+/// expressions are written as strings in the core's syntax, like `[0x2F0] * 10 + [0x2F1]`
+fn expr<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Expr, D::Error> {
+    let source = String::deserialize(deserializer)?;
+    source.parse().map_err(|e| de::Error::custom(format_args!("{} in {:?}", e, source)))
+}
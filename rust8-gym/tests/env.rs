@@ -0,0 +1,130 @@
+//! Stepping environments: actions, rewards, episode ends and resets
+
+use rust8_core::emu::Emulator;
+use rust8_gym::{Env, EnvSpec, Expr, GymError, Reward};
+
+/// counts up in V1 for every trip around the loop while key 5 is held, after six setup instructions
+const COUNTER: [u8; 18] = [
+    0x61, 0x00, 0x60, 0x05, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0x65, 0x00,
+    0xE0, 0xA1, 0x71, 0x01, 0x12, 0x0C,
+];
+/// a fresh random byte in V0 every instruction pair
+const RANDOM: [u8; 4] = [0xC0, 0xFF, 0x12, 0x00];
+
+fn emulator(rom: &[u8]) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.load_rom(rom).unwrap();
+    // a whole number of trips around the loop every frame whether the key is held or not
+    emu.tickrate = 18;
+    emu
+}
+
+fn expr(source: &str) -> Expr {
+    source.parse().unwrap()
+}
+
+fn spec(reward: Reward) -> EnvSpec {
+    EnvSpec { actions: vec![vec![], vec![5], vec![5, 0xF]], ..EnvSpec::new(reward) }
+}
+
+#[test]
+fn spec_is_checked_up_front() {
+    let bad = |spec: EnvSpec| Env::new(emulator(&COUNTER), spec).err();
+    let reward = Reward::Value(expr("0"));
+    assert_eq!(bad(EnvSpec { actions: vec![], ..EnvSpec::new(reward.clone()) }), Some(GymError::NoActions));
+    assert_eq!(bad(EnvSpec { frames_per_step: 0, ..EnvSpec::new(reward.clone()) }), Some(GymError::NoFrames));
+    assert_eq!(bad(EnvSpec { actions: vec![vec![3, 16]], ..EnvSpec::new(reward) }), Some(GymError::InvalidKey(16)));
+}
+
+#[test]
+fn actions_out_of_range_are_errors() {
+    let mut env = Env::new(emulator(&COUNTER), spec(Reward::Value(expr("V1")))).unwrap();
+    assert_eq!(env.action_count(), 3);
+    assert_eq!(env.step(3).err(), Some(GymError::InvalidAction(3)));
+    assert_eq!(env.step(usize::MAX).err(), Some(GymError::InvalidAction(usize::MAX)));
+    // nothing ran
+    assert_eq!(env.emulator().pc, 0x200);
+    assert!(env.step(2).is_ok());
+}
+
+#[test]
+fn actions_hold_their_keys() {
+    let mut env = Env::new(emulator(&COUNTER), spec(Reward::Value(expr("V1")))).unwrap();
+    let (_, reward, _) = env.step(0).unwrap();
+    assert_eq!(reward, 0.0);
+    assert_eq!(env.emulator().keyboard.keypad, [false; 16]);
+
+    let mut env = Env::new(emulator(&COUNTER), spec(Reward::Value(expr("V1")))).unwrap();
+    let (_, reward, _) = env.step(2).unwrap();
+    assert_eq!(reward, 4.0);
+    let keypad = env.emulator().keyboard.keypad;
+    assert_eq!((0..16).filter(|&key| keypad[key]).collect::<Vec<_>>(), [5, 0xF]);
+}
+
+#[test]
+fn value_and_delta_rewards() {
+    let mut value = Env::new(emulator(&COUNTER), spec(Reward::Value(expr("V1")))).unwrap();
+    let mut delta = Env::new(emulator(&COUNTER), spec(Reward::Delta(expr("V1")))).unwrap();
+    // 4 trips around the loop in the first frame with the key held, 6 after that and none without
+    let expected_value = [4.0, 10.0, 10.0, 16.0];
+    let expected_delta = [4.0, 6.0, 0.0, 6.0];
+    for (i, &action) in [1, 1, 0, 1].iter().enumerate() {
+        assert_eq!(value.step(action).unwrap().1, expected_value[i], "value step {}", i);
+        assert_eq!(delta.step(action).unwrap().1, expected_delta[i], "delta step {}", i);
+    }
+}
+
+#[test]
+fn episodes_end_on_done_or_max_frames() {
+    let done = expr("V1 >= 8");
+    let mut env = Env::new(emulator(&COUNTER), EnvSpec { done, ..spec(Reward::Value(expr("V1"))) }).unwrap();
+    assert!(!env.step(1).unwrap().2);
+    assert!(!env.step(0).unwrap().2);
+    assert!(env.step(1).unwrap().2);
+
+    let spec = EnvSpec { frames_per_step: 2, max_frames: Some(5), ..spec(Reward::Value(expr("0"))) };
+    let mut env = Env::new(emulator(&COUNTER), spec).unwrap();
+    assert!(!env.step(0).unwrap().2);
+    assert!(!env.step(0).unwrap().2);
+    assert!(env.step(0).unwrap().2);
+    // a reset starts the frame count over
+    let _ = env.reset(None);
+    assert!(!env.step(0).unwrap().2);
+}
+
+#[test]
+fn reset_returns_to_the_start() {
+    let mut env = Env::new(emulator(&COUNTER), spec(Reward::Delta(expr("V1")))).unwrap();
+    let start = env.reset(None);
+    for _ in 0..3 {
+        let _ = env.step(1).unwrap();
+    }
+    assert_eq!(env.emulator().registers[1], 16);
+
+    let observation = env.reset(None);
+    assert_eq!(observation[..], start[..]);
+    assert_eq!(env.emulator().registers[1], 0);
+    assert_eq!(env.emulator().pc, 0x200);
+    // delta rewards are measured from the reset, not from before it
+    assert_eq!(env.step(1).unwrap().1, 4.0);
+}
+
+#[test]
+fn seeded_resets_are_deterministic() {
+    let mut env = Env::new(emulator(&RANDOM), spec(Reward::Value(expr("V0")))).unwrap();
+    let mut episode = |seed| {
+        let _ = env.reset(Some(seed));
+        (0..8).map(|_| env.step(0).unwrap().1).collect::<Vec<_>>()
+    };
+    let first = episode(7);
+    assert_eq!(episode(7), first);
+    assert_ne!(episode(8), first);
+}
+
+#[test]
+fn rewards_that_divide_by_zero_are_worth_nothing() {
+    let mut env = Env::new(emulator(&COUNTER), spec(Reward::Value(expr("100 / (V1 - 4)")))).unwrap();
+    // V1 is 4 after the first step
+    assert_eq!(env.step(1).unwrap().1, 0.0);
+    assert_eq!(env.step(1).unwrap().1, 16.0);
+}
@@ -0,0 +1,68 @@
+//! Reading specs and their expressions out of json
+
+use rust8_core::emu::Emulator;
+use rust8_gym::{EnvSpec, Expr, Reward};
+
+fn expr(source: &str) -> Expr {
+    source.parse().unwrap()
+}
+
+#[test]
+fn spec_defaults() {
+    let spec: EnvSpec = serde_json::from_str(r#"{"reward": {"value": "V3"}}"#).unwrap();
+    assert_eq!(spec, EnvSpec::new(Reward::Value(expr("V3"))));
+    assert_eq!(spec.actions.len(), 17);
+    assert_eq!(spec.actions[0], Vec::<u8>::new());
+    assert_eq!(spec.actions[0xB], [0xA]);
+    assert_eq!(spec.done, expr("0"));
+    assert_eq!(spec.frames_per_step, 1);
+    assert_eq!(spec.max_frames, None);
+}
+
+#[test]
+fn spec_with_everything() {
+    let spec: EnvSpec = serde_json::from_str(r#"{
+        "actions": [[], [4], [6, 5]],
+        "reward": {"delta": "[0x2F0] * 100 + [0x2F1] * 10 + [0x2F2]"},
+        "done": "![0x300]",
+        "frames_per_step": 4,
+        "max_frames": 3600
+    }"#).unwrap();
+    assert_eq!(spec.actions, [vec![], vec![4], vec![6, 5]]);
+    assert_eq!(spec.reward, Reward::Delta(expr("[752] * 100 + [753] * 10 + [754]")));
+    assert_eq!(spec.done, expr("![768]"));
+    assert_eq!(spec.frames_per_step, 4);
+    assert_eq!(spec.max_frames, Some(3600));
+}
+
+#[test]
+fn spec_expressions_read_the_emulator() {
+    let spec: EnvSpec = serde_json::from_str(r#"{
+        "reward": {"value": "[0x300] * 100 + [0x301] * 10 + [0x302]"},
+        "done": "V2 == 0x42 && [I] != 0"
+    }"#).unwrap();
+    let mut emu = Emulator::new();
+    emu.memory[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+    emu.registers[2] = 0x42;
+    emu.index = 0x302;
+    match &spec.reward {
+        Reward::Value(reward) => assert_eq!(reward.eval(&emu), Some(123)),
+        Reward::Delta(_) => panic!("read a value reward as a delta"),
+    }
+    assert!(spec.done.holds(&emu));
+    emu.index = 0x303;
+    assert!(!spec.done.holds(&emu));
+}
+
+#[test]
+fn malformed_specs_are_rejected() {
+    let error = |json: &str| serde_json::from_str::<EnvSpec>(json).unwrap_err().to_string();
+    // reward is required
+    assert!(error(r#"{"done": "1"}"#).contains("reward"));
+    assert!(error(r#"{"reward": {"total": "1"}}"#).contains("total"));
+    assert!(error(r#"{"reward": {"value": 1}}"#).contains("expected a string"));
+    assert!(error(r#"{"reward": {"value": "1"}, "actions": [[256]]}"#).contains("256"));
+    // parse errors say where, in which expression
+    assert!(error(r#"{"reward": {"value": "V1 +"}}"#).contains(r#"expected a value at column 5 in "V1 +""#));
+    assert!(error(r#"{"reward": {"value": "1"}, "done": "VG == 0"}"#).contains("unknown register at column 1"));
+}
@@ -0,0 +1,423 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A libretro core wrapping rust8-core, build with `cargo build -p rust8-libretro --release`
+//! and load `librust8_libretro.so` in RetroArch or any other libretro frontend.
+//!
+//! The 16 keypad keys are spread over the RetroPad buttons, see `KEYPAD`. Quirks and speed come
+//! from the rom database unless they're overridden in the core options.
+
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+mod libretro;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+use rust8_core::constants::{DEFAULT_TICKRATE, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rust8_core::emu::Emulator;
+use rust8_core::quirks::Quirks;
+use rust8_core::state::STATE_SIZE;
+use rust8_db::{Database, RomInfo};
+
+use crate::libretro::*;
+
+const FPS: f64 = 60.0;
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / 60) as usize;
+const BUZZER_HZ: u32 = 440;
+const BUZZER_VOLUME: i16 = 0x1000;
+
+/// RetroPad button, keypad key and the description shown by the frontend
+const KEYPAD: [(c_uint, u8, &[u8]); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, b"Key 2 (up)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, b"Key 8 (down)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, b"Key 4 (left)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, b"Key 6 (right)\0"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, b"Key 5\0"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, b"Key 0\0"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x1, b"Key 1\0"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x3, b"Key 3\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x7, b"Key 7\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x9, b"Key 9\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0xA, b"Key A\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0xB, b"Key B\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xC, b"Key C\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xD, b"Key D\0"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, b"Key E\0"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, b"Key F\0"),
+];
+
+const QUIRKS_OPTION: &[u8] = b"rust8_quirks\0";
+const TICKRATE_OPTION: &[u8] = b"rust8_tickrate\0";
+
+/// "auto" in either option means use the rom database, falling back to the core's defaults
+const OPTIONS: [(&[u8], &[u8]); 2] = [
+    (QUIRKS_OPTION, b"Quirks; auto|rust8|chip8|modern|schip|xochip\0"),
+    (TICKRATE_OPTION, b"Instructions per frame; auto|8|10|12|15|20|30|50|100|200|500|1000\0"),
+];
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<retro_environment_t>,
+    video_refresh: Option<retro_video_refresh_t>,
+    audio_sample_batch: Option<retro_audio_sample_batch_t>,
+    input_poll: Option<retro_input_poll_t>,
+    input_state: Option<retro_input_state_t>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// The loaded game
+struct Core {
+    emu: Emulator,
+    rom: Vec<u8>,
+    info: Option<RomInfo>,
+    framebuffer: [u32; 64 * 32],
+    audio: [i16; SAMPLES_PER_FRAME * 2],
+    /// buzzer square wave position in samples
+    phase: u32,
+}
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+impl Core {
+    fn new(rom: Vec<u8>) -> Option<Core> {
+        let info = Database::bundled().lookup(&rom);
+        let mut core = Core {
+            emu: Emulator::new(),
+            rom,
+            info,
+            framebuffer: [0; 64 * 32],
+            audio: [0; SAMPLES_PER_FRAME * 2],
+            phase: 0,
+        };
+        core.reset().ok()?;
+        Some(core)
+    }
+
+    fn reset(&mut self) -> Result<(), rust8_core::error::Error> {
+        self.emu = Emulator::new();
+        self.emu.load_rom(&self.rom)?;
+        self.apply_options();
+        Ok(())
+    }
+
+    /// the rom database's settings with the frontend's options on top, starting over from the
+    /// core's defaults so an option set back to auto drops the value it had
+    fn apply_options(&mut self) {
+        self.emu.quirks = Quirks::default();
+        self.emu.tickrate = DEFAULT_TICKRATE;
+        if let Some(info) = &self.info {
+            info.apply(&mut self.emu);
+        }
+        if let Some(quirks) = get_variable(QUIRKS_OPTION).as_deref().and_then(Quirks::from_name) {
+            self.emu.quirks = quirks;
+        }
+        if let Some(tickrate) = get_variable(TICKRATE_OPTION).and_then(|value| value.parse().ok()) {
+            self.emu.tickrate = tickrate;
+        }
+    }
+
+    fn run(&mut self) {
+        // copied out so a frontend can call back into the core from its callbacks
+        let callbacks = *CALLBACKS.lock().unwrap();
+        let mut environment_changed = false;
+        if let Some(environment) = callbacks.environment {
+            unsafe {
+                let _ = environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, ptr::addr_of_mut!(environment_changed) as *mut c_void);
+            }
+        }
+
+        if let (Some(input_poll), Some(input_state)) = (callbacks.input_poll, callbacks.input_state) {
+            unsafe { input_poll() };
+            for &(button, key, _) in KEYPAD.iter() {
+                let down = unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, button) } != 0;
+                if down && !self.emu.keyboard.keypad[key as usize] {
+                    self.emu.keyboard.press_key(key);
+                } else if !down && self.emu.keyboard.keypad[key as usize] {
+                    self.emu.keyboard.release_key(key);
+                }
+            }
+        }
+
+        self.emu.run_frame();
+
+        let vram = self.emu.display.receive_change();
+        for (pixel, &set) in self.framebuffer.iter_mut().zip(vram.iter()) {
+            *pixel = if set { 0x00ff_ffff } else { 0 };
+        }
+        if let Some(video_refresh) = callbacks.video_refresh {
+            unsafe {
+                video_refresh(self.framebuffer.as_ptr() as *const c_void, DISPLAY_WIDTH as c_uint, DISPLAY_HEIGHT as c_uint, DISPLAY_WIDTH as usize * 4);
+            }
+        }
+
+        self.fill_audio();
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            unsafe {
+                let _ = audio_sample_batch(self.audio.as_ptr(), SAMPLES_PER_FRAME);
+            }
+        }
+
+        if environment_changed {
+            self.apply_options();
+        }
+    }
+
+    /// a square wave while the sound timer runs, silence otherwise
+    fn fill_audio(&mut self) {
+        let half_period = SAMPLE_RATE / BUZZER_HZ / 2;
+        let buzzing = self.emu.sound > 0;
+        for frame in self.audio.chunks_exact_mut(2) {
+            let sample = match (buzzing, (self.phase / half_period) % 2) {
+                (false, _) => 0,
+                (true, 0) => BUZZER_VOLUME,
+                (true, _) => -BUZZER_VOLUME,
+            };
+            frame[0] = sample;
+            frame[1] = sample;
+            self.phase = self.phase.wrapping_add(1);
+        }
+    }
+}
+
+/// ask the frontend for the value of a core option
+fn get_variable(key: &[u8]) -> Option<String> {
+    let environment = CALLBACKS.lock().unwrap().environment?;
+    let mut variable = retro_variable { key: key.as_ptr() as *const c_char, value: ptr::null() };
+    unsafe {
+        if !environment(RETRO_ENVIRONMENT_GET_VARIABLE, ptr::addr_of_mut!(variable) as *mut c_void) || variable.value.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(variable.value).to_string_lossy().into_owned())
+    }
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    let environment = CALLBACKS.lock().unwrap().environment;
+    match environment {
+        Some(environment) => unsafe { environment(cmd, data) },
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+/// info must point to a writable retro_system_info
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
+    *info = retro_system_info {
+        library_name: b"rust8\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|c8\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// info must point to a writable retro_system_av_info
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
+    *info = retro_system_av_info {
+        geometry: retro_game_geometry {
+            base_width: DISPLAY_WIDTH as c_uint,
+            base_height: DISPLAY_HEIGHT as c_uint,
+            max_width: DISPLAY_WIDTH as c_uint,
+            max_height: DISPLAY_HEIGHT as c_uint,
+            aspect_ratio: DISPLAY_WIDTH as f32 / DISPLAY_HEIGHT as f32,
+        },
+        timing: retro_system_timing { fps: FPS, sample_rate: SAMPLE_RATE as f64 },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: retro_environment_t) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+    let mut variables: Vec<retro_variable> = OPTIONS.iter()
+        .map(|(key, value)| retro_variable { key: key.as_ptr() as *const c_char, value: value.as_ptr() as *const c_char })
+        .collect();
+    variables.push(retro_variable { key: ptr::null(), value: ptr::null() });
+    let _ = environment(RETRO_ENVIRONMENT_SET_VARIABLES, variables.as_mut_ptr() as *mut c_void);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: retro_video_refresh_t) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+/// single samples aren't used, audio always goes through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: retro_audio_sample_t) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: retro_audio_sample_batch_t) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: retro_input_poll_t) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: retro_input_state_t) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap() = None;
+}
+
+/// # Safety
+/// game must be null or point to a retro_game_info whose data is valid for size bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let rom = slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+
+    let mut pixel_format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, ptr::addr_of_mut!(pixel_format) as *mut c_void) {
+        return false;
+    }
+    let mut descriptors: Vec<retro_input_descriptor> = KEYPAD.iter()
+        .map(|&(button, _, description)| retro_input_descriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: button,
+            description: description.as_ptr() as *const c_char,
+        })
+        .collect();
+    descriptors.push(retro_input_descriptor { port: 0, device: 0, index: 0, id: 0, description: ptr::null() });
+    let _ = environment(RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS, descriptors.as_mut_ptr() as *mut c_void);
+
+    match Core::new(rom) {
+        Some(core) => {
+            *CORE.lock().unwrap() = Some(core);
+            true
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_game_type: c_uint, _info: *const retro_game_info, _num_info: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        // the rom loaded once already so it still fits
+        let _ = core.reset();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.run();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+/// # Safety
+/// data must be null or valid for writes of size bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    match CORE.lock().unwrap().as_ref() {
+        Some(core) if !data.is_null() => core.emu.save_state(slice::from_raw_parts_mut(data as *mut u8, size)).is_ok(),
+        _ => false,
+    }
+}
+
+/// # Safety
+/// data must be null or valid for reads of size bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    match CORE.lock().unwrap().as_mut() {
+        Some(core) if !data.is_null() => core.emu.load_state(slice::from_raw_parts(data as *const u8, size)).is_ok(),
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// system ram is the whole 4k address space, which lets frontends implement cheats and achievements
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match CORE.lock().unwrap().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.emu.memory.as_mut_ptr() as *mut c_void,
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match CORE.lock().unwrap().as_ref() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.emu.memory.len(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,291 @@
+//! A tiny libretro frontend that loads the built core the same way RetroArch would
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use libloading::{Library, Symbol};
+use rust8_core::emu::Emulator;
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+const SET_PIXEL_FORMAT: c_uint = 10;
+const GET_VARIABLE: c_uint = 15;
+const SET_VARIABLES: c_uint = 16;
+const GET_VARIABLE_UPDATE: c_uint = 17;
+/// RETRO_DEVICE_ID_JOYPAD_A, keypad key 5
+const JOYPAD_A: u16 = 8;
+
+#[repr(C)]
+struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct Variable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+/// what the core has handed to the frontend so far
+#[derive(Default)]
+struct Frontend {
+    options: Vec<String>,
+    frame: Vec<u32>,
+    frames: usize,
+    audio_frames: usize,
+    pad: u16,
+    /// values handed back for rust8_quirks and rust8_tickrate, nul terminated
+    quirks: Option<&'static [u8]>,
+    tickrate: Option<&'static [u8]>,
+    /// whether GET_VARIABLE_UPDATE reports a change
+    options_changed: bool,
+}
+
+static FRONTEND: Mutex<Option<Frontend>> = Mutex::new(None);
+/// the core is a process wide singleton so tests take turns
+static CORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn frontend<T>(f: impl FnOnce(&mut Frontend) -> T) -> T {
+    f(FRONTEND.lock().unwrap().get_or_insert_with(Frontend::default))
+}
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        SET_PIXEL_FORMAT => true,
+        SET_VARIABLES => {
+            let mut variable = data as *const Variable;
+            while !(*variable).key.is_null() {
+                let key = CStr::from_ptr((*variable).key).to_string_lossy().into_owned();
+                frontend(|frontend| frontend.options.push(key));
+                variable = variable.add(1);
+            }
+            true
+        }
+        GET_VARIABLE => {
+            let variable = data as *mut Variable;
+            let value = frontend(|frontend| match CStr::from_ptr((*variable).key).to_bytes() {
+                b"rust8_quirks" => frontend.quirks,
+                b"rust8_tickrate" => frontend.tickrate,
+                _ => None,
+            });
+            match value {
+                Some(value) => {
+                    (*variable).value = value.as_ptr() as *const c_char;
+                    true
+                }
+                None => false,
+            }
+        }
+        GET_VARIABLE_UPDATE => {
+            *(data as *mut bool) = frontend(|frontend| std::mem::take(&mut frontend.options_changed));
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    assert_eq!((width, height, pitch), (64, 32, 64 * 4));
+    let pixels = std::slice::from_raw_parts(data as *const u32, 64 * 32).to_vec();
+    frontend(|frontend| {
+        frontend.frame = pixels;
+        frontend.frames += 1;
+    });
+}
+
+unsafe extern "C" fn audio_sample(_left: i16, _right: i16) {}
+
+unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    frontend(|frontend| frontend.audio_frames += frames);
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+/// retro_set_input_poll, for a poll callback that calls back into the core
+static SET_INPUT_POLL: Mutex<Option<unsafe extern "C" fn(unsafe extern "C" fn())>> = Mutex::new(None);
+
+unsafe extern "C" fn reentrant_input_poll() {
+    let set_input_poll = SET_INPUT_POLL.lock().unwrap().expect("set before running");
+    set_input_poll(input_poll);
+}
+
+unsafe extern "C" fn input_state(_port: c_uint, _device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    frontend(|frontend| (frontend.pad >> id) as i16 & 1)
+}
+
+struct Core {
+    library: Library,
+}
+
+impl Core {
+    /// cargo builds the cdylib into the deps directory next to the test binary,
+    /// `cargo build` also copies it up into the profile directory
+    fn load() -> Core {
+        let name = format!("{}rust8_libretro{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX);
+        let deps = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+        let path: PathBuf = [deps.join(&name), deps.parent().unwrap().join(&name)]
+            .iter()
+            .find(|path| path.exists())
+            .cloned()
+            .unwrap_or_else(|| deps.join(&name));
+        let library = unsafe { Library::new(&path) }.unwrap_or_else(|e| panic!("failed to load {}: {}", path.display(), e));
+        *FRONTEND.lock().unwrap() = Some(Frontend::default());
+        let core = Core { library };
+        unsafe {
+            core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn(c_uint, *mut c_void) -> bool)>(b"retro_set_environment")(environment);
+            core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn(*const c_void, c_uint, c_uint, usize))>(b"retro_set_video_refresh")(video_refresh);
+            core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn(i16, i16))>(b"retro_set_audio_sample")(audio_sample);
+            core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn(*const i16, usize) -> usize)>(b"retro_set_audio_sample_batch")(audio_sample_batch);
+            core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn())>(b"retro_set_input_poll")(input_poll);
+            core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn(c_uint, c_uint, c_uint, c_uint) -> i16)>(b"retro_set_input_state")(input_state);
+            core.symbol::<unsafe extern "C" fn()>(b"retro_init")();
+        }
+        core
+    }
+
+    unsafe fn symbol<T>(&self, name: &[u8]) -> Symbol<'_, T> {
+        self.library.get(name).unwrap()
+    }
+
+    fn load_game(&self, rom: &[u8]) -> bool {
+        let game = GameInfo { path: std::ptr::null(), data: rom.as_ptr() as *const c_void, size: rom.len(), meta: std::ptr::null() };
+        unsafe { self.symbol::<unsafe extern "C" fn(*const GameInfo) -> bool>(b"retro_load_game")(&game) }
+    }
+
+    fn run(&self, frames: usize) {
+        for _ in 0..frames {
+            unsafe { self.symbol::<unsafe extern "C" fn()>(b"retro_run")() };
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        unsafe {
+            let size = self.symbol::<unsafe extern "C" fn() -> usize>(b"retro_serialize_size")();
+            let mut state = vec![0u8; size];
+            assert!(self.symbol::<unsafe extern "C" fn(*mut c_void, usize) -> bool>(b"retro_serialize")(state.as_mut_ptr() as *mut c_void, size));
+            state
+        }
+    }
+
+    fn unserialize(&self, state: &[u8]) -> bool {
+        unsafe { self.symbol::<unsafe extern "C" fn(*const c_void, usize) -> bool>(b"retro_unserialize")(state.as_ptr() as *const c_void, state.len()) }
+    }
+}
+
+impl Drop for Core {
+    fn drop(&mut self) {
+        unsafe {
+            self.symbol::<unsafe extern "C" fn()>(b"retro_unload_game")();
+            self.symbol::<unsafe extern "C" fn()>(b"retro_deinit")();
+        }
+    }
+}
+
+#[test]
+fn runs_maze() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let core = Core::load();
+    assert!(core.load_game(MAZE));
+    core.run(120);
+
+    frontend(|frontend| {
+        assert_eq!(frontend.options, ["rust8_quirks", "rust8_tickrate"]);
+        assert_eq!(frontend.frames, 120);
+        assert_eq!(frontend.audio_frames, 120 * 735);
+        assert!(frontend.frame.contains(&0x00ff_ffff));
+    });
+}
+
+#[test]
+fn serialize_round_trips() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let core = Core::load();
+    assert!(core.load_game(MAZE));
+    core.run(10);
+
+    let state = core.serialize();
+    core.run(30);
+    let expected = frontend(|frontend| frontend.frame.clone());
+
+    assert!(core.unserialize(&state));
+    core.run(30);
+    assert_eq!(frontend(|frontend| frontend.frame.clone()), expected);
+    assert!(!core.unserialize(&state[..state.len() - 1]));
+}
+
+#[test]
+fn rejects_roms_that_dont_fit() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let core = Core::load();
+    assert!(!core.load_game(&[0; 4096]));
+}
+
+#[test]
+fn callbacks_can_reenter_the_core() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let core = Core::load();
+    assert!(core.load_game(MAZE));
+    unsafe {
+        let set_input_poll = *core.symbol::<unsafe extern "C" fn(unsafe extern "C" fn())>(b"retro_set_input_poll");
+        *SET_INPUT_POLL.lock().unwrap() = Some(set_input_poll);
+        set_input_poll(reentrant_input_poll);
+    }
+    core.run(2);
+    assert_eq!(frontend(|frontend| frontend.frames), 2);
+}
+
+/// the registers a running core's save state holds
+fn registers(core: &Core) -> [u8; 16] {
+    let mut emu = Emulator::new();
+    emu.load_state(&core.serialize()).unwrap();
+    emu.registers
+}
+
+/// shifts V1 into V0 with 8xy6 and counts trips around the five instruction loop in V2, a rom
+/// that isn't in the database
+const SHIFT_COUNTER: [u8; 10] = [0x61, 0x04, 0x60, 0x01, 0x80, 0x16, 0x72, 0x01, 0x12, 0x00];
+
+#[test]
+fn options_apply_and_go_back_to_the_defaults() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let core = Core::load();
+    frontend(|frontend| {
+        frontend.quirks = Some(b"chip8\0");
+        frontend.tickrate = Some(b"30\0");
+    });
+    assert!(core.load_game(&SHIFT_COUNTER));
+    core.run(1);
+    // six trips in 30 instructions, with chip-8 shifting V1 rather than V0
+    assert_eq!(registers(&core)[..3], [2, 4, 6]);
+
+    frontend(|frontend| {
+        frontend.quirks = Some(b"auto\0");
+        frontend.tickrate = Some(b"auto\0");
+        frontend.options_changed = true;
+    });
+    // options change once the frame that saw the update is over
+    core.run(1);
+    assert_eq!(registers(&core)[2], 12);
+    core.run(1);
+    // back to the default 15 instructions and shifting V0 in place
+    assert_eq!(registers(&core)[..3], [0, 4, 15]);
+}
+
+#[test]
+fn joypad_buttons_press_keypad_keys() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let core = Core::load();
+    // LD V3, K and then spin
+    assert!(core.load_game(&[0xF3, 0x0A, 0x12, 0x02]));
+    core.run(2);
+    assert_eq!(registers(&core)[3], 0);
+    frontend(|frontend| frontend.pad = 1 << JOYPAD_A);
+    core.run(1);
+    assert_eq!(registers(&core)[3], 5);
+}
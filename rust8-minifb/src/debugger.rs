@@ -0,0 +1,378 @@
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use rust8_core::debug::Session;
+use rust8_core::emu::Emulator;
+use rust8_core::expr::{Expr, Register};
+
+use crate::font::{Canvas, CELL_HEIGHT, CELL_WIDTH};
+
+const SCALE: usize = 2;
+const COLUMNS: usize = 80;
+const LINES: usize = 42;
+const WIDTH: usize = COLUMNS * CELL_WIDTH * SCALE;
+const HEIGHT: usize = LINES * CELL_HEIGHT * SCALE;
+
+/// rows of the hex dump shown at once, 16 bytes to a row
+const ROWS: usize = 32;
+const DUMP_LINE: usize = 2;
+/// column of a row's first byte, after its address
+const DUMP_COLUMN: usize = 5;
+const PANEL_COLUMN: usize = 56;
+const WATCH_LINE: usize = 31;
+/// characters of a watch's expression shown before its value
+const WATCH_LABEL: usize = 14;
+/// frames a written byte stays highlighted for
+const WRITE_FADE: u8 = 30;
+
+const BACKGROUND: u32 = 0x10_10_10;
+const TEXT: u32 = 0xC0_C0_C0;
+const DIM: u32 = 0x50_50_50;
+const PC: u32 = 0x20_50_A0;
+const INDEX: u32 = 0x20_80_40;
+const SELECTED: u32 = 0xA0_90_20;
+
+/// the cosmac vip keypad as it's laid out
+const KEYPAD: [[u8; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+
+/// Something in the emulator the debugger can edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Memory(u16),
+    Register(u8),
+    Index,
+    Pc,
+    Sp,
+    Delay,
+    Sound,
+}
+
+impl Field {
+    fn mask(self) -> u16 {
+        match self {
+            Field::Index | Field::Pc => 0xFFF,
+            Field::Sp => 0xF,
+            _ => 0xFF,
+        }
+    }
+
+    fn get(self, emu: &Emulator) -> u16 {
+        match self {
+            Field::Memory(address) => u16::from(emu.memory[address as usize]),
+            Field::Register(x) => u16::from(emu.registers[x as usize]),
+            Field::Index => emu.index,
+            Field::Pc => emu.pc,
+            Field::Sp => u16::from(emu.sp),
+            Field::Delay => u16::from(emu.delay),
+            Field::Sound => u16::from(emu.sound),
+        }
+    }
+
+    fn set(self, emu: &mut Emulator, value: u16) {
+        let value = value & self.mask();
+        match self {
+            Field::Memory(address) => emu.memory[address as usize] = value as u8,
+            Field::Register(x) => emu.registers[x as usize] = value as u8,
+            Field::Index => emu.index = value,
+            Field::Pc => emu.pc = value,
+            Field::Sp => emu.sp = value as u8,
+            Field::Delay => emu.delay = value as u8,
+            Field::Sound => emu.sound = value as u8,
+        }
+    }
+
+    /// an expression for the field's value, to watch it
+    fn expr(self) -> Expr {
+        let register = match self {
+            Field::Memory(address) => return Expr::Memory(Box::new(Expr::Number(u32::from(address)))),
+            Field::Register(x) => Register::V(x),
+            Field::Index => Register::I,
+            Field::Pc => Register::Pc,
+            Field::Sp => Register::Sp,
+            Field::Delay => Register::Dt,
+            Field::Sound => Register::St,
+        };
+        Expr::Register(register)
+    }
+}
+
+/// where the panel draws each field besides memory, as (field, label, column, line)
+fn panel() -> Vec<(Field, String, usize, usize)> {
+    let mut fields = vec![
+        (Field::Pc, "PC".to_string(), PANEL_COLUMN, 2),
+        (Field::Index, "I".to_string(), PANEL_COLUMN, 3),
+        (Field::Sp, "SP".to_string(), PANEL_COLUMN, 4),
+        (Field::Delay, "DT".to_string(), PANEL_COLUMN + 9, 2),
+        (Field::Sound, "ST".to_string(), PANEL_COLUMN + 9, 3),
+    ];
+    fields.extend((0..16u8).map(|x| (Field::Register(x), format!("V{:X}", x), PANEL_COLUMN + 9 * (x as usize / 8), 6 + x as usize % 8)));
+    fields
+}
+
+/// A second window showing the emulator's memory, registers, stack, timers and keypad.
+/// While paused, click a byte or a register and type hex digits to change it, or press backspace
+/// to go back to the instruction that last wrote it.
+pub struct Debugger {
+    window: Window,
+    pixels: Vec<u32>,
+    /// first row of the hex dump
+    top: usize,
+    /// keep pc's row in view
+    follow_pc: bool,
+    selected: Option<Field>,
+    /// shown beside PAUSED, like why going back stopped
+    note: &'static str,
+    /// the mouse button was down last update
+    clicking: bool,
+    /// memory as of the last update, to see what changed
+    previous: Box<[u8; 4096]>,
+    /// frames left to highlight each address as written
+    written: Box<[u8; 4096]>,
+}
+
+impl Debugger {
+    pub fn open(emu: &Emulator) -> minifb::Result<Self> {
+        let mut window = Window::new("Rusty_Chip8 - debugger", WIDTH, HEIGHT, WindowOptions::default())?;
+        // the main window already waits for the next frame
+        window.limit_update_rate(None);
+        Ok(Debugger {
+            window,
+            pixels: vec![BACKGROUND; WIDTH * HEIGHT],
+            top: 0x20,
+            follow_pc: true,
+            selected: None,
+            note: "",
+            clicking: false,
+            previous: Box::new(emu.memory),
+            written: Box::new([0; 4096]),
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    /// Handle the debugger window's input, applying edits to emu and toggling paused, then redraw it
+    /// with the values of watches, which are each an expression and its source
+    pub fn update(&mut self, emu: &mut Emulator, session: &mut Session, watches: &[(String, Expr)], paused: &mut bool) {
+        for (address, (&byte, previous)) in emu.memory.iter().zip(self.previous.iter_mut()).enumerate() {
+            if byte != *previous {
+                *previous = byte;
+                self.written[address] = WRITE_FADE;
+            } else {
+                self.written[address] = self.written[address].saturating_sub(1);
+            }
+        }
+        self.handle_input(emu, session, paused);
+        if self.follow_pc {
+            self.scroll_to(emu.pc as usize & 0xFFF);
+        }
+        self.draw(emu, watches, *paused);
+        let _ = self.window.update_with_buffer(&self.pixels, WIDTH, HEIGHT);
+    }
+
+    fn handle_input(&mut self, emu: &mut Emulator, session: &mut Session, paused: &mut bool) {
+        if self.window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            *paused = !*paused;
+        }
+        if !*paused {
+            self.selected = None;
+            self.note = "";
+        }
+        if self.window.is_key_pressed(Key::Home, KeyRepeat::No) {
+            self.follow_pc = true;
+        }
+        let mut scroll = 0;
+        if let Some((_, y)) = self.window.get_scroll_wheel() {
+            scroll -= y.signum() as isize;
+        }
+        if self.window.is_key_pressed(Key::PageUp, KeyRepeat::Yes) {
+            scroll -= ROWS as isize;
+        }
+        if self.window.is_key_pressed(Key::PageDown, KeyRepeat::Yes) {
+            scroll += ROWS as isize;
+        }
+        if scroll != 0 {
+            self.follow_pc = false;
+            self.top = (self.top as isize + scroll).clamp(0, (256 - ROWS) as isize) as usize;
+        }
+
+        let down = self.window.get_mouse_down(MouseButton::Left);
+        if down && !self.clicking && *paused {
+            if let Some((x, y)) = self.window.get_mouse_pos(MouseMode::Discard) {
+                let (column, line) = (x as usize / (CELL_WIDTH * SCALE), y as usize / (CELL_HEIGHT * SCALE));
+                self.selected = self.field_at(column, line);
+                self.note = "";
+            }
+        }
+        self.clicking = down;
+
+        if self.selected.is_none() {
+            return;
+        }
+        for key in self.window.get_keys_pressed(KeyRepeat::Yes).unwrap_or_default() {
+            let selected = match self.selected {
+                Some(field) => field,
+                None => return,
+            };
+            if let Some(digit) = hex_digit(key) {
+                // digits shift in from the right, so typing a whole value replaces the old one
+                selected.set(emu, selected.get(emu) << 4 | digit);
+                if let Field::Memory(address) = selected {
+                    self.previous[address as usize] = emu.memory[address as usize];
+                }
+                session.edited(emu);
+                continue;
+            }
+            if key == Key::Backspace {
+                self.note = if session.position().is_none() {
+                    "NO HISTORY"
+                } else if session.reverse_to_last_write(emu, &selected.expr()) {
+                    ""
+                } else {
+                    "NO EARLIER WRITE"
+                };
+                continue;
+            }
+            let step = match key {
+                Key::Left => -1,
+                Key::Right => 1,
+                Key::Up => -16,
+                Key::Down => 16,
+                Key::Enter | Key::Escape => {
+                    self.selected = None;
+                    return;
+                }
+                _ => continue,
+            };
+            if let Field::Memory(address) = selected {
+                let address = (address as isize + step).rem_euclid(4096) as usize;
+                self.selected = Some(Field::Memory(address as u16));
+                self.follow_pc = false;
+                self.scroll_to(address);
+            }
+        }
+    }
+
+    /// scroll just enough to show the row holding address
+    fn scroll_to(&mut self, address: usize) {
+        let row = address / 16;
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + ROWS {
+            self.top = (row + 1 - ROWS).min(256 - ROWS);
+        }
+    }
+
+    fn field_at(&self, column: usize, line: usize) -> Option<Field> {
+        if (DUMP_LINE..DUMP_LINE + ROWS).contains(&line) && column >= DUMP_COLUMN {
+            let (byte, offset) = ((column - DUMP_COLUMN) / 3, (column - DUMP_COLUMN) % 3);
+            if byte < 16 && offset < 2 {
+                return Some(Field::Memory(((self.top + line - DUMP_LINE) * 16 + byte) as u16));
+            }
+        }
+        panel().into_iter()
+            .find(|&(_, _, left, top)| top == line && (left + 3..left + 6).contains(&column))
+            .map(|(field, ..)| field)
+    }
+
+    fn draw(&mut self, emu: &Emulator, watches: &[(String, Expr)], paused: bool) {
+        let selected = self.selected;
+        let (top, written) = (self.top, &self.written);
+        let mut canvas = Canvas { pixels: &mut self.pixels, width: WIDTH, scale: SCALE };
+        canvas.fill(0, 0, COLUMNS * CELL_WIDTH, LINES * CELL_HEIGHT, BACKGROUND);
+
+        canvas.text(0, 0, if paused { "PAUSED" } else { "RUNNING" }, TEXT);
+        canvas.text(8, 0, self.note, SELECTED);
+        let header: String = (0..16).map(|i| format!("{:02X} ", i)).collect();
+        canvas.text(DUMP_COLUMN, DUMP_LINE - 1, &header, DIM);
+        let pc = emu.pc as usize & 0xFFF;
+        let index = emu.index as usize & 0xFFF;
+        for row in 0..ROWS {
+            let line = DUMP_LINE + row;
+            canvas.text(0, line, &format!("{:03X}", (top + row) * 16), DIM);
+            for byte in 0..16 {
+                let address = (top + row) * 16 + byte;
+                let background = if selected == Some(Field::Memory(address as u16)) {
+                    Some(SELECTED)
+                } else if address == pc || address == (pc + 1) & 0xFFF {
+                    Some(PC)
+                } else if address == index {
+                    Some(INDEX)
+                } else if written[address] > 0 {
+                    Some(fade(written[address]))
+                } else {
+                    None
+                };
+                let column = DUMP_COLUMN + byte * 3;
+                if let Some(color) = background {
+                    canvas.highlight(column, line, 2, color);
+                }
+                canvas.text(column, line, &format!("{:02X}", emu.memory[address]), TEXT);
+            }
+        }
+        canvas.text(0, LINES - 2, "BACKSPACE GOES BACK TO WHERE THE SELECTION LAST CHANGED", DIM);
+        canvas.text(0, LINES - 1, "SPACE PAUSE  HOME FOLLOW PC  CLICK AND TYPE HEX TO EDIT WHILE PAUSED", DIM);
+
+        for (field, label, column, line) in panel() {
+            let digits = if field.mask() == 0xFFF { 3 } else if field.mask() == 0xF { 1 } else { 2 };
+            canvas.text(column, line, &label, DIM);
+            if selected == Some(field) {
+                canvas.highlight(column + 3, line, digits, SELECTED);
+            }
+            canvas.text(column + 3, line, &format!("{:01$X}", field.get(emu), digits), TEXT);
+        }
+        canvas.text(PANEL_COLUMN, 15, "STACK", DIM);
+        for (depth, &address) in emu.stack.iter().enumerate() {
+            let color = if depth < emu.sp as usize { TEXT } else { DIM };
+            canvas.text(PANEL_COLUMN + 9 * (depth / 8), 16 + depth % 8, &format!("{:X} {:03X}", depth, address), color);
+        }
+        canvas.text(PANEL_COLUMN, 25, "KEYS", DIM);
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (column, &key) in keys.iter().enumerate() {
+                let (column, line) = (PANEL_COLUMN + column * 2, 26 + row);
+                let down = emu.keyboard.keypad[key as usize];
+                if down {
+                    canvas.highlight(column, line, 1, SELECTED);
+                }
+                canvas.text(column, line, &format!("{:X}", key), if down { TEXT } else { DIM });
+            }
+        }
+        if !watches.is_empty() {
+            canvas.text(PANEL_COLUMN, WATCH_LINE, "WATCH", DIM);
+        }
+        for (line, (source, expr)) in (WATCH_LINE + 1..LINES - 2).zip(watches) {
+            let label: String = source.chars().take(WATCH_LABEL).collect();
+            canvas.text(PANEL_COLUMN, line, &label, DIM);
+            let value = expr.eval(emu).map_or_else(|| "-".to_string(), |value| format!("{:X}", value));
+            canvas.text(PANEL_COLUMN + WATCH_LABEL + 1, line, &value, TEXT);
+        }
+    }
+}
+
+/// the highlight of a byte written frames ago, fading from red to the background
+fn fade(remaining: u8) -> u32 {
+    let red = 0x10 + 0xA0 * u32::from(remaining) / u32::from(WRITE_FADE);
+    red << 16 | 0x10_10
+}
+
+fn hex_digit(key: Key) -> Option<u16> {
+    let digit = match key {
+        Key::Key0 | Key::NumPad0 => 0x0,
+        Key::Key1 | Key::NumPad1 => 0x1,
+        Key::Key2 | Key::NumPad2 => 0x2,
+        Key::Key3 | Key::NumPad3 => 0x3,
+        Key::Key4 | Key::NumPad4 => 0x4,
+        Key::Key5 | Key::NumPad5 => 0x5,
+        Key::Key6 | Key::NumPad6 => 0x6,
+        Key::Key7 | Key::NumPad7 => 0x7,
+        Key::Key8 | Key::NumPad8 => 0x8,
+        Key::Key9 | Key::NumPad9 => 0x9,
+        Key::A => 0xA,
+        Key::B => 0xB,
+        Key::C => 0xC,
+        Key::D => 0xD,
+        Key::E => 0xE,
+        Key::F => 0xF,
+        _ => return None,
+    };
+    Some(digit)
+}
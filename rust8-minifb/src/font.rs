@@ -0,0 +1,109 @@
+/// width of a glyph in pixels, each row of a glyph is its low 3 bits
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+/// space taken by a character including the gap after it
+pub const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+pub const CELL_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+/// a 3x5 glyph for the characters the frontend draws, unknown ones are blank
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b100, 0b100],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '&' => [0b010, 0b101, 0b010, 0b101, 0b011],
+        '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '~' => [0b000, 0b011, 0b110, 0b000, 0b000],
+        '^' => [0b010, 0b101, 0b000, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
+
+/// A buffer of 0RGB pixels to draw text into, everything measured in unscaled pixels
+pub struct Canvas<'a> {
+    pub pixels: &'a mut [u32],
+    /// width of the buffer in real pixels
+    pub width: usize,
+    pub scale: usize,
+}
+
+impl Canvas<'_> {
+    /// fill a rectangle, clipped to the buffer
+    pub fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let rows = self.pixels.len() / self.width;
+        for row in (y * self.scale..(y + height) * self.scale).take_while(|&row| row < rows) {
+            let start = row * self.width;
+            for pixel in &mut self.pixels[start + (x * self.scale).min(self.width)..start + ((x + width) * self.scale).min(self.width)] {
+                *pixel = color;
+            }
+        }
+    }
+
+    /// draw text with its top left at a character cell
+    pub fn text(&mut self, column: usize, line: usize, text: &str, color: u32) {
+        for (i, c) in text.chars().enumerate() {
+            let (left, top) = ((column + i) * CELL_WIDTH, line * CELL_HEIGHT);
+            for (y, bits) in glyph(c).iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> x) != 0 {
+                        self.fill(left + x, top + y, 1, 1, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// fill the cells behind text of length characters
+    pub fn highlight(&mut self, column: usize, line: usize, length: usize, color: u32) {
+        self.fill(column * CELL_WIDTH, line * CELL_HEIGHT, length * CELL_WIDTH, CELL_HEIGHT, color);
+    }
+}
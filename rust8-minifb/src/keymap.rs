@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use minifb::Key;
+use serde::Deserialize;
+
+/// keymap.toml shipped with the frontend, user config is layered on top of it
+const DEFAULT_CONFIG: &str = include_str!("../keymap.toml");
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    UnknownLayout(String),
+    UnknownHostKey(String),
+    InvalidPadKey(String),
+    /// a binding for a key the frontend uses itself
+    ReservedKey(Key),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::Io(e) => write!(f, "could not read keymap: {}", e),
+            KeymapError::Toml(e) => write!(f, "could not parse keymap: {}", e),
+            KeymapError::UnknownLayout(name) => write!(f, "unknown layout {:?}", name),
+            KeymapError::UnknownHostKey(name) => write!(f, "unknown host key {:?}", name),
+            KeymapError::InvalidPadKey(name) => write!(f, "{:?} is not a keypad key, expected 0-F", name),
+            KeymapError::ReservedKey(key) => write!(f, "{:?} is a frontend hotkey and can't be bound", key),
+        }
+    }
+}
+
+impl From<io::Error> for KeymapError {
+    fn from(e: io::Error) -> Self {
+        KeymapError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for KeymapError {
+    fn from(e: toml::de::Error) -> Self {
+        KeymapError::Toml(e)
+    }
+}
+
+/// keypad key -> host key name
+type Bindings = HashMap<String, String>;
+
+#[derive(Debug, Default, Deserialize)]
+struct KeymapConfig {
+    layout: Option<String>,
+    #[serde(default)]
+    layouts: HashMap<String, Bindings>,
+    #[serde(default)]
+    roms: HashMap<String, RomOverride>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+struct RomOverride {
+    layout: Option<String>,
+    #[serde(default)]
+    keys: Bindings,
+}
+
+impl KeymapConfig {
+    /// later config wins, layouts and rom overrides are replaced wholesale by name
+    fn merge(&mut self, other: KeymapConfig) {
+        if other.layout.is_some() {
+            self.layout = other.layout;
+        }
+        self.layouts.extend(other.layouts);
+        self.roms.extend(other.roms);
+    }
+}
+
+/// Every layout and override known to the frontend
+#[derive(Debug)]
+pub struct KeymapSet {
+    config: KeymapConfig,
+    /// host keys the frontend listens for, which nothing may bind
+    hotkeys: Vec<Key>,
+}
+
+impl KeymapSet {
+    /// Load the built in layouts, plus the user config at path if there is one.
+    /// Binding any of hotkeys is an error.
+    pub fn load(path: Option<&Path>, hotkeys: &[Key]) -> Result<Self, KeymapError> {
+        let user = path.map(fs::read_to_string).transpose()?;
+        KeymapSet::parse(user.as_deref(), hotkeys)
+    }
+
+    /// the built in layouts with a user config's text on top
+    fn parse(user: Option<&str>, hotkeys: &[Key]) -> Result<Self, KeymapError> {
+        let mut config: KeymapConfig = toml::from_str(DEFAULT_CONFIG)?;
+        if let Some(user) = user {
+            config.merge(toml::from_str(user)?);
+        }
+        Ok(KeymapSet { config, hotkeys: hotkeys.to_vec() })
+    }
+
+    /// Build the keymap for a rom, applying its override on top of the default layout.
+    /// Overrides are looked up under each of the rom's names in turn, such as its hash and file name.
+    pub fn for_rom(&self, rom_names: &[&str]) -> Result<Keymap, KeymapError> {
+        let rom = rom_names.iter()
+            .find_map(|name| self.config.roms.get(*name))
+            .cloned()
+            .unwrap_or_default();
+        let layout_name = rom.layout.as_deref().or(self.config.layout.as_deref()).unwrap_or("qwerty");
+        let layout = self.config.layouts.get(layout_name)
+            .ok_or_else(|| KeymapError::UnknownLayout(layout_name.to_string()))?;
+
+        let parse = |pad: &str, host: &str| -> Result<(u8, Key), KeymapError> {
+            let host = parse_host_key(host)?;
+            if self.hotkeys.contains(&host) {
+                return Err(KeymapError::ReservedKey(host));
+            }
+            Ok((parse_pad_key(pad)?, host))
+        };
+        let mut bindings = HashMap::new();
+        for (pad, host) in layout {
+            let (pad, host) = parse(pad, host)?;
+            let _ = bindings.insert(pad, host);
+        }
+        // a rom's binding takes its host key away from whatever the layout bound it to,
+        // and replaces the layout's binding for the same pad key
+        let mut overrides = Vec::with_capacity(rom.keys.len());
+        for (pad, host) in &rom.keys {
+            overrides.push(parse(pad, host)?);
+        }
+        bindings.retain(|_, host| !overrides.iter().any(|(_, replacing)| replacing == host));
+        bindings.extend(overrides);
+        let bindings = bindings.into_iter().map(|(pad, host)| (host, pad)).collect();
+        Ok(Keymap { bindings, hotkeys: self.hotkeys.clone() })
+    }
+}
+
+/// Host key to keypad key bindings for a single rom
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Key, u8)>,
+    hotkeys: Vec<Key>,
+}
+
+impl Keymap {
+    /// return the state of all 16 keypad keys given every host key currently held down
+    pub fn keypad(&self, down: &[Key]) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for (host, pad) in &self.bindings {
+            if down.contains(host) {
+                keypad[*pad as usize] = true;
+            }
+        }
+        keypad
+    }
+
+    /// Add bindings for the logical buttons a rom database entry declares, such as "up" or "a".
+    /// These are added alongside the layout so the usual keys keep working.
+    pub fn bind_database_keys(&mut self, keys: &HashMap<String, u8>) -> Result<(), KeymapError> {
+        for (button, &pad) in keys {
+            let host = DATABASE_BUTTONS.iter().find(|(name, _)| name == button).map(|(_, host)| *host);
+            if let Some(host) = host.filter(|_| pad < 16) {
+                if self.hotkeys.contains(&host) {
+                    return Err(KeymapError::ReservedKey(host));
+                }
+                self.bindings.push((host, pad));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// host keys for the logical buttons used by the chip-8-database, second player buttons aren't bound.
+/// None of them are modifiers, which the frontend's hotkeys use.
+const DATABASE_BUTTONS: [(&str, Key); 6] = [
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("a", Key::Space),
+    ("b", Key::Enter),
+];
+
+fn parse_pad_key(name: &str) -> Result<u8, KeymapError> {
+    match u8::from_str_radix(name.strip_prefix("0x").unwrap_or(name), 16) {
+        Ok(key) if key < 16 => Ok(key),
+        _ => Err(KeymapError::InvalidPadKey(name.to_string())),
+    }
+}
+
+fn parse_host_key(name: &str) -> Result<Key, KeymapError> {
+    HOST_KEYS.iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| KeymapError::UnknownHostKey(name.to_string()))
+}
+
+/// every key that can be named in a keymap, minifb doesn't provide a way to enumerate them
+const HOST_KEYS: [Key; 104] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+    Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+    Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up,
+    Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal,
+    Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket, Key::Semicolon,
+    Key::Slash, Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape,
+    Key::Home, Key::Insert, Key::Menu, Key::PageDown, Key::PageUp, Key::Pause,
+    Key::Space, Key::Tab, Key::NumLock, Key::CapsLock, Key::ScrollLock,
+    Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+    Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus,
+    Key::NumPadPlus, Key::NumPadEnter,
+    Key::LeftAlt, Key::RightAlt,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HOTKEYS;
+
+    fn keymaps(user: Option<&str>) -> KeymapSet {
+        KeymapSet::parse(user, &HOTKEYS).unwrap()
+    }
+
+    /// the keypad keys down while host is held
+    fn pressed(keymap: &Keymap, host: Key) -> Vec<u8> {
+        (0..16).filter(|&pad| keymap.keypad(&[host])[pad as usize]).collect()
+    }
+
+    #[test]
+    fn default_layout_is_qwerty() {
+        let keymap = keymaps(None).for_rom(&[]).unwrap();
+        assert_eq!(pressed(&keymap, Key::Key4), [0xC]);
+        assert_eq!(pressed(&keymap, Key::X), [0x0]);
+        assert_eq!(pressed(&keymap, Key::V), [0xF]);
+        assert!(pressed(&keymap, Key::P).is_empty());
+    }
+
+    #[test]
+    fn user_config_changes_the_default_and_adds_layouts() {
+        let user = r#"
+            layout = "arrows"
+            [layouts.arrows]
+            2 = "up"
+            8 = "Down"
+        "#;
+        let keymap = keymaps(Some(user)).for_rom(&[]).unwrap();
+        assert_eq!(pressed(&keymap, Key::Up), [0x2]);
+        assert_eq!(pressed(&keymap, Key::Down), [0x8]);
+        assert!(pressed(&keymap, Key::Key1).is_empty());
+    }
+
+    #[test]
+    fn rom_overrides_by_hash_then_name() {
+        let user = r#"
+            [roms.0123abcd]
+            layout = "azerty"
+            [roms."pong.ch8"]
+            keys = { 1 = "W", 0x4 = "S" }
+        "#;
+        let keymaps = keymaps(Some(user));
+
+        // an override replaces whatever the layout bound to its pad key and to its host key
+        let pong = keymaps.for_rom(&["ffff", "pong.ch8"]).unwrap();
+        assert_eq!(pressed(&pong, Key::W), [0x1]);
+        assert_eq!(pressed(&pong, Key::S), [0x4]);
+        assert!(pressed(&pong, Key::Key1).is_empty());
+        assert!(pressed(&pong, Key::Q).is_empty());
+        assert!((0..16).all(|pad| pad == 0x5 || pad == 0x8 || pong.keypad(&HOST_KEYS)[pad]));
+        assert_eq!(pressed(&pong, Key::E), [0x6]);
+
+        // the first name with an override wins
+        let hashed = keymaps.for_rom(&["0123abcd", "pong.ch8"]).unwrap();
+        assert_eq!(pressed(&hashed, Key::A), [0x4]);
+        assert_eq!(pressed(&hashed, Key::Key1), [0x1]);
+    }
+
+    #[test]
+    fn bad_configs_are_errors() {
+        let unknown_host = "[layouts.qwerty]\n1 = \"Banana\"";
+        assert!(matches!(keymaps(Some(unknown_host)).for_rom(&[]), Err(KeymapError::UnknownHostKey(name)) if name == "Banana"));
+        let bad_pad = "[layouts.qwerty]\nG = \"A\"";
+        assert!(matches!(keymaps(Some(bad_pad)).for_rom(&[]), Err(KeymapError::InvalidPadKey(name)) if name == "G"));
+        let double_prefix = "[layouts.qwerty]\n0x0x1 = \"A\"";
+        assert!(matches!(keymaps(Some(double_prefix)).for_rom(&[]), Err(KeymapError::InvalidPadKey(name)) if name == "0x0x1"));
+        let unknown_layout = "[roms.\"a.ch8\"]\nlayout = \"dvorak\"";
+        assert!(matches!(keymaps(Some(unknown_layout)).for_rom(&["a.ch8"]), Err(KeymapError::UnknownLayout(name)) if name == "dvorak"));
+        assert!(matches!(KeymapSet::parse(Some("layout = "), &HOTKEYS), Err(KeymapError::Toml(_))));
+    }
+
+    #[test]
+    fn database_keys_bind_alongside_the_layout() {
+        let mut keymap = keymaps(None).for_rom(&[]).unwrap();
+        let keys = [("up", 0x5), ("a", 0x6), ("player2_up", 0x1), ("left", 0x10)]
+            .iter()
+            .map(|&(button, pad)| (button.to_string(), pad))
+            .collect();
+        keymap.bind_database_keys(&keys).unwrap();
+        assert_eq!(pressed(&keymap, Key::Up), [0x5]);
+        assert_eq!(pressed(&keymap, Key::Space), [0x6]);
+        // unknown buttons and keys off the keypad are ignored
+        assert!(pressed(&keymap, Key::Left).is_empty());
+        assert_eq!(pressed(&keymap, Key::W), [0x5]);
+    }
+
+    #[test]
+    fn hotkeys_cant_be_bound() {
+        let layout = "[layouts.qwerty]\n1 = \"P\"";
+        assert!(matches!(keymaps(Some(layout)).for_rom(&[]), Err(KeymapError::ReservedKey(Key::P))));
+        let rom = "[roms.\"a.ch8\"]\nkeys = { 5 = \"LeftShift\" }";
+        assert!(matches!(keymaps(Some(rom)).for_rom(&["a.ch8"]), Err(KeymapError::ReservedKey(Key::LeftShift))));
+        // only the rom asking for it fails
+        assert!(keymaps(Some(rom)).for_rom(&["b.ch8"]).is_ok());
+
+        // none of the built in layouts or database buttons touch a hotkey
+        let builtin = keymaps(None);
+        for layout in builtin.config.layouts.keys() {
+            let user = format!("layout = {:?}", layout);
+            assert!(keymaps(Some(&user)).for_rom(&[]).is_ok(), "{}", layout);
+        }
+        assert!(DATABASE_BUTTONS.iter().all(|(_, host)| !HOTKEYS.contains(host)));
+        let mut keymap = builtin.for_rom(&[]).unwrap();
+        let b = std::iter::once(("b".to_string(), 0xB)).collect();
+        keymap.bind_database_keys(&b).unwrap();
+        assert_eq!(pressed(&keymap, Key::Enter), [0xB]);
+        assert!(pressed(&keymap, Key::LeftShift).is_empty());
+
+        // a database button on a hotkey is refused rather than bound
+        let mut keymap = KeymapSet::parse(None, &[Key::Space]).unwrap().for_rom(&[]).unwrap();
+        let a = std::iter::once(("a".to_string(), 0x6)).collect();
+        assert!(matches!(keymap.bind_database_keys(&a), Err(KeymapError::ReservedKey(Key::Space))));
+        assert!(pressed(&keymap, Key::Space).is_empty());
+    }
+}
@@ -1,48 +1,333 @@
+mod debugger;
+mod font;
+mod keymap;
+mod saves;
+
+use rust8_core::backend::{Backend, BackendKind};
+use rust8_core::clock::{FrameClock, Speed};
+use rust8_core::debug::Session;
 use rust8_core::emu::Emulator;
+use rust8_core::expr::Expr;
+use rust8_capture::{GifRecorder, Palette, save_png};
+use rust8_db::{Database, rom_hash};
 use minifb::{Window, WindowOptions, Key, KeyRepeat};
 use rust8_core::constants::DISPLAY_WIDTH;
+use crate::debugger::Debugger;
+use crate::font::Canvas;
+use crate::keymap::KeymapSet;
+use crate::saves::{SaveError, Saves, Slot};
+use std::env;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SCALE: usize = 10;
+const SCREENSHOT_KEY: Key = Key::F12;
+const RECORD_KEY: Key = Key::F11;
+const DEBUGGER_KEY: Key = Key::Tab;
+const PAUSE_KEY: Key = Key::P;
+/// with shift held these go backwards, to the last breakpoint hit or instruction
+const FRAME_ADVANCE_KEY: Key = Key::Period;
+const STEP_KEY: Key = Key::Comma;
+const SLOWER_KEY: Key = Key::Minus;
+const FASTER_KEY: Key = Key::Equal;
+const TURBO_KEY: Key = Key::Backquote;
+/// load slots 1 to 10, or save them with shift held
+const SLOT_KEYS: [Key; 10] = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10];
+/// every key the frontend listens for itself, which keymaps can't bind
+const HOTKEYS: [Key; 22] = [
+    SCREENSHOT_KEY, RECORD_KEY, DEBUGGER_KEY, PAUSE_KEY, FRAME_ADVANCE_KEY, STEP_KEY, SLOWER_KEY, FASTER_KEY, TURBO_KEY,
+    SLOT_KEYS[0], SLOT_KEYS[1], SLOT_KEYS[2], SLOT_KEYS[3], SLOT_KEYS[4],
+    SLOT_KEYS[5], SLOT_KEYS[6], SLOT_KEYS[7], SLOT_KEYS[8], SLOT_KEYS[9],
+    // quits, and the modifier for saving and going backwards
+    Key::Escape, Key::LeftShift, Key::RightShift,
+];
+/// how long a message like `SAVED 1` stays on screen
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+
+/// time turbo spends running frames each window update, leaving the rest for drawing
+const TURBO_BUDGET: Duration = Duration::from_millis(12);
+
+/// rom used when none is passed on the command line
+const DEFAULT_ROM: &[u8] = include_bytes!("../../roms/maze.ch8");
+const DEFAULT_ROM_NAME: &str = "maze.ch8";
+
+struct Args {
+    rom: Option<PathBuf>,
+    keymap: Option<PathBuf>,
+    database: Option<PathBuf>,
+    backend: BackendKind,
+    /// where state files go instead of the user's data directory
+    saves: Option<PathBuf>,
+    /// load the auto save from the last run
+    resume: bool,
+    /// expressions that pause emulation when they hold
+    breakpoints: Vec<String>,
+    /// expressions shown in the debugger
+    watches: Vec<String>,
+    /// record execution so it can be stepped backwards, off by default since it slows running down
+    history: bool,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args { rom: None, keymap: None, database: None, backend: BackendKind::Interpreter, saves: None, resume: true, breakpoints: Vec::new(), watches: Vec::new(), history: false };
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--keymap" => args.keymap = Some(argv.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            "--db" => args.database = Some(argv.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            "--backend" => args.backend = argv.next().unwrap_or_else(|| usage()).parse().unwrap_or_else(|e| fail(e)),
+            "--saves" => args.saves = Some(argv.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+            "--no-resume" => args.resume = false,
+            "--break" => args.breakpoints.push(argv.next().unwrap_or_else(|| usage())),
+            "--watch" => args.watches.push(argv.next().unwrap_or_else(|| usage())),
+            "--history" => args.history = true,
+            _ if arg.starts_with('-') || args.rom.is_some() => usage(),
+            _ => args.rom = Some(PathBuf::from(arg)),
+        }
+    }
+    args
+}
+
+fn usage() -> ! {
+    eprintln!("usage: rust8-minifb [--keymap FILE] [--db DIR] [--backend interpreter|cached|jit] [--saves DIR] [--no-resume] [--break EXPR]... [--watch EXPR]... [--history] [ROM]");
+    process::exit(2);
+}
+
+fn fail(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
 
 fn main() {
-    let rom: Vec<u8> = include_bytes!("../../roms/maze.ch8").to_vec();
+    let args = parse_args();
+    let (rom, rom_name) = match &args.rom {
+        Some(path) => match fs::read(path) {
+            Ok(rom) => (rom, path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned())),
+            Err(e) => fail(format!("failed to read {}: {}", path.display(), e)),
+        },
+        None => (DEFAULT_ROM.to_vec(), DEFAULT_ROM_NAME.to_string()),
+    };
+    let hash = rom_hash(&rom);
+
+    let mut database = Database::bundled();
+    if let Some(dir) = &args.database {
+        database.add_dir(dir).unwrap_or_else(|e| fail(e));
+    }
+    let info = database.lookup_hash(&hash);
+
+    let mut keymap = KeymapSet::load(args.keymap.as_deref(), &HOTKEYS)
+        .and_then(|keymaps| keymaps.for_rom(&[&hash, &rom_name]))
+        .unwrap_or_else(|e| fail(e));
+
     let mut emu: Emulator = Emulator::new();
-    emu.load_rom(rom);
+    let mut session = Session::new(Backend::new(args.backend));
+    session.breakpoints = args.breakpoints.iter().map(|source| parse_expr(source)).collect();
+    let watches: Vec<(String, Expr)> = args.watches.iter().map(|source| (source.clone(), parse_expr(source))).collect();
+    emu.load_rom(&rom).unwrap_or_else(|e| fail(e));
+
+    let mut palette = Palette::default();
+    let mut title = String::from("Rusty_Chip8");
+    if let Some(info) = &info {
+        info.apply(&mut emu);
+        keymap.bind_database_keys(&info.keys).unwrap_or_else(|e| fail(e));
+        if let Some((off, on)) = info.colors {
+            palette = Palette::new(off, on);
+        }
+        title = format!("Rusty_Chip8 - {}", info.title);
+    }
+    let (off_color, on_color) = palette.to_u32();
+
+    let saves = args.saves.clone().or_else(Saves::default_dir).map(|dir| Saves::new(dir, &hash));
+    match &saves {
+        Some(saves) if args.resume => match saves.load(Slot::Auto, &mut emu) {
+            Ok(path) => println!("resumed from {}", path.display()),
+            Err(SaveError::Empty(_)) => {}
+            Err(e) => eprintln!("failed to resume: {}", e),
+        },
+        Some(_) => {}
+        None => eprintln!("there's no data directory to keep save states in, pass --saves DIR"),
+    }
+    if args.history {
+        if let Err(e) = session.record(&emu) {
+            eprintln!("can't step backwards: {}", e);
+        }
+    }
 
     let width = 640;
     let height = 320;
 
     let mut framebuf: Vec<u32> = vec![0; width * height];
+    // the frame with the indicator drawn over it
+    let mut screen: Vec<u32> = vec![0; width * height];
+    let mut recorder: Option<GifRecorder<BufWriter<File>>> = None;
+    let mut debugger: Option<Debugger> = None;
+    let mut clock = FrameClock::default();
+    let mut paused = false;
+    let mut turbo = false;
+    let mut last_update = Instant::now();
+    let mut message: Option<(String, Instant)> = None;
 
     let mut window = Window::new(
-        "Rusty_Chip8",
+        &title,
         width,
         height,
         WindowOptions::default(),
     ).unwrap();
+    // the clock decides how many frames each update is worth
+    window.limit_update_rate(Some(Duration::from_secs(1) / 60));
     while window.is_open() && !window.is_key_down(Key::Escape) {
-        emu.tick();
-
-        let key: Option<u8> = match  window.get_keys_pressed(KeyRepeat::Yes).unwrap().first() {
-            Some(Key::Key1) => Some(0x1),
-            Some(Key::Key2) => Some(0x2),
-            Some(Key::Key3) => Some(0x3),
-            Some(Key::Key4) => Some(0xC),
-            Some(Key::Q) => Some(0x4),
-            Some(Key::W) => Some(0x5),
-            Some(Key::E) => Some(0x6),
-            Some(Key::R) => Some(0xD),
-            Some(Key::A) => Some(0x7),
-            Some(Key::S) => Some(0x8),
-            Some(Key::D) => Some(0x9),
-            Some(Key::F) => Some(0xE),
-            Some(Key::Z) => Some(0xA),
-            Some(Key::X) => Some(0x0),
-            Some(Key::C) => Some(0xB),
-            Some(Key::V) => Some(0xF),
-            _ => None,
-        };
+        let now = Instant::now();
+        let elapsed = now - last_update;
+        last_update = now;
 
-        if key.is_some() {
-            emu.keyboard.press_key(key.unwrap());
+        // diff against the core's keypad so every key gets a press and a release
+        let keypad = keymap.keypad(&window.get_keys().unwrap_or_default());
+        for (key, &down) in keypad.iter().enumerate() {
+            if down && !emu.keyboard.keypad[key] {
+                emu.keyboard.press_key(key as u8);
+            } else if !down && emu.keyboard.keypad[key] {
+                emu.keyboard.release_key(key as u8);
+            }
+        }
+
+        if window.is_key_pressed(SCREENSHOT_KEY, KeyRepeat::No) {
+            let path = capture_path("png");
+            match save_png(&path, &emu.display.vram, SCALE as u32, &palette) {
+                Ok(()) => println!("saved screenshot to {}", path),
+                Err(e) => eprintln!("failed to save screenshot: {}", e),
+            }
+        }
+
+        if window.is_key_pressed(RECORD_KEY, KeyRepeat::No) {
+            match recorder.take() {
+                Some(gif) => match gif.finish() {
+                    Ok(()) => println!("stopped recording"),
+                    Err(e) => eprintln!("failed to finish recording: {}", e),
+                },
+                None => {
+                    let path = capture_path("gif");
+                    match GifRecorder::create(&path, SCALE as u32, &palette) {
+                        Ok(gif) => {
+                            println!("recording to {}", path);
+                            recorder = Some(gif);
+                        }
+                        Err(e) => eprintln!("failed to start recording: {}", e),
+                    }
+                }
+            }
+        }
+
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        for (slot, &key) in SLOT_KEYS.iter().enumerate() {
+            if !window.is_key_pressed(key, KeyRepeat::No) {
+                continue;
+            }
+            let slot = Slot::Numbered(slot as u8 + 1);
+            let saves = match &saves {
+                Some(saves) => saves,
+                None => continue,
+            };
+            let text = if shift {
+                match saves.save(slot, &emu, &palette) {
+                    Ok(path) => {
+                        println!("saved {} to {}", slot, path.display());
+                        format!("SAVED {}", slot)
+                    }
+                    Err(e) => {
+                        eprintln!("failed to save {}: {}", slot, e);
+                        "SAVE FAILED".to_string()
+                    }
+                }
+            } else {
+                match saves.load(slot, &mut emu) {
+                    Ok(_) => {
+                        session.edited(&emu);
+                        format!("LOADED {}", slot)
+                    }
+                    Err(SaveError::Empty(_)) => format!("{} IS EMPTY", slot),
+                    Err(e) => {
+                        eprintln!("failed to load {}: {}", slot, e);
+                        "LOAD FAILED".to_string()
+                    }
+                }
+            };
+            message = Some((text, now));
+        }
+
+        if window.is_key_pressed(DEBUGGER_KEY, KeyRepeat::No) {
+            debugger = match debugger {
+                Some(_) => None,
+                None => Debugger::open(&emu).map_err(|e| eprintln!("failed to open the debugger: {}", e)).ok(),
+            };
+        }
+        if window.is_key_pressed(PAUSE_KEY, KeyRepeat::No) {
+            paused = !paused;
+        }
+        if window.is_key_pressed(SLOWER_KEY, KeyRepeat::No) {
+            clock.speed = clock.speed.slower();
+        }
+        if window.is_key_pressed(FASTER_KEY, KeyRepeat::No) {
+            clock.speed = clock.speed.faster();
+        }
+        if window.is_key_pressed(TURBO_KEY, KeyRepeat::No) {
+            turbo = !turbo;
+        }
+        // advancing a frame or stepping pauses first
+        let advance = window.is_key_pressed(FRAME_ADVANCE_KEY, KeyRepeat::Yes);
+        let step = window.is_key_pressed(STEP_KEY, KeyRepeat::Yes);
+        if advance || step {
+            paused = true;
+        }
+        if step && shift {
+            if !session.step_back(&mut emu) {
+                message = Some((history_end(&session), now));
+            }
+        } else if step {
+            session.step(&mut emu);
+        }
+        if advance && shift {
+            let text = match session.reverse_continue(&mut emu) {
+                Some(hit) => format!("BREAK {}", hit + 1),
+                None => history_end(&session),
+            };
+            message = Some((text, now));
+        }
+        if let Some(view) = debugger.as_mut() {
+            view.update(&mut emu, &mut session, &watches, &mut paused);
+            if !view.is_open() {
+                debugger = None;
+            }
+        }
+
+        // how many frames to run, turbo runs until it's out of time instead
+        let budget = if paused {
+            clock.reset();
+            Some(u32::from(advance && !shift))
+        } else if turbo {
+            None
+        } else {
+            Some(clock.frames(elapsed))
+        };
+        let mut frames = 0;
+        while budget.map_or(now.elapsed() < TURBO_BUDGET, |budget| frames < budget) {
+            frames += 1;
+            if let Some(hit) = session.run_frame(&mut emu) {
+                println!("breakpoint {} hit: {}", hit + 1, args.breakpoints[hit]);
+                message = Some((format!("BREAK {}", hit + 1), now));
+                paused = true;
+                break;
+            }
+            // record every frame that finished, so the gif plays at 60fps whatever the speed
+            if let Some(gif) = recorder.as_mut() {
+                if let Err(e) = gif.push_frame(&emu.display.vram) {
+                    eprintln!("failed to record frame, stopping: {}", e);
+                    recorder = None;
+                }
+            }
         }
 
         // render
@@ -50,11 +335,69 @@ fn main() {
             let vram = emu.display.receive_change();
             for y in 0..height {
                 for x in 0..width {
-                    let index = (y / 10) * DISPLAY_WIDTH as usize + (x / 10);
-                    framebuf[y * width + x] = if vram[index] {0xffffff} else { 0x0 };
+                    let index = (y / SCALE) * DISPLAY_WIDTH as usize + (x / SCALE);
+                    framebuf[y * width + x] = if vram[index] { on_color } else { off_color };
                 }
             }
-            window.update_with_buffer(&framebuf, width, height);
         }
+        if message.as_ref().is_some_and(|(_, shown)| shown.elapsed() > MESSAGE_TIME) {
+            message = None;
+        }
+        let indicator = indicator(paused, turbo, &clock, message.as_ref().map(|(text, _)| text.as_str()));
+        if indicator.is_empty() {
+            window.update_with_buffer(&framebuf, width, height).unwrap();
+        } else {
+            screen.copy_from_slice(&framebuf);
+            let mut canvas = Canvas { pixels: &mut screen, width, scale: 3 };
+            canvas.highlight(0, 0, indicator.len() + 1, 0);
+            canvas.text(0, 0, &indicator, 0xFF_FF_FF);
+            window.update_with_buffer(&screen, width, height).unwrap();
+        }
+    }
+
+    if let Some(gif) = recorder {
+        if let Err(e) = gif.finish() {
+            eprintln!("failed to finish recording: {}", e);
+        }
+    }
+    if let Some(saves) = &saves {
+        if let Err(e) = saves.save(Slot::Auto, &emu, &palette) {
+            eprintln!("failed to save {}: {}", Slot::Auto, e);
+        }
+    }
+}
+
+/// what's shown in the corner when emulation isn't running normally, like `PAUSED 2X`,
+/// followed by any message
+fn indicator(paused: bool, turbo: bool, clock: &FrameClock, message: Option<&str>) -> String {
+    let mut states = Vec::new();
+    if paused {
+        states.push("PAUSED".to_string());
+    }
+    if turbo {
+        states.push("TURBO".to_string());
+    } else if clock.speed != Speed::NORMAL {
+        states.push(clock.speed.to_string());
+    }
+    states.extend(message.map(str::to_string));
+    states.join(" ")
+}
+
+/// why going backwards stopped short
+fn history_end(session: &Session) -> String {
+    match session.position() {
+        Some(_) => "START OF HISTORY".to_string(),
+        None => "NO HISTORY".to_string(),
     }
 }
+
+/// parse an expression from the command line or exit
+fn parse_expr(source: &str) -> Expr {
+    source.parse().unwrap_or_else(|e| fail(format!("bad expression {:?}: {}", source, e)))
+}
+
+/// file name for a capture taken right now
+fn capture_path(extension: &str) -> String {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    format!("rust8-{}.{}", timestamp, extension)
+}
@@ -0,0 +1,276 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use rust8_capture::{write_png, CaptureError, Palette};
+use rust8_core::emu::Emulator;
+use rust8_core::error::Error;
+use rust8_core::state::STATE_SIZE;
+
+/// A state file is MAGIC and VERSION, the length of a png thumbnail as a little endian u32,
+/// the thumbnail and then a core save state
+const MAGIC: &[u8; 4] = b"R8SV";
+const VERSION: u8 = 1;
+const THUMBNAIL_SCALE: u32 = 2;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Capture(CaptureError),
+    State(Error),
+    /// the file isn't a state file this version wrote
+    InvalidFile(PathBuf),
+    /// nothing has been saved in the slot for this rom
+    Empty(Slot),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "i/o error: {}", e),
+            SaveError::Capture(e) => write!(f, "could not make a thumbnail: {}", e),
+            SaveError::State(e) => write!(f, "{}", e),
+            SaveError::InvalidFile(path) => write!(f, "{} is not a save state", path.display()),
+            SaveError::Empty(slot) => write!(f, "nothing saved in {}", slot),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<CaptureError> for SaveError {
+    fn from(e: CaptureError) -> Self {
+        SaveError::Capture(e)
+    }
+}
+
+impl From<Error> for SaveError {
+    fn from(e: Error) -> Self {
+        SaveError::State(e)
+    }
+}
+
+/// Where a state is kept for a rom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// a quick save slot, 1 to 10
+    Numbered(u8),
+    /// saved on exit and loaded on the next launch
+    Auto,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Slot::Numbered(number) => write!(f, "slot {}", number),
+            Slot::Auto => write!(f, "the auto save"),
+        }
+    }
+}
+
+/// The state files of one rom, named by the rom's hash in a directory shared by every rom
+pub struct Saves {
+    dir: PathBuf,
+    hash: String,
+}
+
+impl Saves {
+    pub fn new(dir: PathBuf, hash: &str) -> Self {
+        Saves { dir, hash: hash.to_string() }
+    }
+
+    /// rust8/states in the user's data directory, if the platform has one
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rust8").join("states"))
+    }
+
+    pub fn path(&self, slot: Slot) -> PathBuf {
+        match slot {
+            Slot::Numbered(number) => self.dir.join(format!("{}.{}.state", self.hash, number)),
+            Slot::Auto => self.dir.join(format!("{}.auto.state", self.hash)),
+        }
+    }
+
+    /// Save emu's state to a slot with a thumbnail of the screen in palette
+    pub fn save(&self, slot: Slot, emu: &Emulator, palette: &Palette) -> Result<PathBuf, SaveError> {
+        let mut thumbnail = Vec::new();
+        write_png(&mut thumbnail, &emu.display.vram, THUMBNAIL_SCALE, palette)?;
+        let mut state = vec![0; STATE_SIZE];
+        emu.save_state(&mut state)?;
+
+        let mut file = Vec::with_capacity(MAGIC.len() + 5 + thumbnail.len() + state.len());
+        file.extend_from_slice(MAGIC);
+        file.push(VERSION);
+        file.extend_from_slice(&(thumbnail.len() as u32).to_le_bytes());
+        file.extend_from_slice(&thumbnail);
+        file.extend_from_slice(&state);
+
+        // write beside the old file and swap it in so a failed save doesn't lose it
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(slot);
+        let partial = path.with_extension("state.partial");
+        fs::write(&partial, &file)?;
+        fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    /// Load a slot's state into emu, which is untouched if that fails
+    pub fn load(&self, slot: Slot, emu: &mut Emulator) -> Result<PathBuf, SaveError> {
+        let path = self.path(slot);
+        let file = match fs::read(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(SaveError::Empty(slot)),
+            Err(e) => return Err(e.into()),
+        };
+        let state = state(&file).ok_or_else(|| SaveError::InvalidFile(path.clone()))?;
+        emu.load_state(state)?;
+        Ok(path)
+    }
+}
+
+/// the save state in a state file, after the header and thumbnail
+fn state(file: &[u8]) -> Option<&[u8]> {
+    let header = MAGIC.len() + 5;
+    if file.len() < header || &file[..4] != MAGIC || file[4] != VERSION {
+        return None;
+    }
+    let thumbnail = u32::from_le_bytes(<[u8; 4]>::try_from(&file[5..9]).ok()?) as usize;
+    file.get(header.checked_add(thumbnail)?..)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// a directory of its own for each test, removed when it's dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("rust8-saves-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn file(version: u8, thumbnail: u32, rest: &[u8]) -> Vec<u8> {
+        let mut file = MAGIC.to_vec();
+        file.push(version);
+        file.extend_from_slice(&thumbnail.to_le_bytes());
+        file.extend_from_slice(rest);
+        file
+    }
+
+    fn emulator() -> Emulator {
+        let mut emu = Emulator::new();
+        emu.load_rom(include_bytes!("../../roms/maze.ch8")).unwrap();
+        for _ in 0..10 {
+            emu.run_frame();
+        }
+        emu
+    }
+
+    fn snapshot(emu: &Emulator) -> Vec<u8> {
+        let mut state = vec![0; STATE_SIZE];
+        emu.save_state(&mut state).unwrap();
+        state
+    }
+
+    #[test]
+    fn state_follows_the_header_and_thumbnail() {
+        assert_eq!(state(&file(VERSION, 3, &[1, 2, 3, 9, 9])), Some(&[9, 9][..]));
+        assert_eq!(state(&file(VERSION, 0, &[7])), Some(&[7][..]));
+        assert_eq!(state(&file(VERSION, 3, &[1, 2, 3])), Some(&[][..]));
+    }
+
+    #[test]
+    fn state_rejects_bad_headers_and_thumbnail_lengths() {
+        assert_eq!(state(&[]), None);
+        assert_eq!(state(&file(VERSION, 0, &[])[..8]), None);
+        assert_eq!(state(&file(VERSION + 1, 0, &[1])), None);
+        let mut wrong_magic = file(VERSION, 0, &[1]);
+        wrong_magic[0] = b'X';
+        assert_eq!(state(&wrong_magic), None);
+        // the thumbnail runs past the end of the file
+        assert_eq!(state(&file(VERSION, 4, &[1, 2, 3])), None);
+        assert_eq!(state(&file(VERSION, u32::MAX, &[1, 2, 3])), None);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = TempDir::new("round-trip");
+        let saves = Saves::new(dir.0.clone(), "abc123");
+        let emu = emulator();
+        let path = saves.save(Slot::Numbered(3), &emu, &Palette::default()).unwrap();
+        assert_eq!(path, dir.0.join("abc123.3.state"));
+
+        let mut loaded = Emulator::new();
+        assert_eq!(saves.load(Slot::Numbered(3), &mut loaded).unwrap(), path);
+        assert!(snapshot(&loaded) == snapshot(&emu));
+
+        // the thumbnail is a png of the screen at THUMBNAIL_SCALE
+        let file = fs::read(&path).unwrap();
+        let thumbnail = &file[MAGIC.len() + 5..file.len() - STATE_SIZE];
+        assert_eq!(thumbnail[..8], *b"\x89PNG\r\n\x1a\n");
+        // the size is the first thing in the header chunk
+        let size = |at: usize| u32::from_be_bytes(<[u8; 4]>::try_from(&thumbnail[at..at + 4]).unwrap());
+        assert_eq!((size(16), size(20)), (64 * THUMBNAIL_SCALE, 32 * THUMBNAIL_SCALE));
+    }
+
+    #[test]
+    fn save_replaces_the_old_file_through_a_partial_one() {
+        let dir = TempDir::new("partial");
+        let saves = Saves::new(dir.0.clone(), "abc123");
+        let path = saves.path(Slot::Auto);
+        let partial = path.with_extension("state.partial");
+
+        // left behind by a save that didn't finish
+        fs::create_dir_all(&dir.0).unwrap();
+        fs::write(&partial, b"half a save").unwrap();
+        let _ = saves.save(Slot::Auto, &Emulator::new(), &Palette::default()).unwrap();
+        let first = fs::read(&path).unwrap();
+        assert!(!partial.exists());
+
+        let _ = saves.save(Slot::Auto, &emulator(), &Palette::default()).unwrap();
+        assert!(!partial.exists());
+        assert_ne!(fs::read(&path).unwrap(), first);
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn empty_slots_and_invalid_files() {
+        let dir = TempDir::new("errors");
+        let saves = Saves::new(dir.0.clone(), "abc123");
+        let mut emu = emulator();
+        let before = snapshot(&emu);
+
+        assert!(matches!(saves.load(Slot::Auto, &mut emu), Err(SaveError::Empty(Slot::Auto))));
+        assert!(matches!(saves.load(Slot::Numbered(1), &mut emu), Err(SaveError::Empty(Slot::Numbered(1)))));
+
+        fs::create_dir_all(&dir.0).unwrap();
+        fs::write(saves.path(Slot::Numbered(1)), b"not a state").unwrap();
+        match saves.load(Slot::Numbered(1), &mut emu) {
+            Err(SaveError::InvalidFile(path)) => assert_eq!(path, saves.path(Slot::Numbered(1))),
+            other => panic!("expected InvalidFile, got {:?}", other),
+        }
+
+        // a good header in front of a truncated core state
+        fs::write(saves.path(Slot::Numbered(2)), file(VERSION, 0, &[0; 10])).unwrap();
+        assert!(matches!(saves.load(Slot::Numbered(2), &mut emu), Err(SaveError::State(_))));
+        assert!(snapshot(&emu) == before);
+    }
+}
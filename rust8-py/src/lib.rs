@@ -0,0 +1,149 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Python bindings for rust8-core, build and install into the current environment with
+//! `maturin develop --release -m rust8-py/Cargo.toml`, then test with `pytest rust8-py/tests`.
+//!
+//! ```python
+//! import rust8
+//!
+//! emu = rust8.Emulator(seed=1)
+//! emu.load_rom(open("maze.ch8", "rb").read())
+//! start = emu.save_state()
+//! for _ in range(600):
+//!     emu.step(1 << 0x5)  # hold key 5 for a frame
+//! pixels = emu.frame()    # numpy uint8 array, shape (32, 64)
+//! emu.load_state(start)
+//! ```
+
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use rust8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rust8_core::emu;
+use rust8_core::error::Error;
+use rust8_core::rng::Rng;
+use rust8_core::state::STATE_SIZE;
+
+fn value_error(e: Error) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// A CHIP-8 emulator with no window or audio, driven a frame at a time
+#[pyclass(module = "rust8")]
+pub struct Emulator {
+    emu: emu::Emulator,
+}
+
+#[pymethods]
+impl Emulator {
+    /// Create an emulator, the seed drives the random number instruction and defaults to the clock
+    #[new]
+    #[pyo3(signature = (seed = None))]
+    fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+        });
+        let mut emu = emu::Emulator::new();
+        emu.rng = Rng::from_seed(seed);
+        Emulator { emu }
+    }
+
+    /// Load a rom at 0x200, raises ValueError if it doesn't fit in memory
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.emu.load_rom(rom).map_err(value_error)
+    }
+
+    /// Hold the keys in action, a bitmask with bit n set for key n, and run that many frames.
+    /// The GIL is released meanwhile so emulators can run on several threads at once.
+    #[pyo3(signature = (action, frames = 1))]
+    fn step(&mut self, py: Python<'_>, action: u16, frames: u32) {
+        let emu = &mut self.emu;
+        py.detach(|| {
+            emu.keyboard.set_keypad(action);
+            for _ in 0..frames {
+                emu.run_frame();
+            }
+        });
+    }
+
+    /// The display as a (32, 64) uint8 array, 1 where a pixel is lit
+    fn frame<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        let width = DISPLAY_WIDTH as usize;
+        let vram = &self.emu.display.vram;
+        Array2::from_shape_fn((DISPLAY_HEIGHT as usize, width), |(y, x)| vram[y * width + x] as u8).into_pyarray(py)
+    }
+
+    /// A copy of all 4096 bytes of memory
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.emu.memory)
+    }
+
+    /// A copy of V0 through VF
+    #[getter]
+    fn registers<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.emu.registers)
+    }
+
+    /// Instructions run per frame
+    #[getter]
+    fn tickrate(&self) -> u32 {
+        self.emu.tickrate
+    }
+
+    #[setter]
+    fn set_tickrate(&mut self, tickrate: u32) {
+        self.emu.tickrate = tickrate;
+    }
+
+    /// Whether the sound timer is running
+    #[getter]
+    fn sound_active(&self) -> bool {
+        self.emu.sound > 0
+    }
+
+    /// Snapshot everything that changes while a rom runs
+    fn save_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut state = [0; STATE_SIZE];
+        self.emu.save_state(&mut state).map_err(value_error)?;
+        Ok(PyBytes::new(py, &state))
+    }
+
+    /// Restore a snapshot from save_state, raises ValueError if it's invalid
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.emu.load_state(state).map_err(value_error)
+    }
+}
+
+#[pymodule]
+fn rust8(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Emulator>()
+}
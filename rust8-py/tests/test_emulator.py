@@ -0,0 +1,73 @@
+"""Smoke tests for the bindings, run with pytest after `maturin develop -m rust8-py/Cargo.toml`"""
+
+import threading
+from pathlib import Path
+
+import numpy as np
+import pytest
+
+import rust8
+
+MAZE = (Path(__file__).parents[2] / "roms" / "maze.ch8").read_bytes()
+
+
+def maze(seed=1):
+    emu = rust8.Emulator(seed=seed)
+    emu.load_rom(MAZE)
+    return emu
+
+
+def test_frame_shape():
+    emu = maze()
+    pixels = emu.frame()
+    assert pixels.shape == (32, 64)
+    assert pixels.dtype == np.uint8
+    assert not pixels.any()
+
+    emu.step(0, frames=60)
+    pixels = emu.frame()
+    assert pixels.shape == (32, 64)
+    assert set(np.unique(pixels)) <= {0, 1}
+    assert pixels.any()
+
+
+def test_step_holds_keys_and_is_deterministic():
+    first, second = maze(seed=7), maze(seed=7)
+    first.step(1 << 0x5, frames=30)
+    for _ in range(30):
+        second.step(1 << 0x5)
+    assert np.array_equal(first.frame(), second.frame())
+    assert first.registers == second.registers
+    assert len(first.memory) == 4096 and len(first.registers) == 16
+
+
+def test_load_state_resets():
+    emu = maze()
+    start = emu.save_state()
+    emu.step(0, frames=20)
+    after = emu.frame().copy()
+
+    emu.load_state(start)
+    assert not emu.frame().any()
+    emu.step(0, frames=20)
+    assert np.array_equal(emu.frame(), after)
+
+    with pytest.raises(ValueError):
+        emu.load_state(start[:-1])
+
+
+def test_rom_too_large():
+    with pytest.raises(ValueError):
+        rust8.Emulator(seed=1).load_rom(bytes(4096))
+
+
+def test_steps_on_threads():
+    emus = [maze(seed=seed) for seed in range(4)]
+    threads = [threading.Thread(target=emu.step, args=(0, 120)) for emu in emus]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    expected = maze(seed=3)
+    expected.step(0, frames=120)
+    assert np.array_equal(emus[3].frame(), expected.frame())
@@ -0,0 +1,142 @@
+// Copyright (C) 2019 Teddy Heinen
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! JavaScript bindings for rust8-core.
+//!
+//! Build with `wasm-pack build --target web rust8-wasm`, then drive it from a page:
+//!
+//! ```js
+//! import init, { Emulator } from "./pkg/rust8_wasm.js";
+//!
+//! const wasm = await init();
+//! const emu = new Emulator(Math.random() * 2 ** 32);
+//! emu.load_rom(new Uint8Array(await (await fetch("maze.ch8")).arrayBuffer()));
+//! const pixels = new ImageData(
+//!     new Uint8ClampedArray(wasm.memory.buffer, emu.framebuffer(), emu.framebuffer_len()),
+//!     emu.width(), emu.height());
+//! function frame() {
+//!     emu.run_frame();
+//!     context.putImageData(pixels, 0, 0);
+//!     requestAnimationFrame(frame);
+//! }
+//! ```
+//!
+//! Views into `wasm.memory` are detached if the memory grows, so recreate the view if it goes empty.
+
+#![deny(
+trivial_casts,
+trivial_numeric_casts,
+unused_extern_crates,
+unused_import_braces,
+unused_qualifications,
+unused_results,
+warnings
+)]
+
+use rust8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use rust8_core::emu;
+use rust8_core::rng::Rng;
+use wasm_bindgen::prelude::*;
+
+/// bytes in the rgba framebuffer
+const FRAMEBUFFER_LEN: usize = DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize * 4;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    emu: emu::Emulator,
+    framebuffer: Vec<u8>,
+    off: [u8; 4],
+    on: [u8; 4],
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// Create an emulator, the seed drives the random number instruction
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Emulator {
+        let mut emu = emu::Emulator::new();
+        emu.rng = Rng::from_seed(seed as u64);
+        let mut emulator = Emulator {
+            emu,
+            framebuffer: vec![0; FRAMEBUFFER_LEN],
+            off: [0x00, 0x00, 0x00, 0xff],
+            on: [0xff, 0xff, 0xff, 0xff],
+        };
+        emulator.render();
+        emulator
+    }
+
+    /// Copy a rom into memory at 0x200
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.emu.load_rom(rom).map_err(|e| e.to_string())
+    }
+
+    /// Run one 60hz frame and update the framebuffer
+    pub fn run_frame(&mut self) {
+        self.emu.run_frame();
+        if self.emu.display.dirty {
+            self.render();
+        }
+    }
+
+    /// Press or release one of the 16 keypad keys, anything else is ignored
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        match (key < 16, down) {
+            (false, _) => {}
+            (true, true) => self.emu.keyboard.press_key(key),
+            (true, false) => self.emu.keyboard.release_key(key),
+        }
+    }
+
+    /// Set the colors of unset and set pixels as 0xRRGGBB
+    pub fn set_palette(&mut self, off: u32, on: u32) {
+        self.off = rgba(off);
+        self.on = rgba(on);
+        self.render();
+    }
+
+    /// Pointer into wasm memory of the rgba framebuffer, row major and width() pixels wide
+    pub fn framebuffer(&self) -> *const u8 {
+        self.framebuffer.as_ptr()
+    }
+
+    pub fn framebuffer_len(&self) -> usize {
+        self.framebuffer.len()
+    }
+
+    pub fn width(&self) -> u32 {
+        DISPLAY_WIDTH as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        DISPLAY_HEIGHT as u32
+    }
+
+    /// true while the sound timer is running and the buzzer should be on
+    pub fn sound_active(&self) -> bool {
+        self.emu.sound > 0
+    }
+
+    fn render(&mut self) {
+        let vram = self.emu.display.receive_change();
+        for (pixel, &set) in self.framebuffer.chunks_exact_mut(4).zip(vram.iter()) {
+            pixel.copy_from_slice(if set { &self.on } else { &self.off });
+        }
+    }
+}
+
+fn rgba(color: u32) -> [u8; 4] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8, 0xff]
+}
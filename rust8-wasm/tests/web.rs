@@ -0,0 +1,41 @@
+//! Run under node with `wasm-pack test --node rust8-wasm`, on other targets these are plain tests
+
+use rust8_wasm::Emulator;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+
+#[wasm_bindgen_test(unsupported = test)]
+fn maze_draws_to_the_framebuffer() {
+    let mut emu = Emulator::new(1);
+    emu.load_rom(MAZE).unwrap();
+    for _ in 0..60 {
+        emu.run_frame();
+    }
+
+    assert_eq!(emu.framebuffer_len(), (emu.width() * emu.height() * 4) as usize);
+    let framebuffer = unsafe { std::slice::from_raw_parts(emu.framebuffer(), emu.framebuffer_len()) };
+    assert!(framebuffer.chunks(4).any(|pixel| pixel == [0xff, 0xff, 0xff, 0xff]));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn same_seed_draws_the_same_maze() {
+    let mut first = Emulator::new(42);
+    let mut second = Emulator::new(42);
+    first.load_rom(MAZE).unwrap();
+    second.load_rom(MAZE).unwrap();
+    for _ in 0..60 {
+        first.run_frame();
+        second.run_frame();
+    }
+
+    let first = unsafe { std::slice::from_raw_parts(first.framebuffer(), first.framebuffer_len()) };
+    let second = unsafe { std::slice::from_raw_parts(second.framebuffer(), second.framebuffer_len()) };
+    assert_eq!(first, second);
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn oversized_roms_are_rejected() {
+    let mut emu = Emulator::new(1);
+    assert!(emu.load_rom(&[0; 4096]).is_err());
+}